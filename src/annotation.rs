@@ -0,0 +1,822 @@
+//! Text labels and markers pinned to points in the world, drawn over the
+//! viewport.
+//!
+//! Annotations are projected with the camera and painted with egui. Whether
+//! each one is hidden behind the scene is tested on the GPU against a depth
+//! buffer of the scene, read back a frame or more later, and labels that would
+//! overlap are moved around their markers or left out.
+
+use cgmath::{InnerSpace, Point3};
+use egui_wgpu::{self, wgpu};
+use serde::{Deserialize, Serialize};
+use std::{
+    collections::HashSet,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::{
+    camera::{Camera, CameraResources},
+    light::LightResources,
+    mesh::{VertexLayout, VertexTrait},
+    picking::PICK_FORMAT,
+    pipeline::{self, PipelineCache, DEPTH_FORMAT},
+    preprocessor::ShaderRegistry,
+    renderer::{Resource, ScenePass, SceneResources},
+    scene::Scene,
+    texture::TextureResource,
+};
+
+/// The most annotations tested for occlusion, the width of the results row.
+/// Any others are always shown.
+const MAX_TESTED: usize = 2048;
+
+/// Holds whether each anchor is visible, see `annotations.wgsl`.
+const RESULT_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// Written for anchors hidden behind the scene.
+const HIDDEN: u32 = 2;
+
+/// How opaque annotations hidden behind the scene are drawn, if at all.
+const OCCLUDED_OPACITY: f32 = 0.35;
+
+/// Radius of the markers, in points.
+const MARKER_RADIUS: f32 = 5.0;
+
+const FONT_SIZE: f32 = 13.0;
+
+/// Space between a label's text and its edges, in points.
+const LABEL_PADDING: egui::Vec2 = egui::vec2(4.0, 2.0);
+
+/// Refers to an annotation added with [`Annotations::add`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct AnnotationId(u64);
+
+/// The shape drawn at an annotation's position.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum Marker {
+    /// Just the label.
+    None,
+    Dot,
+    Ring,
+    Cross,
+}
+
+/// A label and marker at a point in the world.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Annotation {
+    pub position: Point3<f32>,
+    /// Left out if empty, leaving just the marker.
+    pub text: String,
+    pub marker: Marker,
+    /// Unmultiplied RGBA, for the marker and text.
+    pub color: [f32; 4],
+    /// When labels overlap, those with higher priority keep their place, then
+    /// those nearer the camera.
+    pub priority: i32,
+}
+
+impl Annotation {
+    /// A white dot labeled with `text`.
+    pub fn new(position: Point3<f32>, text: impl Into<String>) -> Self {
+        Self {
+            position,
+            text: text.into(),
+            marker: Marker::Dot,
+            color: [1.0; 4],
+            priority: 0,
+        }
+    }
+
+    pub fn marker(mut self, marker: Marker) -> Self {
+        self.marker = marker;
+        self
+    }
+
+    pub fn color(mut self, color: [f32; 4]) -> Self {
+        self.color = color;
+        self
+    }
+
+    pub fn priority(mut self, priority: i32) -> Self {
+        self.priority = priority;
+        self
+    }
+}
+
+/// How annotations are drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct AnnotationSettings {
+    pub visible: bool,
+    /// Whether annotations hidden behind the scene are drawn faded instead of
+    /// left out.
+    pub show_occluded: bool,
+    /// Whether labels that would overlap others are moved or left out.
+    pub declutter: bool,
+}
+
+impl Default for AnnotationSettings {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            show_occluded: true,
+            declutter: true,
+        }
+    }
+}
+
+/// The annotations in the viewport, shared between the app, whoever adds
+/// them, and [`AnnotationResources`].
+#[derive(Default)]
+pub struct Annotations {
+    annotations: Vec<(AnnotationId, Annotation)>,
+    next_id: u64,
+    /// Bumped on every change, so the occlusion test knows when to run again.
+    revision: u64,
+    /// The annotations found behind the scene by the last occlusion test.
+    occluded: HashSet<AnnotationId>,
+}
+
+impl Annotations {
+    pub fn add(&mut self, annotation: Annotation) -> AnnotationId {
+        let id = AnnotationId(self.next_id);
+        self.next_id += 1;
+        self.annotations.push((id, annotation));
+        self.revision += 1;
+        id
+    }
+
+    pub fn remove(&mut self, id: AnnotationId) -> Option<Annotation> {
+        let index = self
+            .annotations
+            .iter()
+            .position(|(other, _)| *other == id)?;
+        self.revision += 1;
+        Some(self.annotations.remove(index).1)
+    }
+
+    pub fn clear(&mut self) {
+        self.annotations.clear();
+        self.revision += 1;
+    }
+
+    pub fn get(&self, id: AnnotationId) -> Option<&Annotation> {
+        self.iter()
+            .find(|(other, _)| *other == id)
+            .map(|(_, annotation)| annotation)
+    }
+
+    pub fn get_mut(&mut self, id: AnnotationId) -> Option<&mut Annotation> {
+        self.revision += 1;
+        self.annotations
+            .iter_mut()
+            .find(|(other, _)| *other == id)
+            .map(|(_, annotation)| annotation)
+    }
+
+    pub fn len(&self) -> usize {
+        self.annotations.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.annotations.is_empty()
+    }
+
+    /// In the order they were added.
+    pub fn iter(&self) -> impl Iterator<Item = (AnnotationId, &Annotation)> {
+        self.annotations
+            .iter()
+            .map(|(id, annotation)| (*id, annotation))
+    }
+
+    /// Whether `id` was behind the scene when last tested, which lags the
+    /// camera by a frame or more.
+    pub fn is_occluded(&self, id: AnnotationId) -> bool {
+        self.occluded.contains(&id)
+    }
+}
+
+/// Draws the markers and labels of `annotations` over `viewport`, where the
+/// camera's image fills it.
+pub fn show(
+    ui: &egui::Ui,
+    viewport: egui::Rect,
+    camera: &Camera,
+    annotations: &Annotations,
+    settings: &AnnotationSettings,
+) {
+    if !settings.visible {
+        return;
+    }
+    let visible = viewport.intersect(ui.clip_rect());
+    let mut shown: Vec<_> = annotations
+        .iter()
+        .filter_map(|(id, annotation)| {
+            let position = camera
+                .project(annotation.position, viewport)
+                .filter(|&position| visible.contains(position))?;
+            let occluded = annotations.is_occluded(id);
+            if occluded && !settings.show_occluded {
+                return None;
+            }
+            let distance = (annotation.position - camera.eye).magnitude2();
+            Some((annotation, position, distance, occluded))
+        })
+        .collect();
+    shown.sort_by(|a, b| b.0.priority.cmp(&a.0.priority).then(a.2.total_cmp(&b.2)));
+
+    // Labels keep clear of every marker, and of the labels placed before them.
+    let mut taken: Vec<egui::Rect> = if settings.declutter {
+        shown
+            .iter()
+            .filter(|(annotation, ..)| annotation.marker != Marker::None)
+            .map(|&(_, position, ..)| {
+                egui::Rect::from_center_size(position, egui::Vec2::splat(MARKER_RADIUS * 2.0))
+            })
+            .collect()
+    } else {
+        Vec::new()
+    };
+    let painter = ui.painter_at(visible);
+    for (annotation, position, _, occluded) in shown {
+        let [r, g, b, a] = annotation.color;
+        let opacity = if occluded { OCCLUDED_OPACITY } else { 1.0 };
+        let color = egui::Color32::from(egui::Rgba::from_rgba_unmultiplied(r, g, b, a))
+            .gamma_multiply(opacity);
+        paint_marker(&painter, annotation.marker, position, color);
+        if annotation.text.is_empty() {
+            continue;
+        }
+
+        let galley = painter.layout_no_wrap(
+            annotation.text.clone(),
+            egui::FontId::proportional(FONT_SIZE),
+            color,
+        );
+        let places = label_places(
+            position,
+            galley.size() + LABEL_PADDING * 2.0,
+            annotation.marker,
+        );
+        let place = if settings.declutter {
+            places
+                .into_iter()
+                .find(|place| !taken.iter().any(|other| other.intersects(*place)))
+        } else {
+            Some(places[0])
+        };
+        let Some(place) = place else {
+            continue;
+        };
+        taken.push(place);
+        painter.rect_filled(
+            place,
+            3.0,
+            egui::Color32::from_black_alpha(170).gamma_multiply(opacity),
+        );
+        painter.galley(place.min + LABEL_PADDING, galley, color);
+    }
+}
+
+fn paint_marker(painter: &egui::Painter, marker: Marker, center: egui::Pos2, color: egui::Color32) {
+    let stroke = egui::Stroke::new(2.0, color);
+    match marker {
+        Marker::None => {}
+        Marker::Dot => {
+            let outline = egui::Stroke::new(1.0, egui::Color32::from_black_alpha(color.a()));
+            painter.circle(center, MARKER_RADIUS * 0.8, color, outline);
+        }
+        Marker::Ring => {
+            painter.circle_stroke(center, MARKER_RADIUS, stroke);
+        }
+        Marker::Cross => {
+            let corner = egui::Vec2::splat(MARKER_RADIUS);
+            let other = egui::vec2(MARKER_RADIUS, -MARKER_RADIUS);
+            painter.line_segment([center - corner, center + corner], stroke);
+            painter.line_segment([center - other, center + other], stroke);
+        }
+    }
+}
+
+/// Where a label of `size` can go around its marker at `anchor`, best first:
+/// to the right, the left, above and below.
+fn label_places(anchor: egui::Pos2, size: egui::Vec2, marker: Marker) -> [egui::Rect; 4] {
+    let gap = if marker == Marker::None {
+        0.0
+    } else {
+        MARKER_RADIUS + 3.0
+    };
+    [
+        egui::vec2(gap, -size.y / 2.0),
+        egui::vec2(-gap - size.x, -size.y / 2.0),
+        egui::vec2(-size.x / 2.0, -gap - size.y),
+        egui::vec2(-size.x / 2.0, gap),
+    ]
+    .map(|offset| egui::Rect::from_min_size(anchor + offset, size))
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OcclusionParams {
+    view_proj: [[f32; 4]; 4],
+    eye: [f32; 4],
+    size: [f32; 2],
+    columns: f32,
+    _padding: f32,
+}
+
+/// The scene's depth, sized to the viewport, and what the anchors read it
+/// through.
+struct DepthTarget {
+    size: [u32; 2],
+    /// The picking pipelines draw ids too, which are ignored.
+    ids_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    bind_group: wgpu::BindGroup,
+}
+
+/// The row of results, one per anchor, and the buffers around it.
+struct ResultTarget {
+    columns: u32,
+    texture: wgpu::Texture,
+    view: wgpu::TextureView,
+    positions: wgpu::Buffer,
+    readback_buffer: wgpu::Buffer,
+}
+
+/// What the last test was run for, to skip testing again until something
+/// changes.
+#[derive(PartialEq)]
+struct TestedState {
+    view_proj: [[f32; 4]; 4],
+    size: [u32; 2],
+    scene_revision: u64,
+    annotations_revision: u64,
+}
+
+/// Results being copied back from the GPU.
+struct InFlight {
+    /// Set once the readback buffer is mapped, to whether mapping succeeded.
+    mapped: Arc<OnceLock<bool>>,
+    /// The annotation in each column.
+    ids: Vec<AnnotationId>,
+}
+
+pub struct AnnotationResources {
+    annotations: Arc<RwLock<Annotations>>,
+    camera: Arc<RwLock<Camera>>,
+    scene: Arc<RwLock<Scene>>,
+    bind_group_layout: wgpu::BindGroupLayout,
+    params_buffer: wgpu::Buffer,
+    pipeline: wgpu::RenderPipeline,
+    /// Kept to rebuild the pipeline when the shaders change on disk.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    layout: wgpu::PipelineLayout,
+    depth_target: Option<DepthTarget>,
+    result_target: Option<ResultTarget>,
+    tested: Option<TestedState>,
+    in_flight: Option<InFlight>,
+}
+
+impl Resource for AnnotationResources {}
+
+impl AnnotationResources {
+    pub fn new(
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        annotations: Arc<RwLock<Annotations>>,
+        camera: Arc<RwLock<Camera>>,
+        scene: Arc<RwLock<Scene>>,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("annotations_bind_group_layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX_FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                wgpu::BindGroupLayoutEntry {
+                    binding: 1,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        // Loading from depth textures doesn't translate to GLSL.
+                        sample_type: wgpu::TextureSampleType::Float { filterable: false },
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        multisampled: false,
+                    },
+                    count: None,
+                },
+            ],
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("annotations_params"),
+            size: std::mem::size_of::<OcclusionParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("annotations"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, shaders, &layout)
+            .expect("built-in shaders should compile");
+
+        Self {
+            annotations,
+            camera,
+            scene,
+            bind_group_layout,
+            params_buffer,
+            pipeline,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            layout,
+            depth_target: None,
+            result_target: None,
+            tested: None,
+            in_flight: None,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        layout: &wgpu::PipelineLayout,
+    ) -> Result<wgpu::RenderPipeline, String> {
+        let shader = pipeline::shader_module(device, shaders, "annotations", &Default::default())?;
+        let vertex_layout = VertexLayout::custom(
+            wgpu::VertexStepMode::Instance,
+            &[(0, wgpu::VertexFormat::Float32x3)],
+        );
+        Ok(
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("annotations_occlusion"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[vertex_layout.desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(RESULT_FORMAT.into())],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::PointList,
+                    ..Default::default()
+                },
+                depth_stencil: None,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }),
+        )
+    }
+
+    /// Hands the results of the last test to [`Annotations`] if the GPU has
+    /// finished copying them.
+    fn read_back(&mut self, device: &wgpu::Device) {
+        let Some(in_flight) = &self.in_flight else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let Some(&mapped) = in_flight.mapped.get() else {
+            return;
+        };
+
+        if mapped {
+            let readback_buffer = &self.result_target.as_ref().unwrap().readback_buffer;
+            let data = readback_buffer.slice(..).get_mapped_range();
+            let results: &[u32] = bytemuck::cast_slice(&data);
+            self.annotations.write().unwrap().occluded = in_flight
+                .ids
+                .iter()
+                .zip(results)
+                .filter(|&(_, &result)| result == HIDDEN)
+                .map(|(&id, _)| id)
+                .collect();
+            drop(data);
+            readback_buffer.unmap();
+        }
+        self.in_flight = None;
+    }
+
+    /// Uploads the anchors if they need testing, making sure the targets fit
+    /// them and the viewport, and returns the annotations in each column.
+    fn begin(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        viewport: egui::Rect,
+        settings: &AnnotationSettings,
+    ) -> Option<Vec<AnnotationId>> {
+        if self.in_flight.is_some() || !settings.visible {
+            return None;
+        }
+        let size = [viewport.width() as u32, viewport.height() as u32];
+        if size[0] == 0 || size[1] == 0 {
+            return None;
+        }
+        let annotations = self.annotations.read().unwrap();
+        if annotations.is_empty() {
+            return None;
+        }
+        let camera = self.camera.read().unwrap();
+        let view_proj = camera.build_view_projection_matrix();
+        let state = TestedState {
+            view_proj: view_proj.into(),
+            size,
+            scene_revision: self.scene.read().unwrap().revision(),
+            annotations_revision: annotations.revision,
+        };
+        if self.tested.as_ref() == Some(&state) {
+            return None;
+        }
+        self.tested = Some(state);
+
+        if self.depth_target.as_ref().map(|target| target.size) != Some(size) {
+            self.depth_target = Some(DepthTarget::new(
+                device,
+                &self.bind_group_layout,
+                &self.params_buffer,
+                size,
+            ));
+        }
+        let count = annotations.len().min(MAX_TESTED);
+        let columns = (count as u32).next_power_of_two().max(64);
+        if self
+            .result_target
+            .as_ref()
+            .map_or(true, |target| target.columns < columns)
+        {
+            self.result_target = Some(ResultTarget::new(device, columns));
+        }
+
+        let params = OcclusionParams {
+            view_proj: view_proj.into(),
+            eye: camera.eye.to_homogeneous().into(),
+            size: [size[0] as f32, size[1] as f32],
+            columns: self.result_target.as_ref().unwrap().columns as f32,
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+        let (ids, positions): (Vec<_>, Vec<_>) = annotations
+            .iter()
+            .take(count)
+            .map(|(id, annotation)| {
+                let position: [f32; 3] = annotation.position.into();
+                (id, position)
+            })
+            .unzip();
+        queue.write_buffer(
+            &self.result_target.as_ref().unwrap().positions,
+            0,
+            bytemuck::cast_slice(&positions),
+        );
+        Some(ids)
+    }
+}
+
+impl DepthTarget {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params: &wgpu::Buffer,
+        size: [u32; 2],
+    ) -> Self {
+        let create = |label, format, usage| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let ids_view = create(
+            "annotations_ids",
+            PICK_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        let depth_view = create(
+            "annotations_depth",
+            DEPTH_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING,
+        );
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("annotations_bind_group"),
+            layout,
+            entries: &[
+                wgpu::BindGroupEntry {
+                    binding: 0,
+                    resource: params.as_entire_binding(),
+                },
+                wgpu::BindGroupEntry {
+                    binding: 1,
+                    resource: wgpu::BindingResource::TextureView(&depth_view),
+                },
+            ],
+        });
+        Self {
+            size,
+            ids_view,
+            depth_view,
+            bind_group,
+        }
+    }
+}
+
+impl ResultTarget {
+    fn new(device: &wgpu::Device, columns: u32) -> Self {
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            label: Some("annotations_results"),
+            size: wgpu::Extent3d {
+                width: columns,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: RESULT_FORMAT,
+            usage: wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+            view_formats: &[],
+        });
+        let buffer = |label, size, usage| {
+            device.create_buffer(&wgpu::BufferDescriptor {
+                label: Some(label),
+                size,
+                usage,
+                mapped_at_creation: false,
+            })
+        };
+        Self {
+            columns,
+            view: texture.create_view(&wgpu::TextureViewDescriptor::default()),
+            texture,
+            positions: buffer(
+                "annotations_positions",
+                (columns as usize * std::mem::size_of::<[f32; 3]>()) as u64,
+                wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            ),
+            readback_buffer: buffer(
+                "annotations_readback",
+                (columns as usize * std::mem::size_of::<u32>()) as u64,
+                wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            ),
+        }
+    }
+}
+
+/// Tests which annotations are behind the scene, if anything moved since the
+/// last test, and starts copying the results back to the CPU.
+///
+/// Must follow the preparation of the scene, camera and lights for this
+/// frame, including the picking pipelines.
+pub(crate) fn render(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    viewport: egui::Rect,
+    settings: &AnnotationSettings,
+    resources: &mut egui_wgpu::CallbackResources,
+) {
+    let ids = {
+        let annotation_resources: &mut AnnotationResources = resources.get_mut().unwrap();
+        annotation_resources.read_back(device);
+        match annotation_resources.begin(device, queue, viewport, settings) {
+            Some(ids) => ids,
+            None => return,
+        }
+    };
+
+    let mapped = {
+        let annotation_resources: &AnnotationResources = resources.get().unwrap();
+        let scene_resources: &SceneResources = resources.get().unwrap();
+        let pipeline_cache: &PipelineCache = resources.get().unwrap();
+        let camera_resources: &CameraResources = resources.get().unwrap();
+        let light_resources: &LightResources = resources.get().unwrap();
+        let texture_resource: &TextureResource = resources.get().unwrap();
+        let depth_target = annotation_resources.depth_target.as_ref().unwrap();
+        let result_target = annotation_resources.result_target.as_ref().unwrap();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("annotations"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("annotations_depth"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &depth_target.ids_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Discard,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &depth_target.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Store,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            camera_resources.paint(&mut render_pass);
+            light_resources.paint(&mut render_pass);
+            scene_resources.paint(
+                &mut render_pass,
+                ScenePass::Picking,
+                pipeline_cache,
+                texture_resource,
+            );
+        }
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("annotations_occlusion"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &result_target.view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(&annotation_resources.pipeline);
+            render_pass.set_bind_group(0, &depth_target.bind_group, &[]);
+            render_pass.set_vertex_buffer(0, result_target.positions.slice(..));
+            render_pass.draw(0..1, 0..ids.len() as u32);
+        }
+        encoder.copy_texture_to_buffer(
+            result_target.texture.as_image_copy(),
+            wgpu::ImageCopyBuffer {
+                buffer: &result_target.readback_buffer,
+                layout: wgpu::ImageDataLayout::default(),
+            },
+            wgpu::Extent3d {
+                width: result_target.columns,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        // Mapping has to wait for the copy to be submitted, so this can't go
+        // through egui's encoder.
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(OnceLock::new());
+        result_target
+            .readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, {
+                let mapped = Arc::clone(&mapped);
+                move |result| {
+                    if let Err(error) = &result {
+                        log::error!("Failed to read back annotation occlusion: {error}");
+                    }
+                    let _ = mapped.set(result.is_ok());
+                }
+            });
+        mapped
+    };
+    let annotation_resources: &mut AnnotationResources = resources.get_mut().unwrap();
+    annotation_resources.in_flight = Some(InFlight { mapped, ids });
+}
+
+#[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+impl crate::hot_reload::ReloadPipelines for AnnotationResources {
+    type Pipelines = wgpu::RenderPipeline;
+
+    fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+    ) -> Result<Self::Pipelines, String> {
+        Self::create_pipeline(device, shaders, &self.layout)
+    }
+
+    fn set_pipelines(&mut self, pipeline: Self::Pipelines) {
+        self.pipeline = pipeline;
+    }
+}
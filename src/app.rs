@@ -1,19 +1,150 @@
 use crate::{
+    annotation::{self, AnnotationResources, Annotations},
     camera::{self, CameraResources},
-    renderer::{self, create_render_pipeline, CustomTriangleCallback, RenderResources},
+    clipping::ClipSettings,
+    debug_draw::{DebugDraw, DebugDrawResources, DebugOverlays},
+    gizmo::Gizmo,
+    grid::{self, GridResources},
+    history::History,
+    inspector::Inspector,
+    light::{self, LightResources, Lights},
+    measure::Measure,
+    mesh::Mesh,
+    outline::{self, OutlineResources},
+    outliner::{DockSide, Outliner},
+    picking::{PickResources, Picking},
+    preprocessor::ShaderRegistry,
+    primitive::Primitive,
+    raycast::{self, Hit},
+    renderer::{self, CustomTriangleCallback, RenderMode, RenderSettings, SceneResources},
+    scene::{Instance, NodeId, Scene},
+    scene_file::{MeshSource, SceneFile},
+    selection::{Selection, SelectionSet},
     texture::TextureResource,
+    transform::Transform,
+    view_cube::ViewCube,
 };
+use cgmath::{Rotation3, SquareMatrix};
 use egui_wgpu::{self};
-use std::sync::{Arc, RwLock};
-/// We derive Deserialize/Serialize so we can persist app state on shutdown.
+use std::{
+    path::{Path, PathBuf},
+    sync::{Arc, RwLock, RwLockReadGuard},
+};
+/// How many scene files File > Open Recent remembers.
+const MAX_RECENT_FILES: usize = 10;
+
+const UNDO_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::COMMAND, egui::Key::Z);
+const REDO_SHORTCUT: egui::KeyboardShortcut = egui::KeyboardShortcut::new(
+    egui::Modifiers::COMMAND.plus(egui::Modifiers::SHIFT),
+    egui::Key::Z,
+);
+const DELETE_SHORTCUT: egui::KeyboardShortcut =
+    egui::KeyboardShortcut::new(egui::Modifiers::NONE, egui::Key::Delete);
+
+/// The app state restored on the next launch.
 ///
+/// We derive Deserialize/Serialize so we can persist it on shutdown.
+#[derive(serde::Deserialize, serde::Serialize)]
+#[serde(default)]
+struct PersistedState {
+    viewport_height: f32,
+    viewport_width: f32,
+    render_settings: RenderSettings,
+    camera: camera::Camera,
+    dark_mode: bool,
+    recent_files: Vec<PathBuf>,
+    outliner: Outliner,
+    inspector: Inspector,
+    gizmo: Gizmo,
+    measure: Measure,
+    debug_overlays: DebugOverlays,
+}
+
+impl Default for PersistedState {
+    fn default() -> Self {
+        Self {
+            viewport_height: 800.0,
+            viewport_width: 1280.0,
+            render_settings: RenderSettings::default(),
+            camera: camera::Camera::new(),
+            dark_mode: true,
+            recent_files: Vec::new(),
+            outliner: Outliner::default(),
+            inspector: Inspector::default(),
+            gizmo: Gizmo::default(),
+            measure: Measure::default(),
+            debug_overlays: DebugOverlays::default(),
+        }
+    }
+}
 
 pub struct TemplateApp {
-    // Example stuff:
     viewport_height: f32,
     viewport_width: f32,
     outer_rect: Option<egui::Rect>,
+    /// Where the scene was drawn last frame, in points. The projection spans
+    /// only the part of the canvas on screen, like egui's viewport does.
+    viewport_rect: Option<egui::Rect>,
+    /// What is under the cursor in the viewport, found while measuring.
+    hovered: Option<Hit>,
+    /// What `hovered` was found for.
+    hover_query: Option<HoverQuery>,
+    /// The name of the node and the point last clicked in the viewport.
+    clicked_point: Option<String>,
+    render_settings: RenderSettings,
+    dark_mode: bool,
+    /// Scene files opened or saved, most recent first.
+    recent_files: Vec<PathBuf>,
+    camera: Arc<RwLock<camera::Camera>>,
     camera_controller: Arc<RwLock<camera::CameraController>>,
+    lights: Arc<RwLock<Lights>>,
+    scene: Arc<RwLock<Scene>>,
+    /// Every edit to the scene, for undo and redo.
+    history: History,
+    picking: Arc<RwLock<Picking>>,
+    selection: SelectionSet,
+    /// The modifiers held when the pending pick was requested.
+    pick_modifiers: egui::Modifiers,
+    outliner: Outliner,
+    inspector: Inspector,
+    gizmo: Gizmo,
+    measure: Measure,
+    view_cube: ViewCube,
+    /// Shapes added from outside the app, kept until they're cleared.
+    debug_draw: Arc<RwLock<DebugDraw>>,
+    /// The app's own shapes, redrawn every frame.
+    debug_shapes: Arc<RwLock<DebugDraw>>,
+    debug_overlays: DebugOverlays,
+    /// A copy of the camera whose frustum is drawn, to look at it from
+    /// elsewhere.
+    frozen_camera: Option<camera::Camera>,
+    annotations: Arc<RwLock<Annotations>>,
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    renderer: renderer::Renderer,
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    shader_watcher: crate::hot_reload::ShaderWatcher,
+    /// The last shader compile error, shown until the shader compiles again.
+    shader_error: Option<String>,
+    scene_file_dialog: Option<SceneFileDialog>,
+}
+
+/// Asks for the path of a scene file to open or save.
+struct SceneFileDialog {
+    save: bool,
+    path: String,
+    /// Why the last attempt failed.
+    error: Option<String>,
+}
+
+/// Everything the surface under the cursor depends on.
+#[derive(PartialEq)]
+struct HoverQuery {
+    pos: egui::Pos2,
+    viewport: egui::Rect,
+    view_projection: cgmath::Matrix4<f32>,
+    revision: u64,
+    clipping: ClipSettings,
 }
 
 impl TemplateApp {
@@ -24,49 +155,614 @@ impl TemplateApp {
 
         // Load previous app state (if any).
         // Note that you must enable the `persistence` feature for this to work.
+        let state: PersistedState = cc
+            .storage
+            .and_then(|storage| eframe::get_value(storage, eframe::APP_KEY))
+            .unwrap_or_default();
+        cc.egui_ctx.set_visuals(if state.dark_mode {
+            egui::Visuals::dark()
+        } else {
+            egui::Visuals::light()
+        });
 
         let wgpu_render_state = cc.wgpu_render_state.as_ref().unwrap().clone();
         let camera_controller = Arc::new(RwLock::new(camera::CameraController::new(0.2)));
 
-        let (camera_bind_group, camera_bind_group_layout, camera_buffer, camera_uniform) =
-            camera::CameraResources::create_camera_bind_group(&wgpu_render_state.device);
+        let (
+            camera_bind_group,
+            camera_bind_group_layout,
+            camera_buffer,
+            camera_uniform,
+            settings_buffer,
+        ) = camera::CameraResources::create_camera_bind_group(&wgpu_render_state.device);
 
         let image_bytes = include_bytes!("happy-tree.png");
         let mut texture_resource = TextureResource::new(&wgpu_render_state, image_bytes).unwrap();
 
         let texture_bind_group_layout = texture_resource.get_bind_group(&wgpu_render_state);
+        let light_bind_group_layout = LightResources::bind_group_layout(&wgpu_render_state.device);
 
-        let pipeline = Arc::new(create_render_pipeline(
-            &wgpu_render_state,
-            &[&camera_bind_group_layout, &texture_bind_group_layout],
-        ));
+        let camera = Arc::new(RwLock::new(state.camera));
 
-        let camera = Arc::new(RwLock::new(camera::Camera::new()));
+        let mut lights = Lights::default();
+        add_demo_lights(&mut lights);
+        let lights = Arc::new(RwLock::new(lights));
 
-        let render_state = renderer::Renderer::new(&wgpu_render_state, &pipeline);
+        let mut scene = Scene::default();
+        add_demo_nodes(&mut scene);
+        let scene = Arc::new(RwLock::new(scene));
+        let scene_resources = SceneResources::new(&wgpu_render_state.device, Arc::clone(&scene));
 
-        render_state.add_resource(RenderResources::new(&wgpu_render_state, &pipeline));
+        let shaders = ShaderRegistry::default();
+        let light_resources = LightResources::new(
+            &wgpu_render_state.device,
+            &light_bind_group_layout,
+            &shaders,
+            Arc::clone(&camera),
+            Arc::clone(&lights),
+        );
+
+        let outline_resources = OutlineResources::new(
+            &wgpu_render_state.device,
+            wgpu_render_state.target_format,
+            &shaders,
+        );
+
+        let debug_draw = Arc::new(RwLock::new(DebugDraw::default()));
+        let debug_shapes = Arc::new(RwLock::new(DebugDraw::default()));
+        let debug_draw_resources = DebugDrawResources::new(
+            &wgpu_render_state.device,
+            wgpu_render_state.target_format,
+            &shaders,
+            &camera_bind_group_layout,
+            vec![Arc::clone(&debug_draw), Arc::clone(&debug_shapes)],
+        );
+
+        let grid_resources = GridResources::new(
+            &wgpu_render_state.device,
+            wgpu_render_state.target_format,
+            &shaders,
+            Arc::clone(&camera),
+        );
+
+        let annotations = Arc::new(RwLock::new(Annotations::default()));
+        let annotation_resources = AnnotationResources::new(
+            &wgpu_render_state.device,
+            &shaders,
+            Arc::clone(&annotations),
+            Arc::clone(&camera),
+            Arc::clone(&scene),
+        );
+
+        let render_state = renderer::Renderer::new(
+            &wgpu_render_state,
+            &[
+                &camera_bind_group_layout,
+                &texture_bind_group_layout,
+                &light_bind_group_layout,
+                scene_resources.object_bind_group_layout(),
+            ],
+            shaders,
+        );
+
+        render_state.add_resource(scene_resources);
         render_state.add_resource(texture_resource);
         render_state.add_resource(CameraResources {
             camera_uniform,
             camera_buffer,
+            settings_buffer,
             camera: Arc::clone(&camera),
             camera_controller: Arc::clone(&camera_controller),
             camera_bind_group,
-            pipeline: Arc::clone(&pipeline),
         });
+        render_state.add_resource(light_resources);
+        render_state.add_resource(outline_resources);
+        render_state.add_resource(debug_draw_resources);
+        render_state.add_resource(grid_resources);
+        render_state.add_resource(annotation_resources);
+        let picking = Arc::new(RwLock::new(Picking::default()));
+        render_state.add_resource(PickResources::new(
+            &wgpu_render_state.device,
+            Arc::clone(&picking),
+        ));
 
         Self {
-            viewport_height: 800.0,
-            viewport_width: 1280.0,
+            viewport_height: state.viewport_height,
+            viewport_width: state.viewport_width,
+            camera,
             camera_controller,
             outer_rect: None,
+            viewport_rect: None,
+            hovered: None,
+            hover_query: None,
+            clicked_point: None,
+            render_settings: state.render_settings,
+            dark_mode: state.dark_mode,
+            recent_files: state.recent_files,
+            lights,
+            scene,
+            history: History::default(),
+            picking,
+            selection: SelectionSet::default(),
+            pick_modifiers: egui::Modifiers::NONE,
+            outliner: state.outliner,
+            inspector: state.inspector,
+            gizmo: state.gizmo,
+            measure: state.measure,
+            view_cube: ViewCube::default(),
+            debug_draw,
+            debug_shapes,
+            debug_overlays: state.debug_overlays,
+            annotations,
+            frozen_camera: None,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            renderer: render_state,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            shader_watcher: crate::hot_reload::ShaderWatcher::new(crate::hot_reload::SHADER_DIR),
+            shader_error: None,
+            scene_file_dialog: None,
+        }
+    }
+
+    /// The first thing under `screen_pos` in the viewport, in points, tested
+    /// against the scene's triangles on the CPU.
+    ///
+    /// Unlike picking, this answers straight away, and also says where on the
+    /// surface the hit is.
+    pub fn raycast(&self, screen_pos: egui::Pos2) -> Option<Hit> {
+        let ray = self
+            .camera
+            .read()
+            .unwrap()
+            .screen_ray(screen_pos, self.viewport_rect?)?;
+        let clipping = &self.render_settings.clipping;
+        raycast::raycast(&self.scene.read().unwrap(), &ray, |point| {
+            !clipping.clipped(point)
+        })
+    }
+
+    /// The labels and markers drawn over the viewport, for adding to from
+    /// outside the app.
+    pub fn annotations(&self) -> Arc<RwLock<Annotations>> {
+        Arc::clone(&self.annotations)
+    }
+
+    /// Lines drawn over the scene, for adding to from outside the app.
+    ///
+    /// Shapes stay until they're cleared, so they can be added once or
+    /// rebuilt every frame. The app's own debug shapes are kept apart and
+    /// never clear these.
+    pub fn debug_draw(&self) -> Arc<RwLock<DebugDraw>> {
+        Arc::clone(&self.debug_draw)
+    }
+
+    /// The scene drawn in the viewport, for reading from outside the app.
+    ///
+    /// It can't be changed through here, so that every change can be undone
+    /// from the Edit menu. Add to it with [`TemplateApp::add_instances`].
+    pub fn scene(&self) -> RwLockReadGuard<'_, Scene> {
+        self.scene.read().unwrap()
+    }
+
+    /// Adds a root node drawing `mesh` once for every instance, as with
+    /// [`Scene::add_colored_instances`], as a step that can be undone.
+    ///
+    /// A mesh already added from the same `source` is shared instead, and
+    /// meshes built in code are saved with [`MeshSource::Inline`].
+    pub fn add_instances(
+        &mut self,
+        mesh: Mesh,
+        source: MeshSource,
+        instances: &[Instance],
+    ) -> NodeId {
+        let mut scene = self.scene.write().unwrap();
+        let mesh = scene
+            .find_mesh(&source)
+            .unwrap_or_else(|| scene.add_mesh(mesh, source));
+        let node = scene.add_colored_instances(mesh, instances);
+        self.history.added(&scene, node);
+        node
+    }
+
+    /// Raycasts the scene under `pointer` for the measure tool to snap to.
+    ///
+    /// Testing every triangle is too slow to do every frame for nothing, so
+    /// this only happens while measuring, and only once the pointer, the
+    /// camera or the scene has changed.
+    fn update_hovered(&mut self, pointer: Option<egui::Pos2>) {
+        let query = pointer
+            .filter(|_| self.measure.active)
+            .zip(self.viewport_rect)
+            .map(|(pos, viewport)| HoverQuery {
+                pos,
+                viewport,
+                view_projection: self.camera.read().unwrap().build_view_projection_matrix(),
+                revision: self.scene.read().unwrap().revision(),
+                clipping: self.render_settings.clipping,
+            });
+        if query != self.hover_query {
+            self.hovered = query.as_ref().and_then(|query| self.raycast(query.pos));
+            self.hover_query = query;
+        }
+    }
+
+    fn open_scene(&mut self, path: &Path) -> anyhow::Result<()> {
+        let base_dir = path.parent().unwrap_or(Path::new(""));
+        let (scene, lights, camera) = SceneFile::open(path)?.load(base_dir)?;
+        self.scene.write().unwrap().replace(scene);
+        self.history.clear();
+        self.selection.clear();
+        self.clicked_point = None;
+        *self.lights.write().unwrap() = lights;
+        if let Some(camera) = camera {
+            *self.camera.write().unwrap() = camera;
+        }
+        self.add_recent_file(path);
+        Ok(())
+    }
+
+    fn save_scene(&mut self, path: &Path) -> anyhow::Result<()> {
+        SceneFile::new(
+            &self.scene.read().unwrap(),
+            &self.lights.read().unwrap(),
+            &self.camera.read().unwrap(),
+        )
+        .save(path)?;
+        self.add_recent_file(path);
+        Ok(())
+    }
+
+    fn undo(&mut self) {
+        self.history.undo(
+            &mut self.scene.write().unwrap(),
+            &mut self.lights.write().unwrap(),
+            &mut self.render_settings.clipping,
+        );
+    }
+
+    fn redo(&mut self) {
+        self.history.redo(
+            &mut self.scene.write().unwrap(),
+            &mut self.lights.write().unwrap(),
+            &mut self.render_settings.clipping,
+        );
+    }
+
+    /// Deletes the selected nodes, along with their children, and the
+    /// selected lights.
+    fn delete_selected(&mut self) {
+        let nodes: Vec<_> = self.selection.nodes().collect();
+        let lights: Vec<_> = self.selection.lights().collect();
+        self.history.delete(
+            &mut self.scene.write().unwrap(),
+            &nodes,
+            &mut self.lights.write().unwrap(),
+            &lights,
+        );
+        // The lights after the deleted ones have moved down, so the indices
+        // left selected would point at others.
+        self.selection
+            .retain(|item| !matches!(item, Selection::Light(_)));
+    }
+
+    /// Adds a light above the point the camera looks at, shining down if it
+    /// is a spot light, and selects it.
+    fn add_light(&mut self, spot: bool) {
+        let position = self.camera.read().unwrap().target + cgmath::Vector3::unit_y();
+        let light = if spot {
+            light::Light::Spot(light::SpotLight {
+                position,
+                direction: -cgmath::Vector3::unit_y(),
+                color: [1.0, 1.0, 1.0],
+                intensity: 1.0,
+                range: 3.0,
+                inner_angle: cgmath::Deg(10.0),
+                outer_angle: cgmath::Deg(20.0),
+            })
+        } else {
+            light::Light::Point(light::PointLight {
+                position,
+                color: [1.0, 1.0, 1.0],
+                intensity: 1.0,
+                range: 3.0,
+            })
+        };
+        let index = self
+            .history
+            .add_light(&mut self.lights.write().unwrap(), light);
+        if let Some(index) = index {
+            self.selection
+                .click(Some(Selection::Light(index)), egui::Modifiers::NONE);
+        }
+    }
+
+    /// Adds a node under the active node, or at the root, and selects it.
+    ///
+    /// With a `primitive`, the node draws it once, sharing the mesh with
+    /// earlier nodes of the same primitive.
+    fn add_node(&mut self, primitive: Option<Primitive>) {
+        let parent = match self.selection.active() {
+            Some(Selection::Node(id)) => Some(id),
+            _ => None,
+        };
+        let mut scene = self.scene.write().unwrap();
+        let node = match primitive {
+            None => scene.add_node("Node", parent),
+            Some(primitive) => {
+                let source = MeshSource::Primitive(primitive);
+                let mesh = scene
+                    .find_mesh(&source)
+                    .unwrap_or_else(|| scene.add_mesh(primitive.mesh(), source));
+                let node = scene.add_node(primitive.label(), parent);
+                let node_mut = scene.node_mut(node);
+                node_mut.mesh = Some(mesh);
+                node_mut.instances = vec![Transform::default().into()];
+                node
+            }
+        };
+        self.history.added(&scene, node);
+        self.selection
+            .click(Some(Selection::Node(node)), egui::Modifiers::NONE);
+    }
+
+    /// Handles the Edit menu shortcuts, unless a text field has the keyboard
+    /// and they belong to it.
+    fn handle_edit_shortcuts(&mut self, ctx: &egui::Context) {
+        if ctx.memory(|memory| memory.focus().is_some()) {
+            return;
+        }
+        // Redo first, as undo would also match with shift held.
+        if ctx.input_mut(|i| i.consume_shortcut(&REDO_SHORTCUT)) {
+            self.redo();
+        } else if ctx.input_mut(|i| i.consume_shortcut(&UNDO_SHORTCUT)) {
+            self.undo();
+        }
+        if ctx.input_mut(|i| i.consume_shortcut(&DELETE_SHORTCUT)) {
+            self.delete_selected();
+        }
+    }
+
+    fn edit_menu(&mut self, ui: &mut egui::Ui) {
+        let ctx = ui.ctx().clone();
+        let undo = self.history.undo_label();
+        let button =
+            egui::Button::new(undo.map_or("Undo".to_owned(), |label| format!("Undo {label}")))
+                .shortcut_text(ctx.format_shortcut(&UNDO_SHORTCUT));
+        if ui.add_enabled(undo.is_some(), button).clicked() {
+            self.undo();
+            ui.close_menu();
+        }
+        let redo = self.history.redo_label();
+        let button =
+            egui::Button::new(redo.map_or("Redo".to_owned(), |label| format!("Redo {label}")))
+                .shortcut_text(ctx.format_shortcut(&REDO_SHORTCUT));
+        if ui.add_enabled(redo.is_some(), button).clicked() {
+            self.redo();
+            ui.close_menu();
+        }
+        ui.separator();
+        let button =
+            egui::Button::new("Delete").shortcut_text(ctx.format_shortcut(&DELETE_SHORTCUT));
+        let any =
+            self.selection.nodes().next().is_some() || self.selection.lights().next().is_some();
+        if ui.add_enabled(any, button).clicked() {
+            self.delete_selected();
+            ui.close_menu();
+        }
+    }
+
+    /// Replaces the app's own debug shapes with those chosen in View > Debug
+    /// Shapes, and the outlines of the clipping planes.
+    fn draw_debug_shapes(&self) {
+        let mut debug_draw = self.debug_shapes.write().unwrap();
+        debug_draw.clear();
+
+        // The grid shader draws the x and z axes on the ground, but the y
+        // axis has to stand up from it.
+        let grid = self.render_settings.grid;
+        if grid.visible && grid.axes {
+            let length = grid.fade_distance;
+            debug_draw.depth_test = true;
+            debug_draw.line(
+                cgmath::Point3::new(0.0, -length, 0.0),
+                cgmath::Point3::new(0.0, length, 0.0),
+                grid::AXIS_COLORS[1],
+            );
+        }
+
+        let overlays = self.debug_overlays;
+        debug_draw.depth_test = !overlays.x_ray;
+
+        let scene = self.scene.read().unwrap();
+        if overlays.bounds || overlays.axes {
+            let world_matrices = scene.world_matrices();
+            if overlays.bounds {
+                for (id, node) in scene.drawn_nodes() {
+                    let Some(bounds) = node.mesh.and_then(|mesh| scene.mesh_bounds(mesh)) else {
+                        continue;
+                    };
+                    for instance in &node.instances {
+                        let model = world_matrices[id.index()] * instance.transform.matrix();
+                        debug_draw.aabb(&bounds.aabb.transformed(&model), [1.0, 1.0, 0.3, 0.8]);
+                    }
+                }
+            }
+            if overlays.axes {
+                for id in self.selection.nodes() {
+                    debug_draw.axes(world_matrices[id.index()], 0.3);
+                }
+            }
+        }
+
+        if overlays.lights {
+            for light in self.lights.read().unwrap().iter() {
+                match light {
+                    light::Light::Point(light) => {
+                        let [r, g, b] = light.color;
+                        debug_draw.sphere(light.position, light.range, [r, g, b, 0.5]);
+                    }
+                    light::Light::Spot(light) => {
+                        let [r, g, b] = light.color;
+                        let direction = cgmath::InnerSpace::normalize(light.direction);
+                        debug_draw.arrow(
+                            light.position,
+                            light.position + direction * light.range,
+                            [r, g, b, 0.8],
+                        );
+                    }
+                }
+            }
+        }
+
+        if let Some(camera) = &self.frozen_camera {
+            debug_draw.frustum(camera, [0.8, 0.8, 0.8, 1.0]);
+        }
+
+        // Planes cut through whatever they are placed in, so they show
+        // through it.
+        debug_draw.depth_test = false;
+        self.render_settings
+            .clipping
+            .draw(&mut debug_draw, &self.selection);
+    }
+
+    fn add_recent_file(&mut self, path: &Path) {
+        self.recent_files.retain(|recent| recent != path);
+        self.recent_files.insert(0, path.to_owned());
+        self.recent_files.truncate(MAX_RECENT_FILES);
+    }
+
+    /// Opens a recent file, falling back to the Open Scene window to show errors.
+    fn open_recent_file(&mut self, path: &Path) {
+        if let Err(error) = self.open_scene(path) {
+            log::error!("{error:#}");
+            self.scene_file_dialog = Some(SceneFileDialog {
+                save: false,
+                path: path.display().to_string(),
+                error: Some(format!("{error:#}")),
+            });
+        }
+    }
+
+    fn show_scene_file_dialog(&mut self, ctx: &egui::Context) {
+        let Some(dialog) = &mut self.scene_file_dialog else {
+            return;
+        };
+        let title = if dialog.save {
+            "Save Scene"
+        } else {
+            "Open Scene"
+        };
+        let mut open = true;
+        let mut confirmed = false;
+        egui::Window::new(title)
+            .open(&mut open)
+            .collapsible(false)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    ui.label("Path:");
+                    let response = ui.text_edit_singleline(&mut dialog.path);
+                    confirmed =
+                        response.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+                });
+                if let Some(error) = &dialog.error {
+                    ui.colored_label(ui.visuals().error_fg_color, error);
+                }
+                confirmed |= ui.button(title).clicked();
+            });
+
+        if !open {
+            self.scene_file_dialog = None;
+        } else if confirmed {
+            let save = dialog.save;
+            let path = PathBuf::from(&dialog.path);
+            let result = if save {
+                self.save_scene(&path)
+            } else {
+                self.open_scene(&path)
+            };
+            match result {
+                Ok(()) => self.scene_file_dialog = None,
+                Err(error) => {
+                    log::error!("{error:#}");
+                    if let Some(dialog) = &mut self.scene_file_dialog {
+                        dialog.error = Some(format!("{error:#}"));
+                    }
+                }
+            }
         }
     }
 }
 
+/// Adds the pentagon with two smaller copies orbiting it, placed through a
+/// rotated parent node.
+fn add_demo_nodes(scene: &mut Scene) {
+    let pentagon = scene.add_mesh(renderer::pentagon(), MeshSource::Pentagon);
+    let root = scene.add_instances(pentagon, &[Transform::default()]);
+    scene.node_mut(root).name = "Pentagon".to_owned();
+
+    let orbit = scene.add_node("Orbit", Some(root));
+    scene.node_mut(orbit).transform.rotation = cgmath::Quaternion::from_angle_z(cgmath::Deg(30.0));
+    for (name, x) in [("Left moon", -0.7), ("Right moon", 0.7)] {
+        let moon = scene.add_node(name, Some(orbit));
+        let moon = scene.node_mut(moon);
+        moon.transform.translation = cgmath::Vector3::new(x, 0.0, 0.1);
+        moon.transform.scale = cgmath::Vector3::new(0.3, 0.3, 0.3);
+        moon.mesh = Some(pentagon);
+        moon.instances = vec![Transform::default().into()];
+    }
+}
+
+/// Fills the scene with a grid of colored point lights and a few spot lights.
+fn add_demo_lights(lights: &mut Lights) {
+    const GRID: usize = 16;
+    for i in 0..GRID * GRID {
+        let (x, y) = (i % GRID, i / GRID);
+        let hue = (i as f32 * 0.618_034).fract();
+        let color = egui::Rgba::from(egui::ecolor::Hsva::new(hue, 0.8, 1.0, 1.0));
+        lights.add(light::Light::Point(light::PointLight {
+            position: cgmath::Point3::new(
+                -1.0 + 2.0 * x as f32 / (GRID - 1) as f32,
+                -1.0 + 2.0 * y as f32 / (GRID - 1) as f32,
+                0.15,
+            ),
+            color: [color.r(), color.g(), color.b()],
+            intensity: 1.0,
+            range: 0.15,
+        }));
+    }
+
+    for x in [-0.5, 0.5] {
+        lights.add(light::Light::Spot(light::SpotLight {
+            position: cgmath::Point3::new(x, 0.0, 1.0),
+            direction: cgmath::Vector3::new(0.0, 0.0, -1.0),
+            color: [1.0, 1.0, 1.0],
+            intensity: 0.8,
+            range: 3.0,
+            inner_angle: cgmath::Deg(10.0),
+            outer_angle: cgmath::Deg(20.0),
+        }));
+    }
+}
+
 impl eframe::App for TemplateApp {
     /// Called by the frame work to save state before shutdown.
+    fn save(&mut self, storage: &mut dyn eframe::Storage) {
+        let state = PersistedState {
+            viewport_height: self.viewport_height,
+            viewport_width: self.viewport_width,
+            render_settings: self.render_settings,
+            camera: self.camera.read().unwrap().clone(),
+            dark_mode: self.dark_mode,
+            recent_files: self.recent_files.clone(),
+            outliner: self.outliner.clone(),
+            inspector: self.inspector.clone(),
+            gizmo: self.gizmo.clone(),
+            measure: self.measure.clone(),
+            debug_overlays: self.debug_overlays,
+        };
+        eframe::set_value(storage, eframe::APP_KEY, &state);
+    }
 
     /// Called each time the UI needs repainting, which may be many times per second.
     fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
@@ -81,23 +777,285 @@ impl eframe::App for TemplateApp {
                 let is_web = cfg!(target_arch = "wasm32");
                 if !is_web {
                     ui.menu_button("File", |ui| {
+                        for (label, save) in [("Open Scene…", false), ("Save Scene…", true)] {
+                            if ui.button(label).clicked() {
+                                let path = self
+                                    .scene_file_dialog
+                                    .take()
+                                    .map_or_else(|| "scene.ron".to_owned(), |dialog| dialog.path);
+                                self.scene_file_dialog = Some(SceneFileDialog {
+                                    save,
+                                    path,
+                                    error: None,
+                                });
+                                ui.close_menu();
+                            }
+                        }
+                        let mut recent = None;
+                        ui.add_enabled_ui(!self.recent_files.is_empty(), |ui| {
+                            ui.menu_button("Open Recent", |ui| {
+                                for path in &self.recent_files {
+                                    if ui.button(path.display().to_string()).clicked() {
+                                        recent = Some(path.clone());
+                                        ui.close_menu();
+                                    }
+                                }
+                            });
+                        });
+                        if let Some(path) = recent {
+                            self.open_recent_file(&path);
+                        }
+                        ui.separator();
                         if ui.button("Quit").clicked() {
                             ctx.send_viewport_cmd(egui::ViewportCommand::Close);
                         }
                     });
-                    ui.add_space(16.0);
                 }
+                ui.menu_button("Edit", |ui| self.edit_menu(ui));
+                ui.menu_button("Add", |ui| {
+                    if ui.button("Empty Node").clicked() {
+                        self.add_node(None);
+                        ui.close_menu();
+                    }
+                    ui.menu_button("Primitive", |ui| {
+                        for primitive in Primitive::DEFAULTS {
+                            if ui.button(primitive.label()).clicked() {
+                                self.add_node(Some(primitive));
+                                ui.close_menu();
+                            }
+                        }
+                    });
+                    ui.menu_button("Light", |ui| {
+                        let full = self.lights.read().unwrap().len() >= light::MAX_LIGHTS;
+                        ui.add_enabled_ui(!full, |ui| {
+                            if ui.button("Point Light").clicked() {
+                                self.add_light(false);
+                                ui.close_menu();
+                            }
+                            if ui.button("Spot Light").clicked() {
+                                self.add_light(true);
+                                ui.close_menu();
+                            }
+                        });
+                    });
+                });
+                ui.menu_button("View", |ui| {
+                    ui.checkbox(&mut self.outliner.open, "Outliner");
+                    ui.checkbox(&mut self.inspector.open, "Inspector");
+                    ui.horizontal(|ui| {
+                        ui.label("Outliner side:");
+                        for side in DockSide::ALL {
+                            ui.radio_value(&mut self.outliner.side, side, side.label());
+                        }
+                    });
+                    ui.menu_button("Selection Outline", |ui| {
+                        let outline = &mut self.render_settings.outline;
+                        egui::Grid::new("outline").num_columns(2).show(ui, |ui| {
+                            ui.label("Width");
+                            ui.add(
+                                egui::Slider::new(&mut outline.width, 1.0..=outline::MAX_WIDTH)
+                                    .suffix(" px"),
+                            );
+                            ui.end_row();
+
+                            ui.label("Color");
+                            ui.color_edit_button_rgba_unmultiplied(&mut outline.color);
+                            ui.end_row();
+
+                            ui.checkbox(&mut outline.overlay, "Overlay");
+                            ui.add_enabled_ui(outline.overlay, |ui| {
+                                ui.color_edit_button_rgba_unmultiplied(&mut outline.overlay_color);
+                            });
+                            ui.end_row();
+                        });
+                    });
+                    ui.menu_button("Grid", |ui| {
+                        let grid = &mut self.render_settings.grid;
+                        ui.checkbox(&mut grid.visible, "Show grid");
+                        ui.checkbox(&mut grid.axes, "Show axes");
+                        egui::Grid::new("grid").num_columns(2).show(ui, |ui| {
+                            ui.label("Unit size");
+                            ui.add(
+                                egui::DragValue::new(&mut grid.unit)
+                                    .speed(0.01)
+                                    .clamp_range(0.001..=1000.0),
+                            );
+                            ui.end_row();
+
+                            ui.label("Fade distance");
+                            ui.add(
+                                egui::DragValue::new(&mut grid.fade_distance)
+                                    .speed(0.1)
+                                    .clamp_range(0.1..=10000.0),
+                            );
+                            ui.end_row();
+
+                            ui.label("Color");
+                            ui.color_edit_button_rgba_unmultiplied(&mut grid.color);
+                            ui.end_row();
+                        });
+                    });
+                    ui.menu_button("Annotations", |ui| {
+                        let annotations = &mut self.render_settings.annotations;
+                        ui.checkbox(&mut annotations.visible, "Show annotations");
+                        ui.checkbox(&mut annotations.show_occluded, "Show hidden ones faded");
+                        ui.checkbox(&mut annotations.declutter, "Declutter labels")
+                            .on_hover_text("Move or leave out labels that would overlap");
+                    });
+                    ui.menu_button("Clipping", |ui| {
+                        let mut clipping = self.render_settings.clipping;
+                        ui.checkbox(&mut clipping.section_box.enabled, "Section box");
+                        ui.checkbox(&mut clipping.caps, "Hatch cut faces");
+                        let full = clipping.planes.iter().all(Option::is_some);
+                        if ui
+                            .add_enabled(!full, egui::Button::new("Add Plane"))
+                            .clicked()
+                        {
+                            if let Some(index) = clipping.add_plane() {
+                                self.selection.click(
+                                    Some(Selection::ClipPlane(index)),
+                                    egui::Modifiers::NONE,
+                                );
+                            }
+                            ui.close_menu();
+                        }
+                        if ui.button("Remove All").clicked() {
+                            clipping.planes = Default::default();
+                            clipping.section_box.enabled = false;
+                            ui.close_menu();
+                        }
+                        self.history
+                            .edit_clipping(&mut self.render_settings.clipping, |edited| {
+                                *edited = clipping
+                            });
+                    });
+                    ui.menu_button("Debug Shapes", |ui| {
+                        let overlays = &mut self.debug_overlays;
+                        ui.checkbox(&mut overlays.bounds, "Bounding boxes");
+                        ui.checkbox(&mut overlays.lights, "Light ranges");
+                        ui.checkbox(&mut overlays.axes, "Selected node axes");
+                        let mut frozen = self.frozen_camera.is_some();
+                        if ui
+                            .checkbox(&mut frozen, "Frozen camera frustum")
+                            .on_hover_text("Keep drawing what the camera sees now")
+                            .changed()
+                        {
+                            self.frozen_camera =
+                                frozen.then(|| self.camera.read().unwrap().clone());
+                        }
+                        ui.separator();
+                        ui.checkbox(&mut overlays.x_ray, "Show through the scene");
+                    });
+                });
+                ui.add_space(16.0);
 
                 egui::widgets::global_dark_light_mode_buttons(ui);
             });
         });
+        self.dark_mode = ctx.style().visuals.dark_mode;
 
         ctx.input(|i| self.outer_rect = i.viewport().outer_rect);
 
+        #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+        self.reload_changed_shader(ctx);
+
+        self.show_scene_file_dialog(ctx);
+
+        // Edits made while a button is held or a field is being typed in
+        // are undone together.
+        if ctx.input(|i| !i.pointer.any_down()) && ctx.memory(|memory| memory.focus().is_none()) {
+            self.history.seal();
+        }
+        self.handle_edit_shortcuts(ctx);
+
+        if let Some(error) = &self.shader_error {
+            egui::Window::new("Shader error")
+                .default_width(600.0)
+                .show(ctx, |ui| {
+                    ui.label("Keeping the last working shader until this is fixed.");
+                    egui::ScrollArea::vertical().show(ui, |ui| {
+                        ui.monospace(error);
+                    });
+                });
+        }
+
+        {
+            let mut picking = self.picking.write().unwrap();
+            if let Some(node) = picking.take_result() {
+                self.selection
+                    .click(node.map(Selection::Node), self.pick_modifiers);
+            }
+            // The result arrives a frame or more after the click.
+            if picking.is_pending() {
+                ctx.request_repaint();
+            }
+        }
+
+        self.outliner.show(
+            ctx,
+            &mut self.scene.write().unwrap(),
+            &mut self.history,
+            &self.lights.read().unwrap(),
+            &mut self.render_settings.clipping,
+            &mut self.selection,
+        );
+        // The inspector docks opposite the outliner.
+        self.inspector.show(
+            ctx,
+            self.outliner.side.opposite(),
+            &self.selection,
+            &mut self.scene.write().unwrap(),
+            &mut self.history,
+            &mut self.lights.write().unwrap(),
+            &mut self.camera.write().unwrap(),
+            &mut self.render_settings.clipping,
+        );
+
+        // Deleting, or undoing an add, leaves nodes out of the tree.
+        {
+            let scene = self.scene.read().unwrap();
+            let light_count = self.lights.read().unwrap().len();
+            let clipping = &self.render_settings.clipping;
+            self.selection.retain(|item| match item {
+                Selection::Node(id) => scene.contains(id),
+                Selection::Light(index) => index < light_count,
+                Selection::ClipPlane(index) => clipping.planes[index].is_some(),
+                _ => true,
+            });
+        }
+
+        self.draw_debug_shapes();
+
+        self.measure.window(ctx);
+
         egui::CentralPanel::default().show(ctx, |ui| {
             ui.horizontal(|ui| {
                 ui.add(egui::Slider::new(&mut self.viewport_width, 600.0..=2000.0));
                 ui.add(egui::Slider::new(&mut self.viewport_height, 600.0..=2000.0));
+                egui::ComboBox::from_label("Render mode")
+                    .selected_text(self.render_settings.mode.label())
+                    .show_ui(ui, |ui| {
+                        for mode in RenderMode::ALL {
+                            ui.selectable_value(&mut self.render_settings.mode, mode, mode.label());
+                        }
+                    });
+                if self.render_settings.mode == RenderMode::Depth {
+                    ui.add(
+                        egui::DragValue::new(&mut self.render_settings.max_depth)
+                            .clamp_range(0.1..=1000.0)
+                            .prefix("max depth: "),
+                    );
+                }
+                ui.label(format!("{} lights", self.lights.read().unwrap().len()));
+                ui.label(format!(
+                    "{} instances",
+                    self.scene.read().unwrap().instance_count()
+                ));
+            });
+            ui.horizontal(|ui| {
+                self.gizmo.toolbar_ui(ui);
+                ui.separator();
+                self.measure.toolbar_ui(ui);
             });
 
             egui::ScrollArea::both().auto_shrink(false).show(ui, |ui| {
@@ -107,31 +1065,171 @@ impl eframe::App for TemplateApp {
                     self.custom_painting(ui);
                 });
                 ui.label("Drag to rotate!");
+                if let Some(clicked_point) = &self.clicked_point {
+                    ui.label(clicked_point);
+                }
             });
         });
     }
 }
 
 impl TemplateApp {
+    /// Recompiles the pipelines when a shader module changes on disk.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    fn reload_changed_shader(&mut self, ctx: &egui::Context) {
+        // Keep polling the file even when nothing else triggers a repaint.
+        ctx.request_repaint_after(crate::hot_reload::POLL_INTERVAL);
+
+        let Some(shaders) = self.shader_watcher.poll() else {
+            return;
+        };
+        let result = shaders
+            .map_err(|error| error.to_string())
+            .and_then(|shaders| self.renderer.reload_shaders(shaders));
+        match result {
+            Ok(()) => self.shader_error = None,
+            Err(error) => {
+                log::error!("Failed to reload shader: {error}");
+                self.shader_error = Some(error);
+            }
+        }
+    }
+
     fn custom_painting(&mut self, ui: &mut egui::Ui) {
-        let (rect, _response) = if let Some(rect) = self.outer_rect.as_ref() {
-            ui.allocate_exact_size(
-                egui::Vec2::new(rect.width(), rect.height()),
-                egui::Sense::drag(),
-            )
+        let size = if let Some(rect) = self.outer_rect.as_ref() {
+            egui::Vec2::new(rect.width(), rect.height())
         } else {
-            ui.allocate_exact_size(
-                egui::Vec2::new(self.viewport_width, self.viewport_height),
-                egui::Sense::drag().union(egui::Sense::click()),
-            )
+            egui::Vec2::new(self.viewport_width, self.viewport_height)
         };
-
-        let mut camera_controller = self.camera_controller.write().unwrap();
-        camera_controller.process_events(ui);
+        let (rect, response) = ui.allocate_exact_size(size, egui::Sense::click_and_drag());
+        self.viewport_rect = Some(rect.intersect(ui.ctx().screen_rect()));
+        self.update_hovered(response.hover_pos());
 
         ui.painter().add(egui_wgpu::Callback::new_paint_callback(
             rect,
-            CustomTriangleCallback {},
+            CustomTriangleCallback {
+                rect,
+                settings: self.render_settings,
+                selected: self.selection.nodes().collect(),
+            },
         ));
+
+        if let Some(viewport) = self.viewport_rect {
+            annotation::show(
+                ui,
+                viewport,
+                &self.camera.read().unwrap(),
+                &self.annotations.read().unwrap(),
+                &self.render_settings.annotations,
+            );
+            // Points aren't placed through the view cube.
+            self.measure.show(
+                ui,
+                &response,
+                viewport,
+                &self.camera.read().unwrap(),
+                &self.scene.read().unwrap(),
+                self.hovered.filter(|_| !self.view_cube.has_pointer()),
+            );
+        }
+
+        // The gizmo moves the active node or clipping plane, and is drawn over
+        // the scene. While measuring, clicks place points instead.
+        if let (Some(active), Some(viewport), false) = (
+            self.selection.active(),
+            self.viewport_rect,
+            self.measure.active,
+        ) {
+            let camera = self.camera.read().unwrap();
+            let identity = cgmath::Matrix4::identity();
+            let clipping = &mut self.render_settings.clipping;
+            match active {
+                Selection::Node(id) => {
+                    let mut scene = self.scene.write().unwrap();
+                    let parent = scene
+                        .ancestors(id)
+                        .next()
+                        .map_or(identity, |parent| scene.world_matrices()[parent.index()]);
+                    let mut transform = scene.node(id).transform;
+                    if self
+                        .gizmo
+                        .interact(ui, &response, viewport, &camera, parent, &mut transform)
+                    {
+                        self.history
+                            .edit_node(&mut scene, id, |node| node.transform = transform);
+                    }
+                }
+                Selection::ClipPlane(index) => {
+                    if let Some(plane) = clipping.planes[index] {
+                        let mut transform = plane.transform();
+                        if self.gizmo.interact(
+                            ui,
+                            &response,
+                            viewport,
+                            &camera,
+                            identity,
+                            &mut transform,
+                        ) {
+                            self.history.edit_clipping(clipping, |clipping| {
+                                if let Some(plane) = &mut clipping.planes[index] {
+                                    plane.set_transform(&transform);
+                                }
+                            });
+                        }
+                    }
+                }
+                Selection::SectionBox => {
+                    let mut transform = clipping.section_box.transform();
+                    if self.gizmo.interact(
+                        ui,
+                        &response,
+                        viewport,
+                        &camera,
+                        identity,
+                        &mut transform,
+                    ) {
+                        self.history.edit_clipping(clipping, |clipping| {
+                            clipping.section_box.set_transform(&transform);
+                        });
+                    }
+                }
+                Selection::Light(_) | Selection::Camera => {}
+            }
+        }
+
+        // The view cube turns the camera, and is drawn over the gizmo.
+        if let Some(viewport) = self.viewport_rect {
+            let mut camera = self.camera.write().unwrap();
+            self.view_cube.show(ui, &response, viewport, &mut camera);
+        }
+
+        // Clicking selects the node under the cursor, or nothing. Shift or
+        // ctrl add to the selection instead.
+        if let (Some(pos), Some(viewport)) = (
+            response.interact_pointer_pos().filter(|_| {
+                response.clicked()
+                    && !self.measure.active
+                    && !self.gizmo.has_pointer()
+                    && !self.view_cube.has_pointer()
+            }),
+            self.viewport_rect,
+        ) {
+            // The pick target covers the visible part of the viewport, which
+            // is smaller than `rect` when the viewport doesn't fit the window.
+            let pixel = (pos - viewport.min) * ui.ctx().pixels_per_point();
+            self.clicked_point = self.raycast(pos).map(|hit| {
+                let name = self.scene.read().unwrap().node(hit.node).name.clone();
+                let [x, y, z]: [f32; 3] = hit.position.into();
+                format!("{name} at ({x:.2}, {y:.2}, {z:.2})")
+            });
+            self.pick_modifiers = ui.input(|i| i.modifiers);
+            self.picking
+                .write()
+                .unwrap()
+                .request([pixel.x as u32, pixel.y as u32]);
+        }
+
+        let mut camera_controller = self.camera_controller.write().unwrap();
+        camera_controller.process_events(ui);
     }
 }
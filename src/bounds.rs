@@ -0,0 +1,284 @@
+use cgmath::{EuclideanSpace, InnerSpace, Matrix4, Point3, Transform as _, Vector3};
+
+/// A half-line, `origin + t * direction` for `t >= 0`.
+///
+/// The direction isn't normalized, so that transforming a ray keeps the `t`
+/// of every point on it.
+#[derive(Clone, Copy, Debug)]
+pub struct Ray {
+    pub origin: Point3<f32>,
+    pub direction: Vector3<f32>,
+}
+
+impl Ray {
+    pub fn at(&self, t: f32) -> Point3<f32> {
+        self.origin + self.direction * t
+    }
+
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        Self {
+            origin: matrix.transform_point(self.origin),
+            direction: matrix.transform_vector(self.direction),
+        }
+    }
+
+    /// Where the ray crosses the triangle `a`, `b`, `c` from either side, as
+    /// `t` and the barycentric weights of `b` and `c`.
+    ///
+    /// Möller–Trumbore intersection.
+    pub fn intersect_triangle(
+        &self,
+        a: Point3<f32>,
+        b: Point3<f32>,
+        c: Point3<f32>,
+    ) -> Option<(f32, f32, f32)> {
+        let ab = b - a;
+        let ac = c - a;
+        let p = self.direction.cross(ac);
+        let determinant = ab.dot(p);
+        if determinant.abs() < f32::EPSILON {
+            return None;
+        }
+        let inverse = 1.0 / determinant;
+        let s = self.origin - a;
+        let u = s.dot(p) * inverse;
+        if !(0.0..=1.0).contains(&u) {
+            return None;
+        }
+        let q = s.cross(ab);
+        let v = self.direction.dot(q) * inverse;
+        if v < 0.0 || u + v > 1.0 {
+            return None;
+        }
+        let t = ac.dot(q) * inverse;
+        (t >= 0.0).then_some((t, u, v))
+    }
+}
+
+/// An axis-aligned bounding box.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Aabb {
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Aabb {
+    /// The smallest box holding every point, or `None` if there are none.
+    pub fn from_points(points: impl IntoIterator<Item = Point3<f32>>) -> Option<Self> {
+        let mut points = points.into_iter();
+        let first = points.next()?;
+        Some(points.fold(
+            Self {
+                min: first,
+                max: first,
+            },
+            |aabb, point| Self {
+                min: Point3::new(
+                    aabb.min.x.min(point.x),
+                    aabb.min.y.min(point.y),
+                    aabb.min.z.min(point.z),
+                ),
+                max: Point3::new(
+                    aabb.max.x.max(point.x),
+                    aabb.max.y.max(point.y),
+                    aabb.max.z.max(point.z),
+                ),
+            },
+        ))
+    }
+
+    pub fn center(&self) -> Point3<f32> {
+        self.min.midpoint(self.max)
+    }
+
+    pub fn corners(&self) -> [Point3<f32>; 8] {
+        let (min, max) = (self.min, self.max);
+        [
+            Point3::new(min.x, min.y, min.z),
+            Point3::new(max.x, min.y, min.z),
+            Point3::new(min.x, max.y, min.z),
+            Point3::new(max.x, max.y, min.z),
+            Point3::new(min.x, min.y, max.z),
+            Point3::new(max.x, min.y, max.z),
+            Point3::new(min.x, max.y, max.z),
+            Point3::new(max.x, max.y, max.z),
+        ]
+    }
+
+    /// The box around this one after `matrix` is applied.
+    pub fn transformed(&self, matrix: &Matrix4<f32>) -> Self {
+        Self::from_points(self.corners().map(|corner| matrix.transform_point(corner))).unwrap()
+    }
+
+    /// The range of `t` over which the ray is inside the box, if it enters it.
+    ///
+    /// Slab intersection.
+    pub fn intersect_ray(&self, ray: &Ray) -> Option<(f32, f32)> {
+        let (mut near, mut far) = (0.0_f32, f32::INFINITY);
+        for axis in 0..3 {
+            if ray.direction[axis] == 0.0 {
+                // Parallel to the slab, which the ray is either always or never in.
+                if ray.origin[axis] < self.min[axis] || ray.origin[axis] > self.max[axis] {
+                    return None;
+                }
+                continue;
+            }
+            let inverse = 1.0 / ray.direction[axis];
+            let t0 = (self.min[axis] - ray.origin[axis]) * inverse;
+            let t1 = (self.max[axis] - ray.origin[axis]) * inverse;
+            let (t0, t1) = if t0 <= t1 { (t0, t1) } else { (t1, t0) };
+            near = near.max(t0);
+            far = far.min(t1);
+        }
+        (near <= far).then_some((near, far))
+    }
+}
+
+/// A sphere holding every point of a mesh, cheaper to test than an [`Aabb`].
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BoundingSphere {
+    pub center: Point3<f32>,
+    pub radius: f32,
+}
+
+impl BoundingSphere {
+    /// A sphere around `points`, centered on their bounding box.
+    pub fn from_points(points: &[Point3<f32>]) -> Option<Self> {
+        let center = Aabb::from_points(points.iter().copied())?.center();
+        let radius = points
+            .iter()
+            .map(|point| (point - center).magnitude2())
+            .fold(0.0, f32::max)
+            .sqrt();
+        Some(Self { center, radius })
+    }
+
+    /// Whether the ray passes through the sphere.
+    pub fn intersects_ray(&self, ray: &Ray) -> bool {
+        // The closest approach of the ray's line to the center, clamped to the ray.
+        let to_center = self.center - ray.origin;
+        let length2 = ray.direction.magnitude2();
+        let t = if length2 > 0.0 {
+            (to_center.dot(ray.direction) / length2).max(0.0)
+        } else {
+            0.0
+        };
+        (ray.at(t) - self.center).magnitude2() <= self.radius * self.radius
+    }
+}
+
+/// The bounding volumes of a mesh, in its own space.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct MeshBounds {
+    pub aabb: Aabb,
+    pub sphere: BoundingSphere,
+}
+
+impl MeshBounds {
+    /// The bounds of `positions`, or `None` if there are none.
+    pub fn new(positions: &[[f32; 3]]) -> Option<Self> {
+        let points: Vec<Point3<f32>> = positions.iter().copied().map(Point3::from).collect();
+        Some(Self {
+            aabb: Aabb::from_points(points.iter().copied())?,
+            sphere: BoundingSphere::from_points(&points)?,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ray(origin: [f32; 3], direction: [f32; 3]) -> Ray {
+        Ray {
+            origin: origin.into(),
+            direction: direction.into(),
+        }
+    }
+
+    fn unit_box() -> Aabb {
+        Aabb {
+            min: Point3::new(-1.0, -1.0, -1.0),
+            max: Point3::new(1.0, 1.0, 1.0),
+        }
+    }
+
+    const TRIANGLE: [Point3<f32>; 3] = [
+        Point3::new(0.0, 0.0, 0.0),
+        Point3::new(1.0, 0.0, 0.0),
+        Point3::new(0.0, 1.0, 0.0),
+    ];
+
+    #[test]
+    fn rays_hit_triangles_from_either_side() {
+        let [a, b, c] = TRIANGLE;
+        let (t, u, v) = ray([0.25, 0.5, 2.0], [0.0, 0.0, -0.5])
+            .intersect_triangle(a, b, c)
+            .unwrap();
+        assert_eq!((t, u, v), (4.0, 0.25, 0.5));
+
+        let back = ray([0.25, 0.5, -2.0], [0.0, 0.0, 1.0]).intersect_triangle(a, b, c);
+        assert_eq!(back, Some((2.0, 0.25, 0.5)));
+    }
+
+    #[test]
+    fn rays_miss_triangles_beside_behind_or_along_them() {
+        let [a, b, c] = TRIANGLE;
+        let beside = ray([0.75, 0.75, 1.0], [0.0, 0.0, -1.0]);
+        assert_eq!(beside.intersect_triangle(a, b, c), None);
+        let away = ray([0.25, 0.25, 1.0], [0.0, 0.0, 1.0]);
+        assert_eq!(away.intersect_triangle(a, b, c), None);
+        let parallel = ray([-1.0, 0.25, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(parallel.intersect_triangle(a, b, c), None);
+    }
+
+    #[test]
+    fn rays_enter_and_leave_boxes() {
+        let hit = unit_box().intersect_ray(&ray([-3.0, 0.0, 0.0], [1.0, 0.0, 0.0]));
+        assert_eq!(hit, Some((2.0, 4.0)));
+
+        let diagonal = unit_box().intersect_ray(&ray([2.0, 2.0, 2.0], [-1.0, -1.0, -1.0]));
+        assert_eq!(diagonal, Some((1.0, 3.0)));
+
+        let miss = ray([-3.0, 2.0, 0.0], [1.0, 0.0, 0.0]);
+        assert_eq!(unit_box().intersect_ray(&miss), None);
+        let away = ray([-3.0, 0.0, 0.0], [-1.0, 0.0, 0.0]);
+        assert_eq!(unit_box().intersect_ray(&away), None);
+    }
+
+    #[test]
+    fn rays_starting_inside_boxes_enter_at_once() {
+        let inside = unit_box().intersect_ray(&ray([0.0, 0.5, 0.0], [0.0, 0.5, 0.0]));
+        assert_eq!(inside, Some((0.0, 1.0)));
+    }
+
+    #[test]
+    fn rays_along_box_faces_are_inside() {
+        let along = unit_box().intersect_ray(&ray([-3.0, 1.0, 0.0], [1.0, 0.0, 0.0]));
+        assert_eq!(along, Some((2.0, 4.0)));
+    }
+
+    #[test]
+    fn rays_pass_through_spheres() {
+        let sphere = BoundingSphere {
+            center: Point3::new(0.0, 0.0, -5.0),
+            radius: 1.0,
+        };
+        assert!(sphere.intersects_ray(&ray([0.0, 0.5, 0.0], [0.0, 0.0, -1.0])));
+        assert!(!sphere.intersects_ray(&ray([0.0, 1.5, 0.0], [0.0, 0.0, -1.0])));
+        // Behind the origin only.
+        assert!(!sphere.intersects_ray(&ray([0.0, 0.0, 0.0], [0.0, 0.0, 1.0])));
+        assert!(sphere.intersects_ray(&ray([0.0, 0.0, -5.5], [0.0, 0.0, 1.0])));
+    }
+
+    #[test]
+    fn bounds_fit_their_points() {
+        let bounds =
+            MeshBounds::new(&[[1.0, 0.0, 0.0], [-1.0, 2.0, 0.0], [0.0, 0.0, 4.0]]).unwrap();
+        assert_eq!(bounds.aabb.min, Point3::new(-1.0, 0.0, 0.0));
+        assert_eq!(bounds.aabb.max, Point3::new(1.0, 2.0, 4.0));
+        assert_eq!(bounds.sphere.center, Point3::new(0.0, 1.0, 2.0));
+        assert_eq!(bounds.sphere.radius, 6.0_f32.sqrt());
+        assert_eq!(MeshBounds::new(&[]), None);
+    }
+}
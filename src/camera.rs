@@ -1,5 +1,9 @@
-use crate::renderer::Resource;
+use crate::{
+    bounds::Ray,
+    renderer::{RenderSettings, RenderSettingsUniform, Resource},
+};
 use egui_wgpu::{self, wgpu};
+use serde::{Deserialize, Serialize};
 use std::sync::{Arc, RwLock};
 
 #[rustfmt::skip]
@@ -10,14 +14,22 @@ pub const OPENGL_TO_WGPU_MATRIX: cgmath::Matrix4<f32> = cgmath::Matrix4::new(
     0.0, 0.0, 0.0, 1.0,
 );
 
+#[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct Camera {
-    eye: cgmath::Point3<f32>,
-    target: cgmath::Point3<f32>,
-    up: cgmath::Vector3<f32>,
+    pub eye: cgmath::Point3<f32>,
+    pub target: cgmath::Point3<f32>,
+    pub up: cgmath::Vector3<f32>,
     aspect: f32,
-    fovy: f32,
-    znear: f32,
-    zfar: f32,
+    /// Vertical field of view, in degrees.
+    pub fovy: f32,
+    pub znear: f32,
+    pub zfar: f32,
+}
+
+impl Default for Camera {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl Camera {
@@ -35,13 +47,72 @@ impl Camera {
         }
     }
 
-    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
-        let view = cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up);
-        // 2.
+    pub fn build_view_matrix(&self) -> cgmath::Matrix4<f32> {
+        cgmath::Matrix4::look_at_rh(self.eye, self.target, self.up)
+    }
+
+    pub fn build_projection_matrix(&self) -> cgmath::Matrix4<f32> {
         let proj = cgmath::perspective(cgmath::Deg(self.fovy), self.aspect, self.znear, self.zfar);
+        OPENGL_TO_WGPU_MATRIX * proj
+    }
+
+    pub fn build_view_projection_matrix(&self) -> cgmath::Matrix4<f32> {
+        self.build_projection_matrix() * self.build_view_matrix()
+    }
+
+    /// The ray from the near plane through a point given in normalized device
+    /// coordinates, with y pointing up.
+    ///
+    /// Points at other depths are not unprojected, as the depth range the
+    /// projection maps to isn't relied on; the ray runs away from the center of
+    /// projection instead, the point the projection sends to infinity.
+    pub fn ray(&self, ndc: cgmath::Point2<f32>) -> Option<Ray> {
+        use cgmath::SquareMatrix;
+        let inverse = self.build_view_projection_matrix().invert()?;
+        let near = cgmath::Point3::from_homogeneous(
+            inverse * cgmath::Vector4::new(ndc.x, ndc.y, 0.0, 1.0),
+        );
+        let center = inverse * cgmath::Vector4::unit_z();
+        if center.w == 0.0 {
+            return None;
+        }
+        Some(Ray {
+            origin: near,
+            direction: near - cgmath::Point3::from_homogeneous(center),
+        })
+    }
+
+    /// The ray through `pos` on screen, where the camera's image fills
+    /// `viewport`.
+    pub fn screen_ray(&self, pos: egui::Pos2, viewport: egui::Rect) -> Option<Ray> {
+        let ndc = cgmath::Point2::new(
+            (pos.x - viewport.min.x) / viewport.width() * 2.0 - 1.0,
+            1.0 - (pos.y - viewport.min.y) / viewport.height() * 2.0,
+        );
+        self.ray(ndc)
+    }
 
-        // 3.
-        OPENGL_TO_WGPU_MATRIX * proj * view
+    /// Where `point` appears on screen, where the camera's image fills
+    /// `viewport`, or `None` if it is behind the camera.
+    pub fn project(&self, point: cgmath::Point3<f32>, viewport: egui::Rect) -> Option<egui::Pos2> {
+        let clip = self.build_view_projection_matrix() * point.to_homogeneous();
+        if clip.w <= f32::EPSILON {
+            return None;
+        }
+        Some(egui::pos2(
+            viewport.min.x + (clip.x / clip.w + 1.0) * 0.5 * viewport.width(),
+            viewport.min.y + (1.0 - clip.y / clip.w) * 0.5 * viewport.height(),
+        ))
+    }
+
+    /// Width over height of the image.
+    pub fn aspect(&self) -> f32 {
+        self.aspect
+    }
+
+    /// The distances to the near and far clipping planes.
+    pub fn depth_range(&self) -> (f32, f32) {
+        (self.znear, self.zfar)
     }
 }
 
@@ -53,6 +124,7 @@ pub struct CameraUniform {
     // We can't use cgmath with bytemuck directly, so we'll have
     // to convert the Matrix4 into a 4x4 f32 array
     view_proj: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
 }
 
 impl CameraUniform {
@@ -60,11 +132,13 @@ impl CameraUniform {
         use cgmath::SquareMatrix;
         Self {
             view_proj: cgmath::Matrix4::identity().into(),
+            view: cgmath::Matrix4::identity().into(),
         }
     }
 
     pub fn update_view_proj(&mut self, camera: &Camera) {
         self.view_proj = camera.build_view_projection_matrix().into();
+        self.view = camera.build_view_matrix().into();
     }
 }
 
@@ -88,10 +162,13 @@ impl CameraController {
     }
 
     pub fn process_events(&mut self, ui: &mut egui::Ui) {
-        ui.input(|i| self.is_forward_pressed = i.key_pressed(egui::Key::W));
-        ui.input(|i| self.is_backward_pressed = i.key_pressed(egui::Key::S));
-        ui.input(|i| self.is_left_pressed = i.key_pressed(egui::Key::A));
-        ui.input(|i| self.is_right_pressed = i.key_pressed(egui::Key::D));
+        // Keys typed into a text field, like a node being renamed, are not
+        // meant for the camera.
+        let typing = ui.memory(|memory| memory.focus().is_some());
+        ui.input(|i| self.is_forward_pressed = !typing && i.key_pressed(egui::Key::W));
+        ui.input(|i| self.is_backward_pressed = !typing && i.key_pressed(egui::Key::S));
+        ui.input(|i| self.is_left_pressed = !typing && i.key_pressed(egui::Key::A));
+        ui.input(|i| self.is_right_pressed = !typing && i.key_pressed(egui::Key::D));
     }
 
     pub fn update_camera(&self, camera: &mut Camera) {
@@ -139,7 +216,7 @@ impl egui_wgpu::CallbackTrait for CustomCameraCallback {
         resources: &mut egui_wgpu::CallbackResources,
     ) -> Vec<eframe::wgpu::CommandBuffer> {
         let resources: &mut CameraResources = resources.get_mut().unwrap();
-        resources.prepare(device, queue);
+        resources.prepare(device, queue, &RenderSettings::default());
         Vec::new()
     }
 
@@ -155,11 +232,11 @@ impl egui_wgpu::CallbackTrait for CustomCameraCallback {
 }
 
 pub struct CameraResources {
-    pub pipeline: Arc<wgpu::RenderPipeline>,
     pub camera: Arc<RwLock<Camera>>,
     pub camera_controller: Arc<RwLock<CameraController>>,
     pub camera_uniform: CameraUniform,
     pub camera_buffer: wgpu::Buffer,
+    pub settings_buffer: wgpu::Buffer,
     pub camera_bind_group: wgpu::BindGroup,
 }
 
@@ -173,6 +250,7 @@ impl CameraResources {
         wgpu::BindGroupLayout,
         wgpu::Buffer,
         CameraUniform,
+        wgpu::Buffer,
     ) {
         use wgpu::util::DeviceExt;
         let camera_uniform = CameraUniform::new();
@@ -183,46 +261,83 @@ impl CameraResources {
             usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
         });
 
+        let settings_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Render Settings Buffer"),
+            contents: bytemuck::cast_slice(&[RenderSettingsUniform::new(
+                &RenderSettings::default(),
+            )]),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+        });
+
         let camera_bind_group_layout =
             device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
                 label: Some("Camera Bind Group Layout"),
-                entries: &[wgpu::BindGroupLayoutEntry {
-                    visibility: wgpu::ShaderStages::VERTEX,
-                    count: None,
-                    binding: 0,
-                    ty: wgpu::BindingType::Buffer {
-                        ty: wgpu::BufferBindingType::Uniform,
-                        has_dynamic_offset: false,
-                        min_binding_size: None,
+                entries: &[
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::VERTEX,
+                        count: None,
+                        binding: 0,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
+                    },
+                    wgpu::BindGroupLayoutEntry {
+                        visibility: wgpu::ShaderStages::FRAGMENT,
+                        count: None,
+                        binding: 1,
+                        ty: wgpu::BindingType::Buffer {
+                            ty: wgpu::BufferBindingType::Uniform,
+                            has_dynamic_offset: false,
+                            min_binding_size: None,
+                        },
                     },
-                }],
+                ],
             });
 
         (
             device.create_bind_group(&wgpu::BindGroupDescriptor {
                 label: Some("Camera Bind Group"),
                 layout: &camera_bind_group_layout,
-                entries: &[wgpu::BindGroupEntry {
-                    binding: 0,
-                    resource: camera_buffer.as_entire_binding(),
-                }],
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: camera_buffer.as_entire_binding(),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: settings_buffer.as_entire_binding(),
+                    },
+                ],
             }),
             camera_bind_group_layout,
             camera_buffer,
             camera_uniform,
+            settings_buffer,
         )
     }
 
-    pub fn prepare(&mut self, _device: &wgpu::Device, queue: &wgpu::Queue) {
+    pub fn prepare(
+        &mut self,
+        _device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        settings: &RenderSettings,
+    ) {
         let camera_controller = &self.camera_controller.read().unwrap();
         let mut camera = self.camera.write().unwrap();
         camera_controller.update_camera(&mut camera);
-        self.camera_uniform.update_view_proj(&mut camera);
+        self.camera_uniform.update_view_proj(&camera);
         queue.write_buffer(
             &self.camera_buffer,
             0,
             bytemuck::cast_slice(&[self.camera_uniform]),
         );
+        queue.write_buffer(
+            &self.settings_buffer,
+            0,
+            bytemuck::cast_slice(&[RenderSettingsUniform::new(settings)]),
+        );
     }
 
     pub fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
@@ -0,0 +1,279 @@
+//! Cutting the scene open with clipping planes and a section box, to look
+//! inside assemblies.
+//!
+//! Fragments on the far side of a plane or outside the box are discarded by
+//! `clipping.wgsl`. The depth buffer has no stencil to cap the cuts with, so
+//! instead back faces are drawn too, and the insides of the meshes seen
+//! through a cut are filled with a hatch. Open meshes seen from behind are
+//! hatched the same way while anything is clipped.
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Quaternion, Rotation, Rotation3, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    bounds::Aabb,
+    debug_draw::DebugDraw,
+    pipeline::PipelineKey,
+    selection::{Selection, SelectionSet},
+    transform::Transform,
+};
+
+/// How many clipping planes there can be, matching the array in
+/// `common.wgsl`.
+pub const MAX_CLIP_PLANES: usize = 6;
+
+/// Half the width of the square drawn for a plane, in world units.
+const PLANE_HALF_SIZE: f32 = 0.75;
+
+const COLOR: [f32; 4] = [0.3, 0.8, 1.0, 0.6];
+const SELECTED_COLOR: [f32; 4] = [1.0, 0.82, 0.24, 1.0];
+
+/// A plane cutting away everything on the side its normal points to.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct ClipPlane {
+    pub enabled: bool,
+    /// A point on the plane.
+    pub origin: Point3<f32>,
+    /// Turns +Z to the plane's normal.
+    pub rotation: Quaternion<f32>,
+}
+
+impl ClipPlane {
+    /// A plane through `origin` facing `normal`.
+    pub fn new(origin: Point3<f32>, normal: Vector3<f32>) -> Self {
+        Self {
+            enabled: true,
+            origin,
+            rotation: Quaternion::from_arc(Vector3::unit_z(), normal.normalize(), None),
+        }
+    }
+
+    pub fn normal(&self) -> Vector3<f32> {
+        self.rotation.rotate_vector(Vector3::unit_z())
+    }
+
+    /// Turns the plane to face `normal`, unless it has no length.
+    pub fn set_normal(&mut self, normal: Vector3<f32>) {
+        if normal.magnitude2() > 0.0 {
+            self.rotation = Quaternion::from_arc(Vector3::unit_z(), normal.normalize(), None);
+        }
+    }
+
+    /// Points the normal the other way, keeping the side that was cut away.
+    pub fn flip(&mut self) {
+        self.rotation = self.rotation * Quaternion::from_angle_x(cgmath::Deg(180.0));
+    }
+
+    /// The normal and the distance from the origin, so that points with a
+    /// positive `dot(normal, point) + distance` are cut away.
+    fn equation(&self) -> [f32; 4] {
+        let normal = self.normal();
+        [
+            normal.x,
+            normal.y,
+            normal.z,
+            -normal.dot(self.origin.to_vec()),
+        ]
+    }
+
+    /// The plane as a transform for the gizmo to move and rotate.
+    pub fn transform(&self) -> Transform {
+        Transform {
+            translation: self.origin.to_vec(),
+            rotation: self.rotation,
+            ..Transform::default()
+        }
+    }
+
+    /// Moves and turns the plane to `transform`, ignoring its scale.
+    pub fn set_transform(&mut self, transform: &Transform) {
+        self.origin = Point3::from_vec(transform.translation);
+        self.rotation = transform.rotation.normalize();
+    }
+}
+
+/// An axis-aligned box cutting away everything outside it.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SectionBox {
+    pub enabled: bool,
+    pub min: Point3<f32>,
+    pub max: Point3<f32>,
+}
+
+impl Default for SectionBox {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            min: Point3::new(-1.0, -1.0, -1.0),
+            max: Point3::new(1.0, 1.0, 1.0),
+        }
+    }
+}
+
+impl SectionBox {
+    /// The box as a transform for the gizmo to move and scale, with its size
+    /// as the scale.
+    pub fn transform(&self) -> Transform {
+        Transform {
+            translation: self.min.midpoint(self.max).to_vec(),
+            scale: self.max - self.min,
+            ..Transform::default()
+        }
+    }
+
+    /// Moves and resizes the box to `transform`. The box stays aligned to the
+    /// axes, so the rotation is ignored.
+    pub fn set_transform(&mut self, transform: &Transform) {
+        let center = Point3::from_vec(transform.translation);
+        let half_size = transform.scale.map(f32::abs) / 2.0;
+        self.min = center - half_size;
+        self.max = center + half_size;
+    }
+
+    /// Fits the box around `aabb`, such as the scene's bounds.
+    pub fn fit(&mut self, aabb: &Aabb) {
+        self.min = aabb.min;
+        self.max = aabb.max;
+    }
+
+    fn aabb(&self) -> Aabb {
+        Aabb {
+            min: self.min,
+            max: self.max,
+        }
+    }
+}
+
+/// The clipping planes and section box, part of the render settings.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct ClipSettings {
+    /// Slots for the planes, so that removing one leaves the others' indices
+    /// unchanged.
+    pub planes: [Option<ClipPlane>; MAX_CLIP_PLANES],
+    pub section_box: SectionBox,
+    /// Whether the cuts are filled with a hatch.
+    pub caps: bool,
+}
+
+impl Default for ClipSettings {
+    fn default() -> Self {
+        Self {
+            planes: [None; MAX_CLIP_PLANES],
+            section_box: SectionBox::default(),
+            caps: true,
+        }
+    }
+}
+
+impl ClipSettings {
+    /// Adds a plane cutting away the +X half of the scene, returning its index,
+    /// or `None` if every slot is taken.
+    pub fn add_plane(&mut self) -> Option<usize> {
+        let index = self.planes.iter().position(Option::is_none)?;
+        self.planes[index] = Some(ClipPlane::new(Point3::origin(), Vector3::unit_x()));
+        Some(index)
+    }
+
+    fn enabled_planes(&self) -> impl Iterator<Item = &ClipPlane> {
+        self.planes.iter().flatten().filter(|plane| plane.enabled)
+    }
+
+    /// Whether anything is cut away.
+    pub fn active(&self) -> bool {
+        self.section_box.enabled || self.enabled_planes().next().is_some()
+    }
+
+    /// Whether `point` is cut away, like `clipped` in `clipping.wgsl`.
+    pub fn clipped(&self, point: Point3<f32>) -> bool {
+        let section_box = &self.section_box;
+        self.enabled_planes()
+            .any(|plane| plane.normal().dot(point - plane.origin) > 0.0)
+            || section_box.enabled
+                && (0..3).any(|axis| {
+                    point[axis] < section_box.min[axis] || point[axis] > section_box.max[axis]
+                })
+    }
+
+    /// `key` for drawing the scene cut open, if anything is cut away.
+    ///
+    /// Caps need back faces, so pipelines that cull them stop doing so.
+    /// Pipelines that already draw back faces, like the wireframe's, keep
+    /// showing them as they are.
+    pub(crate) fn pipeline_key(&self, key: PipelineKey) -> PipelineKey {
+        if !self.active() {
+            return key;
+        }
+        let defines = key.defines.clone().flag("CLIPPING");
+        if self.caps && key.cull_mode.is_some() {
+            key.defines(defines.flag("CLIP_CAPS")).cull_mode(None)
+        } else {
+            key.defines(defines)
+        }
+    }
+
+    /// Outlines the enabled planes and section box, and the selected ones
+    /// even if they're disabled.
+    pub fn draw(&self, debug_draw: &mut DebugDraw, selection: &SelectionSet) {
+        let color = |item| {
+            if selection.contains(item) {
+                SELECTED_COLOR
+            } else {
+                COLOR
+            }
+        };
+        for (index, plane) in self.planes.iter().enumerate() {
+            let Some(plane) = plane else {
+                continue;
+            };
+            let item = Selection::ClipPlane(index);
+            if !plane.enabled && !selection.contains(item) {
+                continue;
+            }
+            let u = plane.rotation.rotate_vector(Vector3::unit_x()) * PLANE_HALF_SIZE;
+            let v = plane.rotation.rotate_vector(Vector3::unit_y()) * PLANE_HALF_SIZE;
+            let corners = [-u - v, u - v, u + v, -u + v].map(|offset| plane.origin + offset);
+            for i in 0..4 {
+                debug_draw.line(corners[i], corners[(i + 1) % 4], color(item));
+            }
+            debug_draw.arrow(
+                plane.origin,
+                plane.origin + plane.normal() * PLANE_HALF_SIZE,
+                color(item),
+            );
+        }
+        if self.section_box.enabled || selection.contains(Selection::SectionBox) {
+            debug_draw.aabb(&self.section_box.aabb(), color(Selection::SectionBox));
+        }
+    }
+}
+
+/// The clipping part of `RenderSettings` in `common.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct ClipUniform {
+    /// The enabled planes first, see [`ClipPlane::equation`].
+    planes: [[f32; 4]; MAX_CLIP_PLANES],
+    section_min: [f32; 4],
+    section_max: [f32; 4],
+    /// How many planes are enabled in x, and 1 in y if the box is.
+    counts: [u32; 4],
+}
+
+impl ClipUniform {
+    pub fn new(settings: &ClipSettings) -> Self {
+        let mut planes = [[0.0; 4]; MAX_CLIP_PLANES];
+        let mut count = 0;
+        for (equation, plane) in planes.iter_mut().zip(settings.enabled_planes()) {
+            *equation = plane.equation();
+            count += 1;
+        }
+        let section_box = &settings.section_box;
+        Self {
+            planes,
+            section_min: section_box.min.to_homogeneous().into(),
+            section_max: section_box.max.to_homogeneous().into(),
+            counts: [count, section_box.enabled as u32, 0, 0],
+        }
+    }
+}
@@ -0,0 +1,393 @@
+//! Lines drawn over the scene, to see bounds, light ranges, cameras and other
+//! things that have no surface of their own.
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3, Vector3};
+use egui_wgpu::{self, wgpu};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    bounds::Aabb,
+    camera::{Camera, CameraResources},
+    grid::AXIS_COLORS,
+    mesh::{VertexLayout, VertexTrait},
+    pipeline::{self, DEPTH_FORMAT},
+    preprocessor::ShaderRegistry,
+    renderer::Resource,
+};
+
+/// Segments in the circles making up a sphere.
+const CIRCLE_SEGMENTS: usize = 32;
+
+/// The length of an arrow's head, relative to the whole arrow.
+const ARROW_HEAD: f32 = 0.2;
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LineVertex {
+    position: [f32; 3],
+    color: [f32; 4],
+}
+
+impl LineVertex {
+    fn layout() -> VertexLayout {
+        VertexLayout::custom(
+            wgpu::VertexStepMode::Vertex,
+            &[
+                (0, wgpu::VertexFormat::Float32x3),
+                (1, wgpu::VertexFormat::Float32x4),
+            ],
+        )
+    }
+}
+
+/// Which debug shapes the app draws every frame.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct DebugOverlays {
+    /// The world space bounding box of every drawn instance.
+    pub bounds: bool,
+    /// The range of every light, and the direction of spot lights.
+    pub lights: bool,
+    /// The axes of the selected nodes.
+    pub axes: bool,
+    /// Whether the shapes show through the scene instead of being hidden
+    /// behind it.
+    pub x_ray: bool,
+}
+
+impl Default for DebugOverlays {
+    fn default() -> Self {
+        Self {
+            bounds: false,
+            lights: false,
+            axes: false,
+            x_ray: true,
+        }
+    }
+}
+
+/// Line shapes to draw, in world space, kept until [`DebugDraw::clear`].
+///
+/// Every shape is broken into line segments and drawn in one line list, so
+/// there is no cost per shape beyond its vertices. Shapes added while
+/// [`DebugDraw::depth_test`] is set are hidden behind the scene; the others
+/// are drawn over it.
+pub struct DebugDraw {
+    pub depth_test: bool,
+    tested: Vec<LineVertex>,
+    overlay: Vec<LineVertex>,
+}
+
+impl Default for DebugDraw {
+    fn default() -> Self {
+        Self {
+            depth_test: true,
+            tested: Vec::new(),
+            overlay: Vec::new(),
+        }
+    }
+}
+
+impl DebugDraw {
+    /// Removes every shape.
+    pub fn clear(&mut self) {
+        self.tested.clear();
+        self.overlay.clear();
+    }
+
+    /// Colors are unmultiplied RGBA.
+    pub fn line(&mut self, a: Point3<f32>, b: Point3<f32>, color: [f32; 4]) {
+        let vertices = if self.depth_test {
+            &mut self.tested
+        } else {
+            &mut self.overlay
+        };
+        vertices.extend([a, b].map(|position| LineVertex {
+            position: position.into(),
+            color,
+        }));
+    }
+
+    /// The twelve edges of a box.
+    pub fn aabb(&mut self, aabb: &Aabb, color: [f32; 4]) {
+        let corners = aabb.corners();
+        // Corners are numbered by their bits: x is bit 0, y bit 1 and z bit 2,
+        // so corners one bit apart share an edge.
+        for a in 0..8 {
+            for bit in [1, 2, 4] {
+                if a & bit == 0 {
+                    self.line(corners[a], corners[a | bit], color);
+                }
+            }
+        }
+    }
+
+    /// Three circles around `center`, one in each axis plane.
+    pub fn sphere(&mut self, center: Point3<f32>, radius: f32, color: [f32; 4]) {
+        let axes = [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()];
+        for i in 0..3 {
+            self.circle(center, axes[i], axes[(i + 1) % 3], radius, color);
+        }
+    }
+
+    /// A circle spanned by the unit vectors `u` and `v`.
+    fn circle(
+        &mut self,
+        center: Point3<f32>,
+        u: Vector3<f32>,
+        v: Vector3<f32>,
+        radius: f32,
+        color: [f32; 4],
+    ) {
+        let point = |index: usize| {
+            let angle = index as f32 / CIRCLE_SEGMENTS as f32 * std::f32::consts::TAU;
+            center + (u * angle.cos() + v * angle.sin()) * radius
+        };
+        for index in 0..CIRCLE_SEGMENTS {
+            self.line(point(index), point(index + 1), color);
+        }
+    }
+
+    /// A line from `from` to `to` with a four-sided head at `to`.
+    pub fn arrow(&mut self, from: Point3<f32>, to: Point3<f32>, color: [f32; 4]) {
+        self.line(from, to, color);
+        let direction = to - from;
+        let length = direction.magnitude();
+        if length <= f32::EPSILON {
+            return;
+        }
+        let direction = direction / length;
+        let (u, v) = perpendiculars(direction);
+        let base = to - direction * length * ARROW_HEAD;
+        let width = length * ARROW_HEAD * 0.4;
+        for side in [u, -u, v, -v] {
+            self.line(to, base + side * width, color);
+        }
+    }
+
+    /// The x, y and z axes of `transform` in red, green and blue, each `length`
+    /// long whatever the transform's scale.
+    pub fn axes(&mut self, transform: cgmath::Matrix4<f32>, length: f32) {
+        let origin = Point3::from_vec(transform.w.truncate());
+        for (axis, color) in [transform.x, transform.y, transform.z]
+            .into_iter()
+            .zip(AXIS_COLORS)
+        {
+            let axis = axis.truncate();
+            if axis.magnitude2() > 0.0 {
+                self.line(origin, origin + axis.normalize() * length, color);
+            }
+        }
+    }
+
+    /// The pyramid `camera` sees, from its near plane to its far plane, with
+    /// lines from the eye to the near corners.
+    pub fn frustum(&mut self, camera: &Camera, color: [f32; 4]) {
+        let forward = (camera.target - camera.eye).normalize();
+        let right = forward.cross(camera.up).normalize();
+        let up = right.cross(forward);
+        let tan = (camera.fovy.to_radians() / 2.0).tan();
+        let corners = |distance: f32| {
+            let height = distance * tan;
+            let width = height * camera.aspect();
+            let center = camera.eye + forward * distance;
+            [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                .map(|(x, y)| center + right * (x * width) + up * (y * height))
+        };
+        let near = corners(camera.znear);
+        let far = corners(camera.zfar);
+        for i in 0..4 {
+            let next = (i + 1) % 4;
+            self.line(near[i], near[next], color);
+            self.line(far[i], far[next], color);
+            self.line(near[i], far[i], color);
+            self.line(camera.eye, near[i], color);
+        }
+    }
+}
+
+/// Two unit vectors perpendicular to `direction` and to each other.
+fn perpendiculars(direction: Vector3<f32>) -> (Vector3<f32>, Vector3<f32>) {
+    let other = if direction.x.abs() < 0.9 {
+        Vector3::unit_x()
+    } else {
+        Vector3::unit_y()
+    };
+    let u = direction.cross(other).normalize();
+    (u, direction.cross(u))
+}
+
+/// Uploads the lines of every [`DebugDraw`] each frame and draws them over the
+/// scene.
+pub struct DebugDrawResources {
+    batches: Vec<Arc<RwLock<DebugDraw>>>,
+    /// Depth tested, then drawn over everything.
+    pipelines: [wgpu::RenderPipeline; 2],
+    /// Kept to rebuild the pipelines when the shaders change on disk.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    layout: wgpu::PipelineLayout,
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    target_format: wgpu::TextureFormat,
+    vertex_buffer: wgpu::Buffer,
+    /// Vertices of the depth tested lines and of the overlay lines, one after
+    /// the other in the buffer.
+    counts: [u32; 2],
+}
+
+impl Resource for DebugDrawResources {}
+
+impl DebugDrawResources {
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        shaders: &ShaderRegistry,
+        camera_bind_group_layout: &wgpu::BindGroupLayout,
+        batches: Vec<Arc<RwLock<DebugDraw>>>,
+    ) -> Self {
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("lines"),
+            bind_group_layouts: &[camera_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipelines = Self::create_pipelines(device, shaders, &layout, target_format)
+            .expect("built-in shaders should compile");
+
+        Self {
+            batches,
+            pipelines,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            layout,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            target_format,
+            vertex_buffer: Self::create_vertex_buffer(device, 1024),
+            counts: [0; 2],
+        }
+    }
+
+    /// The depth tested and overlay pipelines.
+    fn create_pipelines(
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        layout: &wgpu::PipelineLayout,
+        target_format: wgpu::TextureFormat,
+    ) -> Result<[wgpu::RenderPipeline; 2], String> {
+        let shader = pipeline::shader_module(device, shaders, "lines", &Default::default())?;
+        let vertex_layout = LineVertex::layout();
+        let create_pipeline = |depth_compare| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("lines"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[vertex_layout.desc()],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: target_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState {
+                    topology: wgpu::PrimitiveTopology::LineList,
+                    ..Default::default()
+                },
+                // Lines never hide each other or the scene.
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        Ok([
+            create_pipeline(wgpu::CompareFunction::LessEqual),
+            create_pipeline(wgpu::CompareFunction::Always),
+        ])
+    }
+
+    fn create_vertex_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("debug_lines"),
+            size: (capacity * std::mem::size_of::<LineVertex>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    /// Uploads this frame's lines, growing the buffer if they don't fit.
+    pub fn prepare(&mut self, device: &wgpu::Device, queue: &wgpu::Queue) {
+        let batches: Vec<_> = self
+            .batches
+            .iter()
+            .map(|batch| batch.read().unwrap())
+            .collect();
+        let tested = batches.iter().map(|batch| &batch.tested);
+        let overlay = batches.iter().map(|batch| &batch.overlay);
+        self.counts = [
+            tested.clone().map(Vec::len).sum::<usize>() as u32,
+            overlay.clone().map(Vec::len).sum::<usize>() as u32,
+        ];
+        let count = (self.counts[0] + self.counts[1]) as usize;
+        if count == 0 {
+            return;
+        }
+        let size = (count * std::mem::size_of::<LineVertex>()) as u64;
+        if size > self.vertex_buffer.size() {
+            self.vertex_buffer = Self::create_vertex_buffer(device, count.next_power_of_two());
+        }
+        let mut offset = 0;
+        for vertices in tested.chain(overlay) {
+            let bytes: &[u8] = bytemuck::cast_slice(vertices);
+            if !bytes.is_empty() {
+                queue.write_buffer(&self.vertex_buffer, offset, bytes);
+            }
+            offset += bytes.len() as u64;
+        }
+    }
+
+    pub fn paint<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        camera_resources: &'rp CameraResources,
+    ) {
+        if self.counts == [0; 2] {
+            return;
+        }
+        camera_resources.paint(render_pass);
+        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
+        let mut start = 0;
+        for (pipeline, count) in self.pipelines.iter().zip(self.counts) {
+            if count > 0 {
+                render_pass.set_pipeline(pipeline);
+                render_pass.draw(start..start + count, 0..1);
+            }
+            start += count;
+        }
+    }
+}
+
+#[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+impl crate::hot_reload::ReloadPipelines for DebugDrawResources {
+    type Pipelines = [wgpu::RenderPipeline; 2];
+
+    fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+    ) -> Result<Self::Pipelines, String> {
+        Self::create_pipelines(device, shaders, &self.layout, self.target_format)
+    }
+
+    fn set_pipelines(&mut self, pipelines: Self::Pipelines) {
+        self.pipelines = pipelines;
+    }
+}
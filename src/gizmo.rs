@@ -0,0 +1,836 @@
+//! A manipulator drawn over the viewport for moving, rotating and scaling a
+//! transform with the pointer.
+//!
+//! The gizmo keeps the same size on screen wherever it is. Handles constrain
+//! a drag to one axis, or to the plane of the other two, and the resulting
+//! offset, angle or factor can be snapped to fixed steps.
+
+use cgmath::{
+    EuclideanSpace, InnerSpace, Matrix4, Point3, Quaternion, Rad, Rotation3, SquareMatrix,
+    Transform as _, Vector3,
+};
+use serde::{Deserialize, Serialize};
+
+use crate::{bounds::Ray, camera::Camera, transform::Transform};
+
+/// Length of the axis handles, in points.
+const SIZE: f32 = 90.0;
+
+/// How close the pointer has to be to a handle to grab it, in points.
+const GRAB_DISTANCE: f32 = 8.0;
+
+/// Where the plane handles start and end along their two axes, as fractions
+/// of [`SIZE`].
+const PLANE_HANDLE: (f32, f32) = (0.25, 0.45);
+
+const AXIS_COLORS: [egui::Color32; 3] = [
+    egui::Color32::from_rgb(220, 60, 60),
+    egui::Color32::from_rgb(100, 190, 50),
+    egui::Color32::from_rgb(60, 110, 230),
+];
+const ACTIVE_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 210, 60);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GizmoMode {
+    Translate,
+    Rotate,
+    Scale,
+}
+
+impl GizmoMode {
+    pub const ALL: [GizmoMode; 3] = [GizmoMode::Translate, GizmoMode::Rotate, GizmoMode::Scale];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GizmoMode::Translate => "Move",
+            GizmoMode::Rotate => "Rotate",
+            GizmoMode::Scale => "Scale",
+        }
+    }
+}
+
+/// Which axes the gizmo moves and rotates along.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum GizmoSpace {
+    World,
+    /// The axes of the transform itself.
+    Local,
+}
+
+impl GizmoSpace {
+    pub const ALL: [GizmoSpace; 2] = [GizmoSpace::World, GizmoSpace::Local];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            GizmoSpace::World => "World",
+            GizmoSpace::Local => "Local",
+        }
+    }
+}
+
+/// The steps drags snap to, when enabled.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Snapping {
+    pub enabled: bool,
+    /// In world units.
+    pub translation: f32,
+    /// In degrees.
+    pub rotation: f32,
+    /// A fraction of the original scale.
+    pub scale: f32,
+}
+
+impl Default for Snapping {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            translation: 0.1,
+            rotation: 15.0,
+            scale: 0.1,
+        }
+    }
+}
+
+impl Snapping {
+    fn snap(&self, value: f32, step: f32) -> f32 {
+        if self.enabled && step > 0.0 {
+            (value / step).round() * step
+        } else {
+            value
+        }
+    }
+}
+
+/// A part of the gizmo that can be dragged.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Handle {
+    Axis(usize),
+    /// The plane of the two axes other than this one.
+    Plane(usize),
+    /// Scales along every axis at once.
+    Uniform,
+}
+
+/// Where the gizmo is this frame, and how big.
+#[derive(Clone, Copy, Debug)]
+struct Frame {
+    origin: Point3<f32>,
+    /// Unit length.
+    axes: [Vector3<f32>; 3],
+    /// World units per point at the origin.
+    scale: f32,
+}
+
+impl Frame {
+    /// The two axes spanning the plane of `Handle::Plane(axis)`.
+    fn plane_axes(&self, axis: usize) -> (Vector3<f32>, Vector3<f32>) {
+        (self.axes[(axis + 1) % 3], self.axes[(axis + 2) % 3])
+    }
+
+    fn plane_handle(&self, axis: usize) -> [Point3<f32>; 4] {
+        let (a, b) = self.plane_axes(axis);
+        let (near, far) = PLANE_HANDLE;
+        let size = SIZE * self.scale;
+        [(near, near), (far, near), (far, far), (near, far)]
+            .map(|(u, v)| self.origin + (a * u + b * v) * size)
+    }
+
+    fn ring(&self, axis: usize) -> Vec<Point3<f32>> {
+        let (a, b) = self.plane_axes(axis);
+        let radius = SIZE * self.scale;
+        (0..=64)
+            .map(|step| {
+                let angle = step as f32 / 64.0 * std::f32::consts::TAU;
+                self.origin + (a * angle.cos() + b * angle.sin()) * radius
+            })
+            .collect()
+    }
+
+    /// Where the ray passes closest to the line along `axis`, as a distance
+    /// from the origin.
+    fn closest_on_axis(&self, axis: usize, ray: &Ray) -> Option<f32> {
+        let direction = self.axes[axis];
+        let ray_direction = ray.direction.normalize();
+        let b = direction.dot(ray_direction);
+        let denominator = 1.0 - b * b;
+        if denominator < 1e-4 {
+            return None;
+        }
+        let offset = self.origin - ray.origin;
+        let d = direction.dot(offset);
+        let e = ray_direction.dot(offset);
+        Some((b * e - d) / denominator)
+    }
+
+    /// Where the ray crosses the plane through the origin facing `normal`.
+    fn on_plane(&self, normal: Vector3<f32>, ray: &Ray) -> Option<Point3<f32>> {
+        let facing = ray.direction.dot(normal);
+        if facing.abs() < 1e-6 {
+            return None;
+        }
+        let t = (self.origin - ray.origin).dot(normal) / facing;
+        (t >= 0.0).then(|| ray.at(t))
+    }
+}
+
+/// What a drag measures, captured when it starts.
+#[derive(Clone, Copy, Debug)]
+enum Start {
+    /// Distance along an axis.
+    Along(f32),
+    /// A point on a plane.
+    OnPlane(Point3<f32>),
+    /// Distance from the gizmo's origin on screen.
+    ScreenDistance(f32),
+}
+
+#[derive(Clone, Copy, Debug)]
+struct Drag {
+    handle: Handle,
+    /// The gizmo and transform as they were when the drag started.
+    frame: Frame,
+    transform: Transform,
+    start: Start,
+}
+
+/// Settings and drag state of the transform gizmo.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Gizmo {
+    pub mode: GizmoMode,
+    pub space: GizmoSpace,
+    pub snapping: Snapping,
+    #[serde(skip)]
+    hovered: Option<Handle>,
+    #[serde(skip)]
+    drag: Option<Drag>,
+}
+
+impl Default for Gizmo {
+    fn default() -> Self {
+        Self {
+            mode: GizmoMode::Translate,
+            space: GizmoSpace::World,
+            snapping: Snapping::default(),
+            hovered: None,
+            drag: None,
+        }
+    }
+}
+
+impl Gizmo {
+    /// The mode, space and snapping controls, laid out in a row.
+    pub fn toolbar_ui(&mut self, ui: &mut egui::Ui) {
+        for mode in GizmoMode::ALL {
+            ui.selectable_value(&mut self.mode, mode, mode.label());
+        }
+        ui.separator();
+        egui::ComboBox::from_id_source("gizmo_space")
+            .selected_text(self.space.label())
+            .show_ui(ui, |ui| {
+                for space in GizmoSpace::ALL {
+                    ui.selectable_value(&mut self.space, space, space.label());
+                }
+            });
+        ui.separator();
+        let snapping = &mut self.snapping;
+        ui.checkbox(&mut snapping.enabled, "Snap");
+        ui.add_enabled_ui(snapping.enabled, |ui| match self.mode {
+            GizmoMode::Translate => {
+                ui.add(
+                    egui::DragValue::new(&mut snapping.translation)
+                        .speed(0.01)
+                        .clamp_range(0.001..=f32::MAX),
+                );
+            }
+            GizmoMode::Rotate => {
+                ui.add(
+                    egui::DragValue::new(&mut snapping.rotation)
+                        .clamp_range(0.1..=180.0)
+                        .suffix("°"),
+                );
+            }
+            GizmoMode::Scale => {
+                ui.add(
+                    egui::DragValue::new(&mut snapping.scale)
+                        .speed(0.01)
+                        .clamp_range(0.001..=f32::MAX),
+                );
+            }
+        });
+    }
+
+    /// Whether the pointer is over a handle or dragging one, so that clicks
+    /// belong to the gizmo rather than the viewport.
+    pub fn has_pointer(&self) -> bool {
+        self.hovered.is_some() || self.drag.is_some()
+    }
+
+    /// Draws the gizmo for `transform` over the viewport and applies drags
+    /// of its handles, returning whether `transform` changed.
+    ///
+    /// `parent` is the world matrix `transform` is relative to, and
+    /// `response` the viewport's, covering `viewport`.
+    pub fn interact(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        viewport: egui::Rect,
+        camera: &Camera,
+        parent: Matrix4<f32>,
+        transform: &mut Transform,
+    ) -> bool {
+        let Some(frame) = self.frame(camera, viewport, parent, transform) else {
+            self.hovered = None;
+            self.drag = None;
+            return false;
+        };
+
+        let pointer = response.hover_pos().or(response.interact_pointer_pos());
+        if self.drag.is_none() {
+            self.hovered =
+                pointer.and_then(|pointer| self.handle_at(&frame, camera, viewport, pointer));
+        }
+
+        let mut changed = false;
+        if response.drag_started() {
+            // Drags only start once the pointer has moved a little, so measure
+            // from where it was pressed.
+            let pressed = ui.input(|i| i.pointer.press_origin()).or(pointer);
+            self.drag = pressed.and_then(|pressed| {
+                let handle = self.handle_at(&frame, camera, viewport, pressed)?;
+                Some(Drag {
+                    handle,
+                    frame,
+                    transform: *transform,
+                    start: self.measure(handle, &frame, camera, viewport, pressed)?,
+                })
+            });
+        } else if let (Some(drag), Some(pointer)) = (self.drag, pointer) {
+            if response.dragged() {
+                if let Some(dragged) = self.apply(&drag, camera, viewport, pointer, parent) {
+                    changed = dragged != *transform;
+                    *transform = dragged;
+                }
+            }
+        }
+        if response.drag_released() {
+            self.drag = None;
+        }
+
+        let frame = match self.drag {
+            // Keep the handles the drag started on, so they don't turn under
+            // the pointer while rotating in local space.
+            Some(drag) => Frame {
+                origin: frame.origin,
+                ..drag.frame
+            },
+            None => frame,
+        };
+        self.paint(&ui.painter_at(viewport), &frame, camera, viewport);
+        changed
+    }
+
+    fn frame(
+        &self,
+        camera: &Camera,
+        viewport: egui::Rect,
+        parent: Matrix4<f32>,
+        transform: &Transform,
+    ) -> Option<Frame> {
+        let world = parent * transform.matrix();
+        let origin = Point3::from_vec(world.w.truncate());
+        let axes = match (self.space, self.mode) {
+            // Scale only has the transform's own axes to work along.
+            (GizmoSpace::Local, _) | (_, GizmoMode::Scale) => {
+                [world.x, world.y, world.z].map(|axis| axis.truncate().normalize())
+            }
+            (GizmoSpace::World, _) => [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+        };
+        if axes.iter().any(|axis| !axis.magnitude2().is_normal()) {
+            return None;
+        }
+
+        // Measure how far one world unit reaches on screen next to the origin.
+        let right = (camera.target - camera.eye).cross(camera.up).normalize();
+        let center = camera.project(origin, viewport)?;
+        let unit = camera.project(origin + right, viewport)?;
+        let points_per_unit = (unit - center).length();
+        (points_per_unit > f32::EPSILON).then_some(Frame {
+            origin,
+            axes,
+            scale: 1.0 / points_per_unit,
+        })
+    }
+
+    fn handles(&self) -> Vec<Handle> {
+        let mut handles: Vec<Handle> = (0..3).map(Handle::Axis).collect();
+        match self.mode {
+            GizmoMode::Translate => handles.extend((0..3).map(Handle::Plane)),
+            GizmoMode::Rotate => {}
+            GizmoMode::Scale => {
+                handles.extend((0..3).map(Handle::Plane));
+                handles.push(Handle::Uniform);
+            }
+        }
+        handles
+    }
+
+    /// The handle closest to `pointer` within grabbing distance.
+    fn handle_at(
+        &self,
+        frame: &Frame,
+        camera: &Camera,
+        viewport: egui::Rect,
+        pointer: egui::Pos2,
+    ) -> Option<Handle> {
+        let project = |point| camera.project(point, viewport);
+        let center = project(frame.origin)?;
+        let distance = |handle| -> Option<f32> {
+            match (self.mode, handle) {
+                (GizmoMode::Rotate, Handle::Axis(axis)) => {
+                    let ring: Option<Vec<_>> = frame.ring(axis).into_iter().map(project).collect();
+                    ring?
+                        .windows(2)
+                        .map(|segment| segment_distance(pointer, segment[0], segment[1]))
+                        .min_by(f32::total_cmp)
+                }
+                (_, Handle::Axis(axis)) => {
+                    let end = project(frame.origin + frame.axes[axis] * SIZE * frame.scale)?;
+                    Some(segment_distance(pointer, center, end))
+                }
+                (_, Handle::Plane(axis)) => {
+                    let corners: Option<Vec<_>> =
+                        frame.plane_handle(axis).into_iter().map(project).collect();
+                    // Inside the quad counts as closer than any line.
+                    polygon_contains(&corners?, pointer).then_some(0.0)
+                }
+                (_, Handle::Uniform) => Some((pointer - center).length()),
+            }
+        };
+        self.handles()
+            .into_iter()
+            .filter_map(|handle| Some((handle, distance(handle)?)))
+            .filter(|(_, distance)| *distance <= GRAB_DISTANCE)
+            .min_by(|a, b| a.1.total_cmp(&b.1))
+            .map(|(handle, _)| handle)
+    }
+
+    fn measure(
+        &self,
+        handle: Handle,
+        frame: &Frame,
+        camera: &Camera,
+        viewport: egui::Rect,
+        pointer: egui::Pos2,
+    ) -> Option<Start> {
+        let ray = camera.screen_ray(pointer, viewport)?;
+        match (self.mode, handle) {
+            (GizmoMode::Rotate, Handle::Axis(axis)) => {
+                frame.on_plane(frame.axes[axis], &ray).map(Start::OnPlane)
+            }
+            (_, Handle::Axis(axis)) => frame.closest_on_axis(axis, &ray).map(Start::Along),
+            (_, Handle::Plane(axis)) => frame.on_plane(frame.axes[axis], &ray).map(Start::OnPlane),
+            (_, Handle::Uniform) => {
+                let center = camera.project(frame.origin, viewport)?;
+                Some(Start::ScreenDistance((pointer - center).length()))
+            }
+        }
+    }
+
+    /// The transform after dragging from the start of `drag` to `pointer`.
+    fn apply(
+        &self,
+        drag: &Drag,
+        camera: &Camera,
+        viewport: egui::Rect,
+        pointer: egui::Pos2,
+        parent: Matrix4<f32>,
+    ) -> Option<Transform> {
+        let frame = &drag.frame;
+        let start = &drag.transform;
+        let snapping = &self.snapping;
+        let now = self.measure(drag.handle, frame, camera, viewport, pointer)?;
+        let mut transform = *start;
+        match (self.mode, drag.handle, drag.start, now) {
+            (GizmoMode::Translate, Handle::Axis(axis), Start::Along(from), Start::Along(to)) => {
+                let offset = frame.axes[axis] * snapping.snap(to - from, snapping.translation);
+                transform.translation += parent.invert()?.transform_vector(offset);
+            }
+            (
+                GizmoMode::Translate,
+                Handle::Plane(axis),
+                Start::OnPlane(from),
+                Start::OnPlane(to),
+            ) => {
+                let (a, b) = frame.plane_axes(axis);
+                let offset = to - from;
+                let offset = a * snapping.snap(offset.dot(a), snapping.translation)
+                    + b * snapping.snap(offset.dot(b), snapping.translation);
+                transform.translation += parent.invert()?.transform_vector(offset);
+            }
+            (GizmoMode::Rotate, Handle::Axis(axis), Start::OnPlane(from), Start::OnPlane(to)) => {
+                let normal = frame.axes[axis];
+                let (from, to) = (from - frame.origin, to - frame.origin);
+                let angle = from.cross(to).dot(normal).atan2(from.dot(to));
+                let angle = snapping
+                    .snap(angle.to_degrees(), snapping.rotation)
+                    .to_radians();
+                // Rotate about the axis as seen from the parent's space.
+                let axis = parent.invert()?.transform_vector(normal).normalize();
+                transform.rotation = Quaternion::from_axis_angle(axis, Rad(angle)) * start.rotation;
+            }
+            (GizmoMode::Scale, Handle::Axis(axis), Start::Along(from), Start::Along(to)) => {
+                if from.abs() < f32::EPSILON {
+                    return None;
+                }
+                transform.scale[axis] = self.scaled(start.scale[axis], to / from);
+            }
+            (GizmoMode::Scale, Handle::Plane(axis), Start::OnPlane(from), Start::OnPlane(to)) => {
+                let from = (from - frame.origin).magnitude();
+                if from < f32::EPSILON {
+                    return None;
+                }
+                let factor = (to - frame.origin).magnitude() / from;
+                for axis in [(axis + 1) % 3, (axis + 2) % 3] {
+                    transform.scale[axis] = self.scaled(start.scale[axis], factor);
+                }
+            }
+            (
+                GizmoMode::Scale,
+                Handle::Uniform,
+                Start::ScreenDistance(from),
+                Start::ScreenDistance(to),
+            ) => {
+                if from < f32::EPSILON {
+                    return None;
+                }
+                for axis in 0..3 {
+                    transform.scale[axis] = self.scaled(start.scale[axis], to / from);
+                }
+            }
+            _ => return None,
+        }
+        Some(transform)
+    }
+
+    /// `scale` multiplied by `factor`, snapped to steps of the original scale.
+    fn scaled(&self, scale: f32, factor: f32) -> f32 {
+        scale * self.snapping.snap(factor, self.snapping.scale)
+    }
+
+    fn paint(&self, painter: &egui::Painter, frame: &Frame, camera: &Camera, viewport: egui::Rect) {
+        let project = |point| camera.project(point, viewport);
+        let Some(center) = project(frame.origin) else {
+            return;
+        };
+        let active = self.drag.map(|drag| drag.handle).or(self.hovered);
+        let color = |handle: Handle, axis: usize| {
+            if active == Some(handle) {
+                ACTIVE_COLOR
+            } else {
+                AXIS_COLORS[axis]
+            }
+        };
+
+        if self.mode == GizmoMode::Translate || self.mode == GizmoMode::Scale {
+            for axis in 0..3 {
+                let corners: Option<Vec<_>> =
+                    frame.plane_handle(axis).into_iter().map(project).collect();
+                if let Some(corners) = corners {
+                    let color = color(Handle::Plane(axis), axis);
+                    painter.add(egui::Shape::convex_polygon(
+                        corners,
+                        color.gamma_multiply(0.4),
+                        egui::Stroke::new(1.0, color),
+                    ));
+                }
+            }
+        }
+
+        for axis in 0..3 {
+            let stroke = egui::Stroke::new(2.5, color(Handle::Axis(axis), axis));
+            if self.mode == GizmoMode::Rotate {
+                let ring: Option<Vec<_>> = frame.ring(axis).into_iter().map(project).collect();
+                if let Some(ring) = ring {
+                    painter.add(egui::Shape::line(ring, stroke));
+                }
+                continue;
+            }
+            let Some(end) = project(frame.origin + frame.axes[axis] * SIZE * frame.scale) else {
+                continue;
+            };
+            match self.mode {
+                GizmoMode::Scale => {
+                    painter.line_segment([center, end], stroke);
+                    painter.rect_filled(
+                        egui::Rect::from_center_size(end, egui::vec2(8.0, 8.0)),
+                        0.0,
+                        stroke.color,
+                    );
+                }
+                _ => painter.arrow(center, end - center, stroke),
+            }
+        }
+
+        if self.mode == GizmoMode::Scale {
+            let color = if active == Some(Handle::Uniform) {
+                ACTIVE_COLOR
+            } else {
+                egui::Color32::WHITE
+            };
+            painter.circle_filled(center, 5.0, color);
+        }
+    }
+}
+
+/// Distance from `point` to the segment from `a` to `b`.
+pub(crate) fn segment_distance(point: egui::Pos2, a: egui::Pos2, b: egui::Pos2) -> f32 {
+    let segment = b - a;
+    let length2 = segment.length_sq();
+    let t = if length2 > 0.0 {
+        ((point - a).dot(segment) / length2).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+    (a + segment * t - point).length()
+}
+
+/// Whether `point` is inside the convex polygon with `corners`, in either
+/// winding order.
+pub(crate) fn polygon_contains(corners: &[egui::Pos2], point: egui::Pos2) -> bool {
+    let sides = corners
+        .iter()
+        .zip(corners.iter().cycle().skip(1))
+        .map(|(&a, &b)| {
+            let edge = b - a;
+            let to_point = point - a;
+            edge.x * to_point.y - edge.y * to_point.x
+        });
+    let (mut positive, mut negative) = (false, false);
+    for side in sides {
+        positive |= side > 0.0;
+        negative |= side < 0.0;
+    }
+    !(positive && negative)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Rotation;
+
+    fn camera() -> Camera {
+        let mut camera = Camera::new();
+        camera.eye = Point3::new(0.0, 0.0, 10.0);
+        camera
+    }
+
+    fn viewport() -> egui::Rect {
+        egui::Rect::from_min_size(egui::Pos2::ZERO, egui::vec2(1600.0, 900.0))
+    }
+
+    fn gizmo(mode: GizmoMode, snapping: Option<Snapping>) -> Gizmo {
+        Gizmo {
+            mode,
+            snapping: snapping.unwrap_or_default(),
+            ..Default::default()
+        }
+    }
+
+    fn at(x: f32, y: f32, z: f32) -> Transform {
+        Transform {
+            translation: Vector3::new(x, y, z),
+            ..Default::default()
+        }
+    }
+
+    /// The transform after dragging `handle` between the points where `from`
+    /// and `to` appear on screen.
+    fn drag(
+        gizmo: &Gizmo,
+        transform: Transform,
+        parent: Matrix4<f32>,
+        handle: Handle,
+        from: Point3<f32>,
+        to: Point3<f32>,
+    ) -> Transform {
+        let (camera, viewport) = (camera(), viewport());
+        let frame = gizmo.frame(&camera, viewport, parent, &transform).unwrap();
+        let pointer = |point| camera.project(point, viewport).unwrap();
+        let start = gizmo
+            .measure(handle, &frame, &camera, viewport, pointer(from))
+            .unwrap();
+        let drag = Drag {
+            handle,
+            frame,
+            transform,
+            start,
+        };
+        gizmo
+            .apply(&drag, &camera, viewport, pointer(to), parent)
+            .unwrap()
+    }
+
+    fn assert_near(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() < 1e-3,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn finds_the_closest_point_on_an_axis() {
+        let frame = Frame {
+            origin: Point3::new(1.0, 0.0, 0.0),
+            axes: [Vector3::unit_x(), Vector3::unit_y(), Vector3::unit_z()],
+            scale: 1.0,
+        };
+        let ray = Ray {
+            origin: Point3::new(3.0, 2.0, 5.0),
+            direction: Vector3::new(0.0, 0.0, -2.0),
+        };
+        assert_eq!(frame.closest_on_axis(0, &ray), Some(2.0));
+        assert_eq!(frame.closest_on_axis(1, &ray), Some(2.0));
+        // Looking straight down the axis.
+        assert_eq!(frame.closest_on_axis(2, &ray), None);
+    }
+
+    #[test]
+    fn measures_distance_to_segments() {
+        let (a, b) = (egui::pos2(0.0, 0.0), egui::pos2(10.0, 0.0));
+        assert_eq!(segment_distance(egui::pos2(4.0, 3.0), a, b), 3.0);
+        assert_eq!(segment_distance(egui::pos2(13.0, 4.0), a, b), 5.0);
+        assert_eq!(segment_distance(egui::pos2(-3.0, -4.0), a, b), 5.0);
+        assert_eq!(segment_distance(egui::pos2(3.0, 4.0), a, a), 5.0);
+    }
+
+    #[test]
+    fn polygons_contain_points_in_either_winding() {
+        let square = [
+            egui::pos2(0.0, 0.0),
+            egui::pos2(2.0, 0.0),
+            egui::pos2(2.0, 2.0),
+            egui::pos2(0.0, 2.0),
+        ];
+        let reversed: Vec<_> = square.iter().rev().copied().collect();
+        for corners in [&square[..], &reversed] {
+            assert!(polygon_contains(corners, egui::pos2(1.0, 1.0)));
+            assert!(polygon_contains(corners, egui::pos2(2.0, 1.0)));
+            assert!(!polygon_contains(corners, egui::pos2(3.0, 1.0)));
+            assert!(!polygon_contains(corners, egui::pos2(-0.5, 2.5)));
+        }
+    }
+
+    #[test]
+    fn translates_along_an_axis_and_in_a_plane() {
+        let gizmo = gizmo(GizmoMode::Translate, None);
+        let start = at(1.0, 0.0, 0.0);
+        let moved = drag(
+            &gizmo,
+            start,
+            Matrix4::identity(),
+            Handle::Axis(0),
+            Point3::new(1.5, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+        );
+        assert_near(moved.translation, Vector3::new(1.5, 0.0, 0.0));
+
+        let moved = drag(
+            &gizmo,
+            start,
+            Matrix4::identity(),
+            Handle::Plane(2),
+            Point3::new(1.5, 0.5, 0.0),
+            Point3::new(2.0, 1.5, 0.0),
+        );
+        assert_near(moved.translation, Vector3::new(1.5, 1.0, 0.0));
+    }
+
+    #[test]
+    fn translates_in_the_parents_space() {
+        let gizmo = gizmo(GizmoMode::Translate, None);
+        let parent = Matrix4::from_scale(2.0);
+        let moved = drag(
+            &gizmo,
+            at(0.5, 0.0, 0.0),
+            parent,
+            Handle::Axis(0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(2.0, 0.0, 0.0),
+        );
+        assert_near(moved.translation, Vector3::new(1.0, 0.0, 0.0));
+    }
+
+    #[test]
+    fn snaps_the_drag_offset_not_the_position() {
+        let snapping = Snapping {
+            enabled: true,
+            translation: 0.5,
+            ..Default::default()
+        };
+        let gizmo = gizmo(GizmoMode::Translate, Some(snapping));
+        let moved = drag(
+            &gizmo,
+            at(0.2, 0.0, 0.0),
+            Matrix4::identity(),
+            Handle::Axis(0),
+            Point3::new(0.2, 0.0, 0.0),
+            Point3::new(0.85, 0.0, 0.0),
+        );
+        // An offset of 0.65 snaps to 0.5, keeping the position off the grid.
+        assert_near(moved.translation, Vector3::new(0.7, 0.0, 0.0));
+    }
+
+    #[test]
+    fn rotates_about_an_axis_in_snapped_steps() {
+        let direction = |degrees: f32| {
+            let radians = degrees.to_radians();
+            Vector3::new(radians.cos(), radians.sin(), 0.0)
+        };
+        let rotated = |snapping| {
+            let gizmo = gizmo(GizmoMode::Rotate, snapping);
+            let transform = drag(
+                &gizmo,
+                Transform::default(),
+                Matrix4::identity(),
+                Handle::Axis(2),
+                Point3::from_vec(direction(0.0)),
+                Point3::from_vec(direction(50.0)),
+            );
+            transform.rotation.rotate_vector(Vector3::unit_x())
+        };
+        assert_near(rotated(None), direction(50.0));
+        let snapping = Snapping {
+            enabled: true,
+            rotation: 15.0,
+            ..Default::default()
+        };
+        assert_near(rotated(Some(snapping)), direction(45.0));
+    }
+
+    #[test]
+    fn scales_by_snapped_factors_of_the_original_scale() {
+        let snapping = Snapping {
+            enabled: true,
+            scale: 0.25,
+            ..Default::default()
+        };
+        let gizmo = gizmo(GizmoMode::Scale, Some(snapping));
+        let start = Transform {
+            scale: Vector3::new(2.0, 3.0, 4.0),
+            ..Default::default()
+        };
+        let scaled = drag(
+            &gizmo,
+            start,
+            Matrix4::identity(),
+            Handle::Axis(0),
+            Point3::new(1.0, 0.0, 0.0),
+            Point3::new(1.6, 0.0, 0.0),
+        );
+        // A factor of 1.6 snaps to 1.5.
+        assert_near(scaled.scale, Vector3::new(3.0, 3.0, 4.0));
+    }
+}
@@ -0,0 +1,223 @@
+//! An infinite ground grid with the world axes, see `grid.wgsl`.
+
+use cgmath::SquareMatrix;
+use egui_wgpu::{self, wgpu};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+use crate::{
+    camera::Camera,
+    pipeline::{self, DEPTH_FORMAT},
+    preprocessor::ShaderRegistry,
+    renderer::Resource,
+};
+
+/// Colors of the x, y and z axes, shared by everything that draws them.
+pub const AXIS_COLORS: [[f32; 4]; 3] = [
+    [0.9, 0.2, 0.2, 1.0],
+    [0.3, 0.8, 0.2, 1.0],
+    [0.2, 0.4, 0.9, 1.0],
+];
+
+/// How the ground grid is drawn.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct GridSettings {
+    pub visible: bool,
+    /// Whether the x, y and z axes are drawn too.
+    pub axes: bool,
+    /// The size of the smallest cells, in world units. Larger cells are ten,
+    /// a hundred and so on times this.
+    pub unit: f32,
+    /// How far from the camera the grid fades out, in world units.
+    pub fade_distance: f32,
+    /// Unmultiplied RGBA.
+    pub color: [f32; 4],
+}
+
+impl Default for GridSettings {
+    fn default() -> Self {
+        Self {
+            visible: true,
+            axes: true,
+            unit: 0.1,
+            fade_distance: 20.0,
+            color: [0.6, 0.6, 0.6, 0.6],
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct GridParams {
+    view_proj: [[f32; 4]; 4],
+    inverse_view_proj: [[f32; 4]; 4],
+    color: [f32; 4],
+    x_axis_color: [f32; 4],
+    z_axis_color: [f32; 4],
+    unit: f32,
+    fade_distance: f32,
+    axes: f32,
+    _padding: f32,
+}
+
+pub struct GridResources {
+    camera: Arc<RwLock<Camera>>,
+    params_buffer: wgpu::Buffer,
+    bind_group: wgpu::BindGroup,
+    pipeline: wgpu::RenderPipeline,
+    /// Kept to rebuild the pipeline when the shaders change on disk.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    layout: wgpu::PipelineLayout,
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    target_format: wgpu::TextureFormat,
+    visible: bool,
+}
+
+impl Resource for GridResources {}
+
+impl GridResources {
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        shaders: &ShaderRegistry,
+        camera: Arc<RwLock<Camera>>,
+    ) -> Self {
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("grid_bind_group_layout"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::FRAGMENT,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("grid_params"),
+            size: std::mem::size_of::<GridParams>() as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("grid_bind_group"),
+            layout: &bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            }],
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("grid"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let pipeline = Self::create_pipeline(device, shaders, &layout, target_format)
+            .expect("built-in shaders should compile");
+
+        Self {
+            camera,
+            params_buffer,
+            bind_group,
+            pipeline,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            layout,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            target_format,
+            visible: false,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        layout: &wgpu::PipelineLayout,
+        target_format: wgpu::TextureFormat,
+    ) -> Result<wgpu::RenderPipeline, String> {
+        let shader = pipeline::shader_module(device, shaders, "grid", &Default::default())?;
+        Ok(
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some("grid"),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point: "fs_main",
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format: target_format,
+                        blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                // Hidden behind the scene, but never hiding anything drawn later.
+                depth_stencil: Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::LessEqual,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            }),
+        )
+    }
+
+    /// Uploads the camera and settings. Must follow the camera's own update
+    /// for the frame.
+    pub fn prepare(&mut self, queue: &wgpu::Queue, settings: &GridSettings) {
+        let view_proj = self.camera.read().unwrap().build_view_projection_matrix();
+        let inverse_view_proj = view_proj.invert();
+        self.visible = settings.visible && inverse_view_proj.is_some();
+        let Some(inverse_view_proj) = inverse_view_proj else {
+            return;
+        };
+        let params = GridParams {
+            view_proj: view_proj.into(),
+            inverse_view_proj: inverse_view_proj.into(),
+            color: settings.color,
+            x_axis_color: AXIS_COLORS[0],
+            z_axis_color: AXIS_COLORS[2],
+            unit: settings.unit.max(f32::EPSILON),
+            fade_distance: settings.fade_distance.max(f32::EPSILON),
+            axes: if settings.axes { 1.0 } else { 0.0 },
+            _padding: 0.0,
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::bytes_of(&params));
+    }
+
+    pub fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
+        if !self.visible {
+            return;
+        }
+        render_pass.set_pipeline(&self.pipeline);
+        render_pass.set_bind_group(0, &self.bind_group, &[]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+#[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+impl crate::hot_reload::ReloadPipelines for GridResources {
+    type Pipelines = wgpu::RenderPipeline;
+
+    fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+    ) -> Result<Self::Pipelines, String> {
+        Self::create_pipeline(device, shaders, &self.layout, self.target_format)
+    }
+
+    fn set_pipelines(&mut self, pipeline: Self::Pipelines) {
+        self.pipeline = pipeline;
+    }
+}
@@ -0,0 +1,644 @@
+use std::sync::Arc;
+
+use crate::{
+    clipping::ClipSettings,
+    light::{Light, Lights},
+    scene::{Material, MaterialId, Node, NodeId, Scene, TreePosition},
+    transform::Transform,
+};
+
+/// How many edits can be undone before the oldest are forgotten.
+const MAX_UNDO: usize = 256;
+
+/// The parts of a node that are edited in the outliner, inspector and gizmo.
+#[derive(Clone, Debug, PartialEq)]
+pub struct NodeProperties {
+    pub name: String,
+    pub visible: bool,
+    pub transform: Transform,
+    pub material: Option<MaterialId>,
+}
+
+impl NodeProperties {
+    pub fn of(node: &Node) -> Self {
+        Self {
+            name: node.name.clone(),
+            visible: node.visible,
+            transform: node.transform,
+            material: node.material,
+        }
+    }
+
+    fn apply(&self, node: &mut Node) {
+        node.name.clone_from(&self.name);
+        node.visible = self.visible;
+        node.transform = self.transform;
+        node.material = self.material;
+    }
+}
+
+/// One undoable change to the scene, holding enough to make it in either
+/// direction.
+enum Command {
+    EditNode {
+        node: NodeId,
+        before: NodeProperties,
+        after: NodeProperties,
+    },
+    EditMaterial {
+        material: MaterialId,
+        before: Material,
+        after: Material,
+    },
+    EditLight {
+        index: usize,
+        before: Light,
+        after: Light,
+    },
+    Add {
+        node: NodeId,
+        position: TreePosition,
+    },
+    AddLight {
+        index: usize,
+        light: Light,
+    },
+    /// Boxed, as every plane is kept on both sides.
+    EditClipping {
+        before: Box<ClipSettings>,
+        after: Box<ClipSettings>,
+    },
+    /// Several nodes and lights, each in the order they were removed.
+    Delete {
+        nodes: Vec<(NodeId, TreePosition)>,
+        lights: Vec<(usize, Light)>,
+    },
+    Reparent {
+        node: NodeId,
+        from: TreePosition,
+        to: TreePosition,
+    },
+}
+
+impl Command {
+    fn undo(&self, scene: &mut Scene, lights: &mut Lights, clipping: &mut ClipSettings) {
+        match self {
+            Command::EditNode { node, before, .. } => before.apply(scene.node_mut(*node)),
+            Command::EditMaterial {
+                material, before, ..
+            } => *scene.material_mut(*material) = before.clone(),
+            Command::EditLight { index, before, .. } => {
+                *lights.get_mut(*index).unwrap() = before.clone();
+            }
+            Command::Add { node, .. } => scene.remove(*node),
+            Command::AddLight { index, .. } => {
+                lights.remove(*index);
+            }
+            Command::EditClipping { before, .. } => *clipping = **before,
+            Command::Delete {
+                nodes,
+                lights: removed,
+            } => {
+                for &(node, position) in nodes.iter().rev() {
+                    scene.insert(node, position);
+                }
+                for (index, light) in removed.iter().rev() {
+                    lights.insert(*index, light.clone());
+                }
+            }
+            Command::Reparent { node, from, .. } => scene.insert(*node, *from),
+        }
+    }
+
+    fn redo(&self, scene: &mut Scene, lights: &mut Lights, clipping: &mut ClipSettings) {
+        match self {
+            Command::EditNode { node, after, .. } => after.apply(scene.node_mut(*node)),
+            Command::EditMaterial {
+                material, after, ..
+            } => *scene.material_mut(*material) = after.clone(),
+            Command::EditLight { index, after, .. } => {
+                *lights.get_mut(*index).unwrap() = after.clone();
+            }
+            Command::Add { node, position } => scene.insert(*node, *position),
+            Command::AddLight { index, light } => lights.insert(*index, light.clone()),
+            Command::EditClipping { after, .. } => *clipping = **after,
+            Command::Delete {
+                nodes,
+                lights: removed,
+            } => {
+                for &(node, _) in nodes {
+                    scene.remove(node);
+                }
+                for &(index, _) in removed {
+                    lights.remove(index);
+                }
+            }
+            Command::Reparent { node, to, .. } => scene.insert(*node, *to),
+        }
+    }
+
+    /// Folds `next` into this command if both edit the same thing, keeping
+    /// this command's starting state. Returns whether it did.
+    fn merge(&mut self, next: &Command) -> bool {
+        match (self, next) {
+            (
+                Command::EditNode { node, after, .. },
+                Command::EditNode {
+                    node: next_node,
+                    after: next_after,
+                    ..
+                },
+            ) if node == next_node => {
+                after.clone_from(next_after);
+                true
+            }
+            (
+                Command::EditMaterial {
+                    material, after, ..
+                },
+                Command::EditMaterial {
+                    material: next_material,
+                    after: next_after,
+                    ..
+                },
+            ) if material == next_material => {
+                after.clone_from(next_after);
+                true
+            }
+            (
+                Command::EditLight { index, after, .. },
+                Command::EditLight {
+                    index: next_index,
+                    after: next_after,
+                    ..
+                },
+            ) if index == next_index => {
+                after.clone_from(next_after);
+                true
+            }
+            (
+                Command::EditClipping { after, .. },
+                Command::EditClipping {
+                    after: next_after, ..
+                },
+            ) => {
+                after.clone_from(next_after);
+                true
+            }
+            _ => false,
+        }
+    }
+
+    /// Names the change for the Edit menu.
+    fn label(&self) -> &'static str {
+        match self {
+            Command::EditNode { before, after, .. } => {
+                if before.name != after.name {
+                    "Rename"
+                } else if before.visible != after.visible {
+                    "Visibility"
+                } else if before.material != after.material {
+                    "Material Assignment"
+                } else {
+                    "Transform"
+                }
+            }
+            Command::EditMaterial { .. } => "Material Edit",
+            Command::EditLight { .. } => "Light Edit",
+            Command::Add { .. } => "Add Node",
+            Command::AddLight { .. } => "Add Light",
+            Command::EditClipping { .. } => "Clipping",
+            Command::Delete { .. } => "Delete",
+            Command::Reparent { .. } => "Reparent",
+        }
+    }
+}
+
+/// The undo and redo stacks for edits to the scene.
+///
+/// Every change to the scene tree, its nodes, its materials, the lights and
+/// the clipping planes goes through here rather than straight to the
+/// [`Scene`], [`Lights`] or [`ClipSettings`]. Edits that follow each other on
+/// the same node, material or light, or on the clipping, are merged into one
+/// step until [`History::seal`] is called, so a whole drag is undone at once.
+///
+/// The camera is a way of looking at the scene rather than part of it, so it
+/// is changed directly and can't be undone.
+#[derive(Default)]
+pub struct History {
+    undo: Vec<Command>,
+    redo: Vec<Command>,
+    /// Whether the next edit starts a new step.
+    sealed: bool,
+}
+
+impl History {
+    /// Changes the properties of `node` with `edit`, if it changes anything.
+    pub fn edit_node(
+        &mut self,
+        scene: &mut Scene,
+        node: NodeId,
+        edit: impl FnOnce(&mut NodeProperties),
+    ) {
+        let before = NodeProperties::of(scene.node(node));
+        let mut after = before.clone();
+        edit(&mut after);
+        if after == before {
+            return;
+        }
+        after.apply(scene.node_mut(node));
+        self.push(Command::EditNode {
+            node,
+            before,
+            after,
+        });
+    }
+
+    /// Changes `material` with `edit`, if it changes anything.
+    pub fn edit_material(
+        &mut self,
+        scene: &mut Scene,
+        material: MaterialId,
+        edit: impl FnOnce(&mut Material),
+    ) {
+        let before = scene.material(material).clone();
+        let mut after = before.clone();
+        edit(&mut after);
+        let texture_changed = match (&before.texture, &after.texture) {
+            (Some(before), Some(after)) => !Arc::ptr_eq(before, after),
+            (before, after) => before.is_some() != after.is_some(),
+        };
+        if after.name == before.name && after.base_color == before.base_color && !texture_changed {
+            return;
+        }
+        *scene.material_mut(material) = after.clone();
+        self.push(Command::EditMaterial {
+            material,
+            before,
+            after,
+        });
+    }
+
+    /// Changes the light at `index` with `edit`, if it changes anything.
+    pub fn edit_light(&mut self, lights: &mut Lights, index: usize, edit: impl FnOnce(&mut Light)) {
+        let Some(light) = lights.get_mut(index) else {
+            return;
+        };
+        let before = light.clone();
+        edit(light);
+        if *light == before {
+            return;
+        }
+        let after = light.clone();
+        self.push(Command::EditLight {
+            index,
+            before,
+            after,
+        });
+    }
+
+    /// Adds `light` after the others, returning its index, or `None` if there
+    /// is no room for it.
+    pub fn add_light(&mut self, lights: &mut Lights, light: Light) -> Option<usize> {
+        let index = lights.add(light.clone())?;
+        self.push(Command::AddLight { index, light });
+        Some(index)
+    }
+
+    /// Changes the clipping planes and section box with `edit`, if it changes
+    /// anything.
+    pub fn edit_clipping(
+        &mut self,
+        clipping: &mut ClipSettings,
+        edit: impl FnOnce(&mut ClipSettings),
+    ) {
+        let before = *clipping;
+        edit(clipping);
+        if *clipping == before {
+            return;
+        }
+        self.push(Command::EditClipping {
+            before: Box::new(before),
+            after: Box::new(*clipping),
+        });
+    }
+
+    /// Records that `node` was just added to the scene, so that undoing
+    /// removes it again.
+    pub fn added(&mut self, scene: &Scene, node: NodeId) {
+        self.push(Command::Add {
+            node,
+            position: scene.position(node),
+        });
+    }
+
+    /// Removes `nodes` and their descendants from the scene, and the lights
+    /// at `light_indices`, as one step.
+    ///
+    /// Nodes already out of the tree, or under another of the nodes, are
+    /// skipped, as are lights that don't exist.
+    pub fn delete(
+        &mut self,
+        scene: &mut Scene,
+        nodes: &[NodeId],
+        lights: &mut Lights,
+        light_indices: &[usize],
+    ) {
+        let mut removed_nodes = Vec::new();
+        for &node in nodes {
+            if !scene.contains(node) || scene.ancestors(node).any(|id| nodes.contains(&id)) {
+                continue;
+            }
+            removed_nodes.push((node, scene.position(node)));
+            scene.remove(node);
+        }
+        // From the back, so that the indices still to go stay put.
+        let mut light_indices = light_indices.to_vec();
+        light_indices.sort_unstable();
+        light_indices.dedup();
+        let mut removed_lights = Vec::new();
+        for &index in light_indices.iter().rev() {
+            if index < lights.len() {
+                removed_lights.push((index, lights.remove(index)));
+            }
+        }
+        if !removed_nodes.is_empty() || !removed_lights.is_empty() {
+            self.push(Command::Delete {
+                nodes: removed_nodes,
+                lights: removed_lights,
+            });
+        }
+    }
+
+    /// Moves `node` under `parent`, or to the root, as with
+    /// [`Scene::set_parent`].
+    pub fn set_parent(
+        &mut self,
+        scene: &mut Scene,
+        node: NodeId,
+        parent: Option<NodeId>,
+    ) -> Result<(), String> {
+        let from = scene.position(node);
+        scene.set_parent(node, parent)?;
+        let to = scene.position(node);
+        if to != from {
+            self.push(Command::Reparent { node, from, to });
+        }
+        Ok(())
+    }
+
+    /// Reverts the last step, returning whether there was one.
+    pub fn undo(
+        &mut self,
+        scene: &mut Scene,
+        lights: &mut Lights,
+        clipping: &mut ClipSettings,
+    ) -> bool {
+        let Some(command) = self.undo.pop() else {
+            return false;
+        };
+        command.undo(scene, lights, clipping);
+        self.redo.push(command);
+        self.sealed = true;
+        true
+    }
+
+    /// Makes the last undone step again, returning whether there was one.
+    pub fn redo(
+        &mut self,
+        scene: &mut Scene,
+        lights: &mut Lights,
+        clipping: &mut ClipSettings,
+    ) -> bool {
+        let Some(command) = self.redo.pop() else {
+            return false;
+        };
+        command.redo(scene, lights, clipping);
+        self.undo.push(command);
+        self.sealed = true;
+        true
+    }
+
+    /// What [`History::undo`] would revert, if anything.
+    pub fn undo_label(&self) -> Option<&'static str> {
+        self.undo.last().map(Command::label)
+    }
+
+    /// What [`History::redo`] would make again, if anything.
+    pub fn redo_label(&self) -> Option<&'static str> {
+        self.redo.last().map(Command::label)
+    }
+
+    /// Starts a new step with the next edit, instead of merging it into the
+    /// last one.
+    pub fn seal(&mut self) {
+        self.sealed = true;
+    }
+
+    /// Forgets everything, such as when another scene is opened.
+    pub fn clear(&mut self) {
+        self.undo.clear();
+        self.redo.clear();
+    }
+
+    fn push(&mut self, command: Command) {
+        self.redo.clear();
+        let merged = match self.undo.last_mut() {
+            Some(last) if !self.sealed => last.merge(&command),
+            _ => false,
+        };
+        if !merged {
+            if self.undo.len() == MAX_UNDO {
+                self.undo.remove(0);
+            }
+            self.undo.push(command);
+        }
+        self.sealed = false;
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::PointLight;
+
+    fn rename(history: &mut History, scene: &mut Scene, node: NodeId, name: &str) {
+        history.edit_node(scene, node, |properties| properties.name = name.to_owned());
+    }
+
+    fn point_light(intensity: f32) -> Light {
+        Light::Point(PointLight {
+            position: cgmath::Point3::new(0.0, 0.0, 0.0),
+            color: [1.0; 3],
+            intensity,
+            range: 1.0,
+        })
+    }
+
+    fn intensity(lights: &Lights, index: usize) -> f32 {
+        match lights.get(index).unwrap() {
+            Light::Point(light) => light.intensity,
+            Light::Spot(light) => light.intensity,
+        }
+    }
+
+    #[test]
+    fn merges_edits_until_sealed() {
+        let mut scene = Scene::default();
+        let mut lights = Lights::default();
+        let mut clipping = ClipSettings::default();
+        let node = scene.add_node("a", None);
+        let mut history = History::default();
+
+        rename(&mut history, &mut scene, node, "b");
+        rename(&mut history, &mut scene, node, "c");
+        history.seal();
+        rename(&mut history, &mut scene, node, "d");
+
+        assert!(history.undo(&mut scene, &mut lights, &mut clipping));
+        assert_eq!(scene.node(node).name, "c");
+        assert!(history.undo(&mut scene, &mut lights, &mut clipping));
+        assert_eq!(scene.node(node).name, "a");
+        assert!(!history.undo(&mut scene, &mut lights, &mut clipping));
+
+        assert!(history.redo(&mut scene, &mut lights, &mut clipping));
+        assert_eq!(scene.node(node).name, "c");
+    }
+
+    #[test]
+    fn does_not_merge_edits_to_different_nodes() {
+        let mut scene = Scene::default();
+        let mut lights = Lights::default();
+        let mut clipping = ClipSettings::default();
+        let a = scene.add_node("a", None);
+        let b = scene.add_node("b", None);
+        let mut history = History::default();
+
+        rename(&mut history, &mut scene, a, "a2");
+        rename(&mut history, &mut scene, b, "b2");
+        history.undo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!((&*scene.node(a).name, &*scene.node(b).name), ("a2", "b"));
+    }
+
+    #[test]
+    fn ignores_edits_that_change_nothing() {
+        let mut scene = Scene::default();
+        let node = scene.add_node("a", None);
+        let mut history = History::default();
+        rename(&mut history, &mut scene, node, "a");
+        assert_eq!(history.undo_label(), None);
+    }
+
+    #[test]
+    fn new_edits_clear_redo() {
+        let mut scene = Scene::default();
+        let mut lights = Lights::default();
+        let mut clipping = ClipSettings::default();
+        let node = scene.add_node("a", None);
+        let mut history = History::default();
+        rename(&mut history, &mut scene, node, "b");
+        history.undo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!(history.redo_label(), Some("Rename"));
+        rename(&mut history, &mut scene, node, "c");
+        assert_eq!(history.redo_label(), None);
+    }
+
+    #[test]
+    fn forgets_the_oldest_steps() {
+        let mut scene = Scene::default();
+        let mut lights = Lights::default();
+        let mut clipping = ClipSettings::default();
+        let node = scene.add_node("0", None);
+        let mut history = History::default();
+        for step in 1..=MAX_UNDO + 10 {
+            history.seal();
+            rename(&mut history, &mut scene, node, &step.to_string());
+        }
+        let mut undone = 0;
+        while history.undo(&mut scene, &mut lights, &mut clipping) {
+            undone += 1;
+        }
+        assert_eq!(undone, MAX_UNDO);
+        assert_eq!(scene.node(node).name, "10");
+    }
+
+    #[test]
+    fn deletes_nodes_and_lights_as_one_step() {
+        let mut scene = Scene::default();
+        let mut lights = Lights::default();
+        let mut clipping = ClipSettings::default();
+        for i in 0..4 {
+            lights.add(point_light(i as f32));
+        }
+        let parent = scene.add_node("parent", None);
+        let child = scene.add_node("child", Some(parent));
+        let other = scene.add_node("other", None);
+        let mut history = History::default();
+
+        history.delete(&mut scene, &[child, parent], &mut lights, &[3, 1, 1]);
+        assert_eq!(scene.roots(), [other]);
+        let left: Vec<_> = (0..lights.len()).map(|i| intensity(&lights, i)).collect();
+        assert_eq!(left, [0.0, 2.0]);
+
+        history.undo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!(scene.roots(), [parent, other]);
+        assert_eq!(scene.node(parent).children(), [child]);
+        let restored: Vec<_> = (0..lights.len()).map(|i| intensity(&lights, i)).collect();
+        assert_eq!(restored, [0.0, 1.0, 2.0, 3.0]);
+        assert_eq!(history.undo_label(), None);
+    }
+
+    #[test]
+    fn undoes_light_edits_and_additions() {
+        let mut scene = Scene::default();
+        let mut lights = Lights::default();
+        let mut clipping = ClipSettings::default();
+        let mut history = History::default();
+
+        let index = history.add_light(&mut lights, point_light(1.0)).unwrap();
+        history.seal();
+        for value in [2.0, 3.0] {
+            history.edit_light(&mut lights, index, |light| *light = point_light(value));
+        }
+        assert_eq!(history.undo_label(), Some("Light Edit"));
+
+        history.undo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!(intensity(&lights, index), 1.0);
+        history.undo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!(lights.len(), 0);
+
+        history.redo(&mut scene, &mut lights, &mut clipping);
+        history.redo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!(intensity(&lights, index), 3.0);
+    }
+
+    #[test]
+    fn undoes_clipping_edits() {
+        let mut scene = Scene::default();
+        let mut lights = Lights::default();
+        let mut clipping = ClipSettings::default();
+        let mut history = History::default();
+
+        let mut index = None;
+        history.edit_clipping(&mut clipping, |clipping| index = clipping.add_plane());
+        let index = index.unwrap();
+        history.seal();
+        for offset in [0.5, 1.0] {
+            history.edit_clipping(&mut clipping, |clipping| {
+                clipping.planes[index].as_mut().unwrap().origin.x = offset;
+            });
+        }
+        history.edit_clipping(&mut clipping, |_| {});
+        assert_eq!(history.undo_label(), Some("Clipping"));
+
+        history.undo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!(clipping.planes[index].unwrap().origin.x, 0.0);
+        history.undo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!(clipping, ClipSettings::default());
+        assert_eq!(history.undo_label(), None);
+
+        history.redo(&mut scene, &mut lights, &mut clipping);
+        history.redo(&mut scene, &mut lights, &mut clipping);
+        assert_eq!(clipping.planes[index].unwrap().origin.x, 1.0);
+    }
+}
@@ -0,0 +1,117 @@
+use crate::{preprocessor::ShaderRegistry, renderer::Resource};
+use egui_wgpu::wgpu;
+use std::{
+    path::PathBuf,
+    time::{Duration, Instant, SystemTime},
+};
+
+/// Location of the shader modules in the source tree, watched in development builds.
+pub const SHADER_DIR: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/src/shaders");
+
+/// How often the watched directory's modification times are checked.
+pub const POLL_INTERVAL: Duration = Duration::from_millis(500);
+
+/// Watches the shader directory on disk and reports when any module in it changes.
+pub struct ShaderWatcher {
+    path: PathBuf,
+    last_modified: Option<SystemTime>,
+    last_checked: Option<Instant>,
+}
+
+impl ShaderWatcher {
+    pub fn new(path: impl Into<PathBuf>) -> Self {
+        let path = path.into();
+        // The binary already embeds the current contents, so only later edits count.
+        let last_modified = Self::modified(&path);
+        Self {
+            path,
+            last_modified,
+            last_checked: None,
+        }
+    }
+
+    /// The latest modification time of the directory or any file in it.
+    fn modified(path: &PathBuf) -> Option<SystemTime> {
+        let modified = |path: &std::path::Path| std::fs::metadata(path).and_then(|m| m.modified());
+        let mut latest = modified(path).ok()?;
+        for entry in std::fs::read_dir(path).ok()?.flatten() {
+            if let Ok(time) = modified(&entry.path()) {
+                latest = latest.max(time);
+            }
+        }
+        Some(latest)
+    }
+
+    /// Returns the reloaded modules if anything changed since the last call.
+    pub fn poll(&mut self) -> Option<std::io::Result<ShaderRegistry>> {
+        let now = Instant::now();
+        if self
+            .last_checked
+            .is_some_and(|last_checked| now - last_checked < POLL_INTERVAL)
+        {
+            return None;
+        }
+        self.last_checked = Some(now);
+
+        let modified = Self::modified(&self.path);
+        if modified.is_none() || modified == self.last_modified {
+            return None;
+        }
+        self.last_modified = modified;
+
+        log::info!("Reloading {}", self.path.display());
+        Some(ShaderRegistry::from_dir(&self.path))
+    }
+}
+
+/// Parses and validates preprocessed WGSL with naga, returning a readable error report.
+pub fn validate_wgsl(source: &str, label: &str) -> Result<(), String> {
+    let module = naga::front::wgsl::parse_str(source)
+        .map_err(|error| error.emit_to_string_with_path(source, label))?;
+
+    naga::valid::Validator::new(
+        naga::valid::ValidationFlags::all(),
+        naga::valid::Capabilities::all(),
+    )
+    .validate(&module)
+    .map_err(|error| format!("{label}: {}", error.emit_to_string(source)))?;
+
+    Ok(())
+}
+
+/// Runs `create`, also turning the wgpu validation errors it raises into an
+/// `Err` prefixed with `label`, so that shaders edited on disk can't panic the
+/// app.
+pub fn checked<T>(
+    device: &wgpu::Device,
+    label: &str,
+    create: impl FnOnce() -> Result<T, String>,
+) -> Result<T, String> {
+    device.push_error_scope(wgpu::ErrorFilter::Validation);
+    let result = create();
+    if let Some(error) = pollster::block_on(device.pop_error_scope()) {
+        return Err(format!("{label}: {error}"));
+    }
+    result
+}
+
+/// A resource whose pipelines are built outside the
+/// [`PipelineCache`](crate::pipeline::PipelineCache), rebuilt with it when the
+/// shader modules change on disk.
+///
+/// Reloading builds every resource's pipelines before replacing any, so that
+/// shaders sharing a module, like the forward and cluster passes, never run
+/// out of step.
+pub trait ReloadPipelines: Resource {
+    type Pipelines;
+
+    /// Builds the pipelines from `shaders`, leaving the ones in use alone.
+    fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+    ) -> Result<Self::Pipelines, String>;
+
+    /// Starts drawing with pipelines from [`ReloadPipelines::build_pipelines`].
+    fn set_pipelines(&mut self, pipelines: Self::Pipelines);
+}
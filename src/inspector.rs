@@ -0,0 +1,380 @@
+use cgmath::{Deg, Euler, Quaternion, Vector3};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::Camera,
+    clipping::{ClipPlane, ClipSettings, SectionBox},
+    history::{History, NodeProperties},
+    light::{Light, Lights},
+    outliner::DockSide,
+    scene::{MaterialId, NodeId, Scene},
+    selection::{Selection, SelectionSet},
+};
+
+/// A side panel editing whatever is selected in the outliner.
+///
+/// Edits to the scene are written back through the undo history as they are
+/// made, so the viewport follows along.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Inspector {
+    pub open: bool,
+    /// The Euler angles last shown for a node, kept while its rotation is
+    /// unchanged so that dragging one angle doesn't make the others jump
+    /// between equivalent solutions.
+    #[serde(skip)]
+    angles: Option<(NodeId, Quaternion<f32>, [f32; 3])>,
+}
+
+impl Default for Inspector {
+    fn default() -> Self {
+        Self {
+            open: true,
+            angles: None,
+        }
+    }
+}
+
+impl Inspector {
+    #[allow(clippy::too_many_arguments)]
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        side: DockSide,
+        selection: &SelectionSet,
+        scene: &mut Scene,
+        history: &mut History,
+        lights: &mut Lights,
+        camera: &mut Camera,
+        clipping: &mut ClipSettings,
+    ) {
+        if !self.open {
+            return;
+        }
+        side.panel("inspector")
+            .resizable(true)
+            .default_width(240.0)
+            .show(ctx, |ui| {
+                ui.heading("Inspector");
+                ui.separator();
+                if selection.len() > 1 {
+                    ui.weak(format!(
+                        "{} selected, showing the last one.",
+                        selection.len()
+                    ));
+                }
+                egui::ScrollArea::vertical().show(ui, |ui| match selection.active() {
+                    None => {
+                        ui.weak("Select something in the outliner.");
+                    }
+                    Some(Selection::Node(id)) => self.node_ui(ui, scene, history, id),
+                    Some(Selection::Light(index)) => match lights.get(index) {
+                        Some(light) => {
+                            let mut edited = light.clone();
+                            light_ui(ui, &mut edited);
+                            history.edit_light(lights, index, |light| *light = edited);
+                        }
+                        None => {
+                            ui.weak("This light no longer exists.");
+                        }
+                    },
+                    Some(Selection::Camera) => camera_ui(ui, camera),
+                    Some(Selection::ClipPlane(index)) => match clipping.planes[index] {
+                        Some(mut plane) => {
+                            let removed = clip_plane_ui(ui, &mut plane);
+                            history.edit_clipping(clipping, |clipping| {
+                                clipping.planes[index] = (!removed).then_some(plane);
+                            });
+                        }
+                        None => {
+                            ui.weak("This plane no longer exists.");
+                        }
+                    },
+                    Some(Selection::SectionBox) => {
+                        let mut section_box = clipping.section_box;
+                        section_box_ui(ui, &mut section_box, scene);
+                        history.edit_clipping(clipping, |clipping| {
+                            clipping.section_box = section_box;
+                        });
+                    }
+                });
+            });
+    }
+
+    fn node_ui(&mut self, ui: &mut egui::Ui, scene: &mut Scene, history: &mut History, id: NodeId) {
+        let node = scene.node(id);
+        let mut name = node.name.clone();
+        let mut visible = node.visible;
+        let mut transform = node.transform;
+        let mut material = node.material;
+
+        egui::Grid::new("node").num_columns(2).show(ui, |ui| {
+            ui.label("Name");
+            ui.text_edit_singleline(&mut name);
+            ui.end_row();
+
+            ui.label("Visible");
+            ui.checkbox(&mut visible, "");
+            ui.end_row();
+
+            ui.label("Translation");
+            vector_ui(ui, transform.translation.as_mut(), 0.01);
+            ui.end_row();
+
+            ui.label("Rotation");
+            let mut angles = match self.angles {
+                Some((node, rotation, angles)) if node == id && rotation == transform.rotation => {
+                    angles
+                }
+                _ => {
+                    let euler = Euler::from(transform.rotation);
+                    [euler.x, euler.y, euler.z].map(|angle| Deg::from(angle).0)
+                }
+            };
+            if vector_ui(ui, &mut angles, 1.0) {
+                transform.rotation =
+                    Euler::new(Deg(angles[0]), Deg(angles[1]), Deg(angles[2])).into();
+            }
+            self.angles = Some((id, transform.rotation, angles));
+            ui.end_row();
+
+            ui.label("Scale");
+            vector_ui(ui, transform.scale.as_mut(), 0.01);
+            ui.end_row();
+
+            ui.label("Mesh");
+            match node.mesh {
+                Some(mesh) => ui.label(format!(
+                    "Mesh {}, {} instances",
+                    mesh.index(),
+                    node.instances.len()
+                )),
+                None => ui.weak("None"),
+            };
+            ui.end_row();
+
+            ui.label("Material");
+            let material_name = |material: Option<MaterialId>| {
+                material.map_or("None", |id| &scene.material(id).name)
+            };
+            egui::ComboBox::from_id_source("material")
+                .selected_text(material_name(material))
+                .show_ui(ui, |ui| {
+                    ui.selectable_value(&mut material, None, "None");
+                    for id in scene.material_ids() {
+                        ui.selectable_value(&mut material, Some(id), material_name(Some(id)));
+                    }
+                });
+            ui.end_row();
+        });
+
+        let edited = NodeProperties {
+            name,
+            visible,
+            transform,
+            material,
+        };
+        history.edit_node(scene, id, |properties| *properties = edited);
+
+        if let Some(material) = material {
+            ui.separator();
+            material_ui(ui, scene, history, material);
+        }
+    }
+}
+
+/// Edits the parameters of a material, which may be shared by several nodes.
+fn material_ui(ui: &mut egui::Ui, scene: &mut Scene, history: &mut History, id: MaterialId) {
+    let material = scene.material(id);
+    let mut name = material.name.clone();
+    let mut base_color = material.base_color;
+
+    ui.strong("Material");
+    egui::Grid::new("material").num_columns(2).show(ui, |ui| {
+        ui.label("Name");
+        ui.text_edit_singleline(&mut name);
+        ui.end_row();
+
+        ui.label("Base color");
+        ui.color_edit_button_rgba_unmultiplied(&mut base_color);
+        ui.end_row();
+
+        ui.label("Texture");
+        match &material.texture {
+            Some(texture) => ui.label(texture.path.display().to_string()),
+            None => ui.weak("Default"),
+        };
+        ui.end_row();
+    });
+
+    history.edit_material(scene, id, |material| {
+        material.name = name;
+        material.base_color = base_color;
+    });
+}
+
+fn light_ui(ui: &mut egui::Ui, light: &mut Light) {
+    ui.strong(light.kind());
+    egui::Grid::new("light")
+        .num_columns(2)
+        .show(ui, |ui| match light {
+            Light::Point(light) => {
+                ui.label("Position");
+                vector_ui(ui, light.position.as_mut(), 0.01);
+                ui.end_row();
+                color_ui(ui, &mut light.color, &mut light.intensity, &mut light.range);
+            }
+            Light::Spot(light) => {
+                ui.label("Position");
+                vector_ui(ui, light.position.as_mut(), 0.01);
+                ui.end_row();
+
+                ui.label("Direction");
+                vector_ui(ui, light.direction.as_mut(), 0.01);
+                ui.end_row();
+                color_ui(ui, &mut light.color, &mut light.intensity, &mut light.range);
+
+                ui.label("Outer angle");
+                ui.add(
+                    egui::DragValue::new(&mut light.outer_angle.0)
+                        .clamp_range(0.0..=89.0)
+                        .suffix("°"),
+                );
+                ui.end_row();
+
+                ui.label("Inner angle");
+                ui.add(
+                    egui::DragValue::new(&mut light.inner_angle.0)
+                        .clamp_range(0.0..=light.outer_angle.0)
+                        .suffix("°"),
+                );
+                ui.end_row();
+            }
+        });
+}
+
+/// Edits a clipping plane, returning whether it should be removed.
+fn clip_plane_ui(ui: &mut egui::Ui, plane: &mut ClipPlane) -> bool {
+    ui.strong("Clipping plane");
+    egui::Grid::new("clip_plane").num_columns(2).show(ui, |ui| {
+        ui.label("Enabled");
+        ui.checkbox(&mut plane.enabled, "");
+        ui.end_row();
+
+        ui.label("Origin");
+        vector_ui(ui, plane.origin.as_mut(), 0.01);
+        ui.end_row();
+
+        ui.label("Normal");
+        let mut normal = plane.normal().into();
+        if vector_ui(ui, &mut normal, 0.01) {
+            plane.set_normal(Vector3::from(normal));
+        }
+        ui.end_row();
+    });
+    ui.horizontal(|ui| {
+        if ui
+            .button("Flip")
+            .on_hover_text("Cut away the other side")
+            .clicked()
+        {
+            plane.flip();
+        }
+        ui.button("Remove").clicked()
+    })
+    .inner
+}
+
+fn section_box_ui(ui: &mut egui::Ui, section_box: &mut SectionBox, scene: &Scene) {
+    ui.strong("Section box");
+    egui::Grid::new("section_box")
+        .num_columns(2)
+        .show(ui, |ui| {
+            ui.label("Enabled");
+            ui.checkbox(&mut section_box.enabled, "");
+            ui.end_row();
+
+            ui.label("Min");
+            vector_ui(ui, section_box.min.as_mut(), 0.01);
+            ui.end_row();
+
+            ui.label("Max");
+            vector_ui(ui, section_box.max.as_mut(), 0.01);
+            ui.end_row();
+        });
+    if ui.button("Fit to scene").clicked() {
+        if let Some(bounds) = scene.bounds() {
+            section_box.fit(&bounds);
+        }
+    }
+}
+
+/// The grid rows shared by every kind of light.
+fn color_ui(ui: &mut egui::Ui, color: &mut [f32; 3], intensity: &mut f32, range: &mut f32) {
+    ui.label("Color");
+    ui.color_edit_button_rgb(color);
+    ui.end_row();
+
+    ui.label("Intensity");
+    ui.add(
+        egui::DragValue::new(intensity)
+            .speed(0.01)
+            .clamp_range(0.0..=f32::MAX),
+    );
+    ui.end_row();
+
+    ui.label("Range");
+    ui.add(
+        egui::DragValue::new(range)
+            .speed(0.01)
+            .clamp_range(0.0..=f32::MAX),
+    );
+    ui.end_row();
+}
+
+fn camera_ui(ui: &mut egui::Ui, camera: &mut Camera) {
+    ui.strong("Camera");
+    egui::Grid::new("camera").num_columns(2).show(ui, |ui| {
+        ui.label("Eye");
+        vector_ui(ui, camera.eye.as_mut(), 0.01);
+        ui.end_row();
+
+        ui.label("Target");
+        vector_ui(ui, camera.target.as_mut(), 0.01);
+        ui.end_row();
+
+        ui.label("Field of view");
+        ui.add(
+            egui::DragValue::new(&mut camera.fovy)
+                .clamp_range(1.0..=170.0)
+                .suffix("°"),
+        );
+        ui.end_row();
+
+        ui.label("Near plane");
+        ui.add(
+            egui::DragValue::new(&mut camera.znear)
+                .speed(0.01)
+                .clamp_range(0.001..=camera.zfar),
+        );
+        ui.end_row();
+
+        ui.label("Far plane");
+        ui.add(egui::DragValue::new(&mut camera.zfar).clamp_range(camera.znear..=f32::MAX));
+        ui.end_row();
+    });
+}
+
+/// Drag values for x, y and z, returning whether any of them changed.
+fn vector_ui(ui: &mut egui::Ui, vector: &mut [f32; 3], speed: f64) -> bool {
+    ui.horizontal(|ui| {
+        let mut changed = false;
+        for (value, axis) in vector.iter_mut().zip(["x: ", "y: ", "z: "]) {
+            changed |= ui
+                .add(egui::DragValue::new(value).speed(speed).prefix(axis))
+                .changed();
+        }
+        changed
+    })
+    .inner
+}
@@ -1,7 +1,44 @@
 #![warn(clippy::all, rust_2018_idioms)]
 
+mod annotation;
 mod app;
+mod bounds;
 mod camera;
+mod clipping;
+mod debug_draw;
+mod gizmo;
+mod grid;
+mod history;
+#[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+mod hot_reload;
+mod inspector;
+mod light;
+mod measure;
+mod mesh;
+mod obj;
+mod outline;
+mod outliner;
+mod picking;
+mod pipeline;
+mod preprocessor;
+mod primitive;
+mod raycast;
 mod renderer;
+mod scene;
+mod scene_file;
+mod selection;
 mod texture;
+mod transform;
+mod view_cube;
+pub use annotation::{Annotation, AnnotationId, Annotations, Marker};
 pub use app::TemplateApp;
+pub use bounds::{Aabb, BoundingSphere, MeshBounds, Ray};
+pub use camera::Camera;
+pub use debug_draw::DebugDraw;
+pub use mesh::Mesh;
+pub use pipeline::DEPTH_BUFFER_BITS;
+pub use primitive::Primitive;
+pub use raycast::Hit;
+pub use scene::{Instance, MeshId, Node, NodeId, Scene};
+pub use scene_file::MeshSource;
+pub use transform::Transform;
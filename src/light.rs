@@ -0,0 +1,445 @@
+use crate::{
+    camera::Camera,
+    pipeline,
+    preprocessor::{ShaderDefines, ShaderRegistry},
+    renderer::Resource,
+};
+use egui_wgpu::{self, wgpu};
+use serde::{Deserialize, Serialize};
+use std::sync::{Arc, RwLock};
+
+/// Upper bound on the number of lights uploaded to the GPU each frame.
+pub const MAX_LIGHTS: usize = 1024;
+
+/// Number of light slots reserved for every cluster.
+pub const MAX_LIGHTS_PER_CLUSTER: usize = 128;
+
+/// Number of clusters along the x, y and z axes of the view frustum.
+pub const CLUSTER_DIMENSIONS: [u32; 3] = [16, 9, 24];
+
+const CLUSTER_COUNT: usize =
+    (CLUSTER_DIMENSIONS[0] * CLUSTER_DIMENSIONS[1] * CLUSTER_DIMENSIONS[2]) as usize;
+
+const WORKGROUP_SIZE: u32 = 4;
+
+/// The constants shared with `cluster.wgsl` and `lighting.wgsl`.
+pub fn shader_defines() -> ShaderDefines {
+    ShaderDefines::default()
+        .value(
+            "MAX_LIGHTS_PER_CLUSTER",
+            format!("{MAX_LIGHTS_PER_CLUSTER}u"),
+        )
+        .value("WORKGROUP_SIZE", WORKGROUP_SIZE)
+}
+
+const LIGHT_KIND_POINT: f32 = 0.0;
+const LIGHT_KIND_SPOT: f32 = 1.0;
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct PointLight {
+    pub position: cgmath::Point3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    /// Distance at which the light's contribution falls off to zero.
+    pub range: f32,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct SpotLight {
+    pub position: cgmath::Point3<f32>,
+    pub direction: cgmath::Vector3<f32>,
+    pub color: [f32; 3],
+    pub intensity: f32,
+    pub range: f32,
+    /// Half-angle of the fully lit inner cone.
+    pub inner_angle: cgmath::Deg<f32>,
+    /// Half-angle at which the light fades out completely.
+    pub outer_angle: cgmath::Deg<f32>,
+}
+
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Light {
+    Point(PointLight),
+    Spot(SpotLight),
+}
+
+impl Light {
+    pub fn kind(&self) -> &'static str {
+        match self {
+            Light::Point(_) => "Point light",
+            Light::Spot(_) => "Spot light",
+        }
+    }
+
+    fn to_uniform(&self) -> LightUniform {
+        use cgmath::{Angle, InnerSpace};
+        match self {
+            Light::Point(light) => LightUniform {
+                position_range: [
+                    light.position.x,
+                    light.position.y,
+                    light.position.z,
+                    light.range,
+                ],
+                color_intensity: [
+                    light.color[0],
+                    light.color[1],
+                    light.color[2],
+                    light.intensity,
+                ],
+                direction_kind: [0.0, 0.0, -1.0, LIGHT_KIND_POINT],
+                cone: [-1.0, -1.0, 0.0, 0.0],
+            },
+            Light::Spot(light) => {
+                let direction = light.direction.normalize();
+                LightUniform {
+                    position_range: [
+                        light.position.x,
+                        light.position.y,
+                        light.position.z,
+                        light.range,
+                    ],
+                    color_intensity: [
+                        light.color[0],
+                        light.color[1],
+                        light.color[2],
+                        light.intensity,
+                    ],
+                    direction_kind: [direction.x, direction.y, direction.z, LIGHT_KIND_SPOT],
+                    cone: [
+                        light.inner_angle.cos(),
+                        light.outer_angle.cos(),
+                        light.outer_angle.sin(),
+                        0.0,
+                    ],
+                }
+            }
+        }
+    }
+}
+
+/// The lights in the scene, shared between the app and [`LightResources`].
+#[derive(Default)]
+pub struct Lights {
+    lights: Vec<Light>,
+}
+
+impl Lights {
+    /// Adds `light` at the end, returning its index, or `None` if there are
+    /// already [`MAX_LIGHTS`].
+    pub fn add(&mut self, light: Light) -> Option<usize> {
+        if self.lights.len() >= MAX_LIGHTS {
+            log::warn!("Ignoring light, at most {MAX_LIGHTS} lights are supported");
+            return None;
+        }
+        self.lights.push(light);
+        Some(self.lights.len() - 1)
+    }
+
+    /// Puts back a light taken out with [`Lights::remove`], moving the ones
+    /// after it along.
+    pub fn insert(&mut self, index: usize, light: Light) {
+        self.lights.insert(index, light);
+    }
+
+    /// Takes out the light at `index`, moving the ones after it back.
+    pub fn remove(&mut self, index: usize) -> Light {
+        self.lights.remove(index)
+    }
+
+    pub fn len(&self) -> usize {
+        self.lights.len()
+    }
+
+    pub fn iter(&self) -> impl Iterator<Item = &Light> {
+        self.lights.iter()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&Light> {
+        self.lights.get(index)
+    }
+
+    pub fn get_mut(&mut self, index: usize) -> Option<&mut Light> {
+        self.lights.get_mut(index)
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct LightUniform {
+    position_range: [f32; 4],
+    color_intensity: [f32; 4],
+    // w holds the light kind
+    direction_kind: [f32; 4],
+    // cos(inner), cos(outer), sin(outer)
+    cone: [f32; 4],
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ClusterParams {
+    inverse_projection: [[f32; 4]; 4],
+    view: [[f32; 4]; 4],
+    // cluster dimensions in xyz, light count in w
+    grid: [u32; 4],
+    // origin and size of the viewport in physical pixels
+    viewport: [f32; 4],
+    // znear, zfar
+    depth: [f32; 4],
+}
+
+/// Bins the scene's lights into view-space clusters with a compute pass,
+/// so that the forward shader only has to shade the lights that touch a
+/// fragment's cluster.
+pub struct LightResources {
+    pub camera: Arc<RwLock<Camera>>,
+    pub lights: Arc<RwLock<Lights>>,
+    params_buffer: wgpu::Buffer,
+    light_buffer: wgpu::Buffer,
+    cluster_pipeline: wgpu::ComputePipeline,
+    /// Kept to rebuild the pipeline when the shaders change on disk.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    pipeline_layout: wgpu::PipelineLayout,
+    cluster_bind_group: wgpu::BindGroup,
+    bind_group: wgpu::BindGroup,
+}
+
+impl Resource for LightResources {}
+
+impl LightResources {
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        let storage = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Buffer {
+                ty: wgpu::BufferBindingType::Storage { read_only: true },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("Light Bind Group Layout"),
+            entries: &[
+                wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: false,
+                        min_binding_size: None,
+                    },
+                    count: None,
+                },
+                storage(1),
+                storage(2),
+                storage(3),
+            ],
+        })
+    }
+
+    pub fn new(
+        device: &wgpu::Device,
+        bind_group_layout: &wgpu::BindGroupLayout,
+        shaders: &ShaderRegistry,
+        camera: Arc<RwLock<Camera>>,
+        lights: Arc<RwLock<Lights>>,
+    ) -> Self {
+        use std::mem;
+
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cluster Params Buffer"),
+            size: mem::size_of::<ClusterParams>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let light_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Light Buffer"),
+            size: (mem::size_of::<LightUniform>() * MAX_LIGHTS) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let cluster_count_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cluster Light Count Buffer"),
+            size: (mem::size_of::<u32>() * CLUSTER_COUNT) as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let cluster_index_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Cluster Light Index Buffer"),
+            size: (mem::size_of::<u32>() * CLUSTER_COUNT * MAX_LIGHTS_PER_CLUSTER)
+                as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::STORAGE,
+            mapped_at_creation: false,
+        });
+
+        let buffer_entry = |binding, read_only| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::COMPUTE,
+            ty: wgpu::BindingType::Buffer {
+                ty: match read_only {
+                    Some(read_only) => wgpu::BufferBindingType::Storage { read_only },
+                    None => wgpu::BufferBindingType::Uniform,
+                },
+                has_dynamic_offset: false,
+                min_binding_size: None,
+            },
+            count: None,
+        };
+
+        let cluster_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("Cluster Bind Group Layout"),
+                entries: &[
+                    buffer_entry(0, None),
+                    buffer_entry(1, Some(true)),
+                    buffer_entry(2, Some(false)),
+                    buffer_entry(3, Some(false)),
+                ],
+            });
+
+        let entries = [
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: params_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: light_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 2,
+                resource: cluster_count_buffer.as_entire_binding(),
+            },
+            wgpu::BindGroupEntry {
+                binding: 3,
+                resource: cluster_index_buffer.as_entire_binding(),
+            },
+        ];
+
+        let cluster_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Cluster Bind Group"),
+            layout: &cluster_bind_group_layout,
+            entries: &entries,
+        });
+
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("Light Bind Group"),
+            layout: bind_group_layout,
+            entries: &entries,
+        });
+
+        let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("cluster"),
+            bind_group_layouts: &[&cluster_bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let cluster_pipeline = Self::create_pipeline(device, shaders, &pipeline_layout)
+            .expect("built-in shaders should compile");
+
+        Self {
+            camera,
+            lights,
+            params_buffer,
+            light_buffer,
+            cluster_pipeline,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            pipeline_layout,
+            cluster_bind_group,
+            bind_group,
+        }
+    }
+
+    fn create_pipeline(
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        layout: &wgpu::PipelineLayout,
+    ) -> Result<wgpu::ComputePipeline, String> {
+        let shader = pipeline::shader_module(device, shaders, "cluster", &shader_defines())?;
+        Ok(
+            device.create_compute_pipeline(&wgpu::ComputePipelineDescriptor {
+                label: Some("cluster"),
+                layout: Some(layout),
+                module: &shader,
+                entry_point: "cs_main",
+            }),
+        )
+    }
+
+    /// Uploads the lights and records the clustering compute pass.
+    pub fn prepare(
+        &mut self,
+        queue: &wgpu::Queue,
+        encoder: &mut wgpu::CommandEncoder,
+        viewport: egui::Rect,
+    ) {
+        use cgmath::SquareMatrix;
+
+        let lights = self.lights.read().unwrap();
+        let light_uniforms: Vec<LightUniform> =
+            lights.lights.iter().map(Light::to_uniform).collect();
+        if !light_uniforms.is_empty() {
+            queue.write_buffer(&self.light_buffer, 0, bytemuck::cast_slice(&light_uniforms));
+        }
+
+        let camera = self.camera.read().unwrap();
+        let inverse_projection = camera
+            .build_projection_matrix()
+            .invert()
+            .unwrap_or_else(cgmath::Matrix4::identity);
+        let (znear, zfar) = camera.depth_range();
+
+        let params = ClusterParams {
+            inverse_projection: inverse_projection.into(),
+            view: camera.build_view_matrix().into(),
+            grid: [
+                CLUSTER_DIMENSIONS[0],
+                CLUSTER_DIMENSIONS[1],
+                CLUSTER_DIMENSIONS[2],
+                light_uniforms.len() as u32,
+            ],
+            viewport: [
+                viewport.min.x,
+                viewport.min.y,
+                viewport.width(),
+                viewport.height(),
+            ],
+            depth: [znear, zfar, 0.0, 0.0],
+        };
+        queue.write_buffer(&self.params_buffer, 0, bytemuck::cast_slice(&[params]));
+
+        let mut compute_pass = encoder.begin_compute_pass(&wgpu::ComputePassDescriptor {
+            label: Some("cluster"),
+            timestamp_writes: None,
+        });
+        compute_pass.set_pipeline(&self.cluster_pipeline);
+        compute_pass.set_bind_group(0, &self.cluster_bind_group, &[]);
+        let [x, y, z] = CLUSTER_DIMENSIONS.map(|n| (n + WORKGROUP_SIZE - 1) / WORKGROUP_SIZE);
+        compute_pass.dispatch_workgroups(x, y, z);
+    }
+
+    pub fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
+        render_pass.set_bind_group(2, &self.bind_group, &[]);
+    }
+}
+
+#[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+impl crate::hot_reload::ReloadPipelines for LightResources {
+    type Pipelines = wgpu::ComputePipeline;
+
+    fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+    ) -> Result<Self::Pipelines, String> {
+        Self::create_pipeline(device, shaders, &self.pipeline_layout)
+    }
+
+    fn set_pipelines(&mut self, pipeline: Self::Pipelines) {
+        self.cluster_pipeline = pipeline;
+    }
+}
@@ -16,6 +16,7 @@ fn main() -> eframe::Result<()> {
                     .unwrap(),
             ),
         renderer: eframe::Renderer::Wgpu,
+        depth_buffer: octoren::DEPTH_BUFFER_BITS,
         ..Default::default()
     };
     eframe::run_native(
@@ -31,7 +32,10 @@ fn main() {
     // Redirect `log` message to `console.log` and friends:
     eframe::WebLogger::init(log::LevelFilter::Debug).ok();
 
-    let web_options = eframe::WebOptions::default();
+    let web_options = eframe::WebOptions {
+        depth_buffer: octoren::DEPTH_BUFFER_BITS,
+        ..Default::default()
+    };
 
     wasm_bindgen_futures::spawn_local(async {
         eframe::WebRunner::new()
@@ -0,0 +1,814 @@
+//! Measuring distances, angles and areas between points clicked on the
+//! scene's surfaces.
+//!
+//! Points snap to the corners and edges of the triangle under the pointer,
+//! and results are shown in the units chosen, assuming the scene is in the
+//! unit chosen for it.
+
+use cgmath::{EuclideanSpace, InnerSpace, Point3};
+use serde::{Deserialize, Serialize};
+
+use crate::{bounds::Ray, camera::Camera, gizmo::segment_distance, raycast::Hit, scene::Scene};
+
+/// How close the pointer has to be to a corner or edge to snap to it, in
+/// points.
+const SNAP_DISTANCE: f32 = 10.0;
+
+const POINT_RADIUS: f32 = 4.0;
+
+/// Radius of the arc drawn at the corner of an angle, in points.
+const ARC_RADIUS: f32 = 24.0;
+
+const LINE_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 200, 40);
+
+/// Space between a label's text and its edges, in points.
+const LABEL_PADDING: egui::Vec2 = egui::vec2(4.0, 2.0);
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MeasureKind {
+    /// Between two points.
+    Distance,
+    /// At the second of three points.
+    Angle,
+    /// Inside a polygon of three or more points.
+    Area,
+}
+
+impl MeasureKind {
+    pub const ALL: [MeasureKind; 3] =
+        [MeasureKind::Distance, MeasureKind::Angle, MeasureKind::Area];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            MeasureKind::Distance => "Distance",
+            MeasureKind::Angle => "Angle",
+            MeasureKind::Area => "Area",
+        }
+    }
+
+    /// How many points finish a measurement, if there's a fixed number.
+    fn points(self) -> Option<usize> {
+        match self {
+            MeasureKind::Distance => Some(2),
+            MeasureKind::Angle => Some(3),
+            MeasureKind::Area => None,
+        }
+    }
+
+    fn hint(self) -> &'static str {
+        match self {
+            MeasureKind::Distance => "Click two points.",
+            MeasureKind::Angle => "Click three points. The angle is at the second.",
+            MeasureKind::Area => {
+                "Click the corners, then the first corner again or press Enter to close."
+            }
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum LengthUnit {
+    Millimeters,
+    Centimeters,
+    Meters,
+    Kilometers,
+    Inches,
+    Feet,
+}
+
+impl LengthUnit {
+    pub const ALL: [LengthUnit; 6] = [
+        LengthUnit::Millimeters,
+        LengthUnit::Centimeters,
+        LengthUnit::Meters,
+        LengthUnit::Kilometers,
+        LengthUnit::Inches,
+        LengthUnit::Feet,
+    ];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            LengthUnit::Millimeters => "Millimeters",
+            LengthUnit::Centimeters => "Centimeters",
+            LengthUnit::Meters => "Meters",
+            LengthUnit::Kilometers => "Kilometers",
+            LengthUnit::Inches => "Inches",
+            LengthUnit::Feet => "Feet",
+        }
+    }
+
+    pub fn symbol(self) -> &'static str {
+        match self {
+            LengthUnit::Millimeters => "mm",
+            LengthUnit::Centimeters => "cm",
+            LengthUnit::Meters => "m",
+            LengthUnit::Kilometers => "km",
+            LengthUnit::Inches => "in",
+            LengthUnit::Feet => "ft",
+        }
+    }
+
+    fn meters(self) -> f64 {
+        match self {
+            LengthUnit::Millimeters => 0.001,
+            LengthUnit::Centimeters => 0.01,
+            LengthUnit::Meters => 1.0,
+            LengthUnit::Kilometers => 1000.0,
+            LengthUnit::Inches => 0.0254,
+            LengthUnit::Feet => 0.3048,
+        }
+    }
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AngleUnit {
+    Degrees,
+    Radians,
+}
+
+/// How measurements are shown.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MeasureUnits {
+    /// What one unit of the scene stands for.
+    pub scene: LengthUnit,
+    /// What lengths and areas are shown in.
+    pub display: LengthUnit,
+    pub angle: AngleUnit,
+    /// Digits after the decimal point.
+    pub decimals: usize,
+}
+
+impl Default for MeasureUnits {
+    fn default() -> Self {
+        Self {
+            scene: LengthUnit::Meters,
+            display: LengthUnit::Meters,
+            angle: AngleUnit::Degrees,
+            decimals: 3,
+        }
+    }
+}
+
+impl MeasureUnits {
+    /// `value` in scene units, in the display unit.
+    fn length(&self, value: f32) -> String {
+        let value = value as f64 * self.scene.meters() / self.display.meters();
+        format!("{value:.*} {}", self.decimals, self.display.symbol())
+    }
+
+    /// `value` in square scene units, in the square display unit.
+    fn area(&self, value: f32) -> String {
+        let value = value as f64 * (self.scene.meters() / self.display.meters()).powi(2);
+        format!("{value:.*} {}²", self.decimals, self.display.symbol())
+    }
+
+    /// `value` in radians.
+    fn angle(&self, value: f32) -> String {
+        match self.angle {
+            AngleUnit::Degrees => format!("{:.*}°", self.decimals, value.to_degrees()),
+            AngleUnit::Radians => format!("{value:.*} rad", self.decimals),
+        }
+    }
+}
+
+/// What the point under the pointer snapped to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+enum Snap {
+    Corner,
+    Edge,
+    Surface,
+}
+
+/// Points picked in the world and what they measure.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Measurement {
+    pub kind: MeasureKind,
+    pub points: Vec<Point3<f32>>,
+}
+
+impl Measurement {
+    /// The distance or area in scene units, or the angle in radians, once
+    /// there are enough points.
+    pub fn value(&self) -> Option<f32> {
+        match (self.kind, self.points.as_slice()) {
+            (MeasureKind::Distance, &[a, b]) => Some((b - a).magnitude()),
+            (MeasureKind::Angle, &[a, corner, b]) => {
+                let (u, v) = (a - corner, b - corner);
+                (u.magnitude2() > 0.0 && v.magnitude2() > 0.0).then(|| u.angle(v).0)
+            }
+            // Half the length of the sum of the cross products of the
+            // triangles fanning out from the first point, which is exact
+            // for flat polygons.
+            (MeasureKind::Area, points) if points.len() >= 3 => {
+                let origin = points[0];
+                let sum = points[1..]
+                    .windows(2)
+                    .map(|pair| (pair[0] - origin).cross(pair[1] - origin))
+                    .fold(cgmath::Vector3::new(0.0, 0.0, 0.0), |sum, cross| {
+                        sum + cross
+                    });
+                Some(sum.magnitude() / 2.0)
+            }
+            _ => None,
+        }
+    }
+
+    /// The value with its unit, once there are enough points.
+    pub fn describe(&self, units: &MeasureUnits) -> Option<String> {
+        let value = self.value()?;
+        Some(match self.kind {
+            MeasureKind::Distance => units.length(value),
+            MeasureKind::Angle => units.angle(value),
+            MeasureKind::Area => units.area(value),
+        })
+    }
+
+    /// Where the value is labeled.
+    fn label_anchor(&self) -> Option<Point3<f32>> {
+        match self.kind {
+            MeasureKind::Angle => self.points.get(1).copied(),
+            _ if self.points.is_empty() => None,
+            _ => Some(Point3::centroid(&self.points)),
+        }
+    }
+}
+
+/// The measuring tool, with its results.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Measure {
+    /// Whether clicks in the viewport place points instead of selecting.
+    #[serde(skip)]
+    pub active: bool,
+    pub kind: MeasureKind,
+    /// Whether points snap to the corners and edges of triangles.
+    pub snapping: bool,
+    pub units: MeasureUnits,
+    #[serde(skip)]
+    measurements: Vec<Measurement>,
+    /// The measurement being placed.
+    #[serde(skip)]
+    current: Option<Measurement>,
+    /// The point a click would place.
+    #[serde(skip)]
+    hovered: Option<(Point3<f32>, Snap)>,
+}
+
+impl Default for Measure {
+    fn default() -> Self {
+        Self {
+            active: false,
+            kind: MeasureKind::Distance,
+            snapping: true,
+            units: MeasureUnits::default(),
+            measurements: Vec::new(),
+            current: None,
+            hovered: None,
+        }
+    }
+}
+
+impl Measure {
+    pub fn toolbar_ui(&mut self, ui: &mut egui::Ui) {
+        ui.toggle_value(&mut self.active, "Measure")
+            .on_hover_text("Measure distances, angles and areas");
+        if self.active {
+            for kind in MeasureKind::ALL {
+                ui.selectable_value(&mut self.kind, kind, kind.label());
+            }
+        }
+    }
+
+    /// Lists the measurements, with the units they are shown in, while the
+    /// tool is active.
+    pub fn window(&mut self, ctx: &egui::Context) {
+        let mut open = self.active;
+        egui::Window::new("Measurements")
+            .open(&mut open)
+            .default_width(260.0)
+            .show(ctx, |ui| {
+                ui.horizontal(|ui| {
+                    for kind in MeasureKind::ALL {
+                        ui.selectable_value(&mut self.kind, kind, kind.label());
+                    }
+                });
+                ui.label(self.kind.hint());
+                ui.checkbox(&mut self.snapping, "Snap to corners and edges");
+
+                egui::CollapsingHeader::new("Units").show(ui, |ui| {
+                    let units = &mut self.units;
+                    egui::Grid::new("measure_units")
+                        .num_columns(2)
+                        .show(ui, |ui| {
+                            ui.label("One scene unit is");
+                            unit_combo(ui, "measure_scene_unit", &mut units.scene);
+                            ui.end_row();
+
+                            ui.label("Show lengths in");
+                            unit_combo(ui, "measure_display_unit", &mut units.display);
+                            ui.end_row();
+
+                            ui.label("Show angles in");
+                            ui.horizontal(|ui| {
+                                ui.radio_value(&mut units.angle, AngleUnit::Degrees, "Degrees");
+                                ui.radio_value(&mut units.angle, AngleUnit::Radians, "Radians");
+                            });
+                            ui.end_row();
+
+                            ui.label("Decimals");
+                            ui.add(egui::DragValue::new(&mut units.decimals).clamp_range(0..=6));
+                            ui.end_row();
+                        });
+                });
+                ui.separator();
+
+                if self.measurements.is_empty() {
+                    ui.weak("Nothing measured yet.");
+                }
+                let mut removed = None;
+                egui::Grid::new("measurements")
+                    .num_columns(3)
+                    .striped(true)
+                    .show(ui, |ui| {
+                        for (index, measurement) in self.measurements.iter().enumerate() {
+                            ui.label(format!("{} {}", measurement.kind.label(), index + 1));
+                            ui.monospace(measurement.describe(&self.units).unwrap_or_default());
+                            if ui.small_button("Remove").clicked() {
+                                removed = Some(index);
+                            }
+                            ui.end_row();
+                        }
+                    });
+                if let Some(index) = removed {
+                    self.measurements.remove(index);
+                }
+                if ui
+                    .add_enabled(!self.measurements.is_empty(), egui::Button::new("Clear"))
+                    .clicked()
+                {
+                    self.measurements.clear();
+                }
+            });
+        self.active = open;
+    }
+
+    /// Places points while the tool is active, and draws the measurements
+    /// over the viewport.
+    ///
+    /// `response` is the viewport's, covering `viewport`, and `hit` the
+    /// surface under the pointer.
+    pub fn show(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        viewport: egui::Rect,
+        camera: &Camera,
+        scene: &Scene,
+        hit: Option<Hit>,
+    ) {
+        if self.active {
+            self.interact(ui, response, viewport, camera, scene, hit);
+        } else {
+            self.current = None;
+            self.hovered = None;
+        }
+        self.paint(ui, viewport, camera);
+    }
+
+    fn interact(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        viewport: egui::Rect,
+        camera: &Camera,
+        scene: &Scene,
+        hit: Option<Hit>,
+    ) {
+        if self
+            .current
+            .as_ref()
+            .is_some_and(|current| current.kind != self.kind)
+        {
+            self.current = None;
+        }
+
+        let pointer = response.hover_pos();
+        // Clicking the first corner of an area again closes it.
+        let first_corner = self
+            .current
+            .as_ref()
+            .filter(|current| current.kind == MeasureKind::Area && current.points.len() >= 3)
+            .map(|current| current.points[0]);
+        let closing = first_corner.filter(|&corner| {
+            let screen = camera.project(corner, viewport);
+            pointer
+                .zip(screen)
+                .is_some_and(|(pointer, screen)| pointer.distance(screen) < SNAP_DISTANCE)
+        });
+        self.hovered = match closing {
+            Some(corner) => Some((corner, Snap::Corner)),
+            None => pointer.and_then(|pointer| {
+                let hit = hit?;
+                Some(if self.snapping {
+                    snap(pointer, viewport, camera, scene, &hit)
+                } else {
+                    (hit.position, Snap::Surface)
+                })
+            }),
+        };
+
+        if response.clicked() {
+            if closing.is_some() {
+                self.finish();
+            } else if let Some((point, _)) = self.hovered {
+                let kind = self.kind;
+                let current = self.current.get_or_insert_with(|| Measurement {
+                    kind,
+                    points: Vec::new(),
+                });
+                current.points.push(point);
+                if Some(current.points.len()) == kind.points() {
+                    self.finish();
+                }
+            }
+        }
+
+        if ui.memory(|memory| memory.focus().is_some()) {
+            return;
+        }
+        let [enter, backspace, escape] =
+            [egui::Key::Enter, egui::Key::Backspace, egui::Key::Escape]
+                .map(|key| ui.input_mut(|i| i.consume_key(egui::Modifiers::NONE, key)));
+        if enter {
+            self.finish();
+        }
+        if backspace {
+            if let Some(current) = &mut self.current {
+                current.points.pop();
+                if current.points.is_empty() {
+                    self.current = None;
+                }
+            }
+        }
+        // Escape drops the measurement being placed, or leaves the tool.
+        if escape && self.current.take().is_none() {
+            self.active = false;
+        }
+    }
+
+    /// Keeps the measurement being placed, if it has enough points.
+    fn finish(&mut self) {
+        if let Some(current) = self.current.take() {
+            if current.value().is_some() {
+                self.measurements.push(current);
+            }
+        }
+    }
+
+    fn paint(&self, ui: &egui::Ui, viewport: egui::Rect, camera: &Camera) {
+        let painter = ui.painter_at(viewport.intersect(ui.clip_rect()));
+        let project = |point| camera.project(point, viewport);
+        for measurement in &self.measurements {
+            self.paint_measurement(&painter, &project, measurement, true);
+        }
+        if let Some(current) = &self.current {
+            // Follow the pointer with the next point.
+            let mut preview = current.clone();
+            preview.points.extend(self.hovered.map(|(point, _)| point));
+            self.paint_measurement(&painter, &project, &preview, false);
+        }
+
+        let Some((point, snap)) = self.hovered else {
+            return;
+        };
+        let Some(center) = project(point) else {
+            return;
+        };
+        let stroke = egui::Stroke::new(1.5, egui::Color32::WHITE);
+        let size = POINT_RADIUS + 2.0;
+        match snap {
+            Snap::Corner => {
+                painter.rect_stroke(
+                    egui::Rect::from_center_size(center, egui::Vec2::splat(size * 2.0)),
+                    0.0,
+                    stroke,
+                );
+            }
+            Snap::Edge => {
+                let corners = [(0.0, -1.0), (1.0, 0.0), (0.0, 1.0), (-1.0, 0.0)]
+                    .map(|(x, y)| center + egui::vec2(x, y) * size * 1.4);
+                painter.add(egui::Shape::closed_line(corners.to_vec(), stroke));
+            }
+            Snap::Surface => {
+                painter.circle_stroke(center, size, stroke);
+            }
+        }
+    }
+
+    fn paint_measurement(
+        &self,
+        painter: &egui::Painter,
+        project: &impl Fn(Point3<f32>) -> Option<egui::Pos2>,
+        measurement: &Measurement,
+        closed: bool,
+    ) {
+        let points: Vec<_> = measurement
+            .points
+            .iter()
+            .map(|&point| project(point))
+            .collect();
+        let stroke = egui::Stroke::new(2.0, LINE_COLOR);
+        let count = points.len();
+        let mut segments: Vec<_> = (1..count).map(|index| (index - 1, index)).collect();
+        if measurement.kind == MeasureKind::Area && closed && count >= 3 {
+            segments.push((count - 1, 0));
+        }
+        for (a, b) in segments {
+            if let (Some(a), Some(b)) = (points[a], points[b]) {
+                painter.line_segment([a, b], stroke);
+            }
+        }
+        if let (MeasureKind::Angle, &[Some(a), Some(corner), Some(b)]) =
+            (measurement.kind, points.as_slice())
+        {
+            paint_arc(painter, corner, a, b, stroke);
+        }
+        for point in points.iter().flatten() {
+            painter.circle(
+                *point,
+                POINT_RADIUS,
+                LINE_COLOR,
+                egui::Stroke::new(1.0, egui::Color32::BLACK),
+            );
+        }
+
+        let (Some(text), Some(anchor)) = (
+            measurement.describe(&self.units),
+            measurement.label_anchor().and_then(project),
+        ) else {
+            return;
+        };
+        let galley =
+            painter.layout_no_wrap(text, egui::FontId::proportional(13.0), egui::Color32::WHITE);
+        // Angles are labeled beside their corner, the rest on their middle.
+        let align = if measurement.kind == MeasureKind::Angle {
+            egui::Align2::LEFT_BOTTOM
+        } else {
+            egui::Align2::CENTER_CENTER
+        };
+        let offset = if measurement.kind == MeasureKind::Angle {
+            egui::vec2(ARC_RADIUS / 2.0, -ARC_RADIUS / 2.0)
+        } else {
+            egui::Vec2::ZERO
+        };
+        let rect = align
+            .anchor_size(anchor + offset, galley.size())
+            .expand2(LABEL_PADDING);
+        painter.rect_filled(rect, 3.0, egui::Color32::from_black_alpha(170));
+        painter.galley(rect.min + LABEL_PADDING, galley, egui::Color32::WHITE);
+    }
+}
+
+fn unit_combo(ui: &mut egui::Ui, id: &str, unit: &mut LengthUnit) {
+    egui::ComboBox::from_id_source(id)
+        .selected_text(unit.label())
+        .show_ui(ui, |ui| {
+            for option in LengthUnit::ALL {
+                ui.selectable_value(unit, option, option.label());
+            }
+        });
+}
+
+/// The point on the triangle under `pointer` to place: a corner or a point on
+/// an edge if one is near enough on screen, otherwise where the ray hit.
+fn snap(
+    pointer: egui::Pos2,
+    viewport: egui::Rect,
+    camera: &Camera,
+    scene: &Scene,
+    hit: &Hit,
+) -> (Point3<f32>, Snap) {
+    let corners = hit.corners(scene);
+    let screen = corners.map(|corner| camera.project(corner, viewport));
+
+    let corner = (0..3)
+        .filter_map(|i| Some((i, pointer.distance(screen[i]?))))
+        .filter(|&(_, distance)| distance < SNAP_DISTANCE)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    if let Some((i, _)) = corner {
+        return (corners[i], Snap::Corner);
+    }
+
+    let edge = (0..3)
+        .filter_map(|i| {
+            let j = (i + 1) % 3;
+            Some((i, segment_distance(pointer, screen[i]?, screen[j]?)))
+        })
+        .filter(|&(_, distance)| distance < SNAP_DISTANCE)
+        .min_by(|a, b| a.1.total_cmp(&b.1));
+    if let (Some((i, _)), Some(ray)) = (edge, camera.screen_ray(pointer, viewport)) {
+        let point = closest_on_segment(corners[i], corners[(i + 1) % 3], &ray);
+        return (point, Snap::Edge);
+    }
+
+    (hit.position, Snap::Surface)
+}
+
+/// The point on the segment from `a` to `b` nearest the line of `ray`.
+fn closest_on_segment(a: Point3<f32>, b: Point3<f32>, ray: &Ray) -> Point3<f32> {
+    let edge = b - a;
+    let offset = a - ray.origin;
+    let (ee, ed, dd) = (
+        edge.dot(edge),
+        edge.dot(ray.direction),
+        ray.direction.dot(ray.direction),
+    );
+    let denominator = ee * dd - ed * ed;
+    if denominator.abs() <= f32::EPSILON {
+        return a;
+    }
+    let s = (ed * ray.direction.dot(offset) - dd * edge.dot(offset)) / denominator;
+    a + edge * s.clamp(0.0, 1.0)
+}
+
+/// The shorter arc around `corner` from the direction of `a` to that of `b`.
+fn paint_arc(
+    painter: &egui::Painter,
+    corner: egui::Pos2,
+    a: egui::Pos2,
+    b: egui::Pos2,
+    stroke: egui::Stroke,
+) {
+    let start = (a - corner).angle();
+    let mut sweep = (b - corner).angle() - start;
+    if sweep > std::f32::consts::PI {
+        sweep -= std::f32::consts::TAU;
+    } else if sweep < -std::f32::consts::PI {
+        sweep += std::f32::consts::TAU;
+    }
+    let points = (0..=16)
+        .map(|step| {
+            let angle = start + sweep * step as f32 / 16.0;
+            corner + egui::Vec2::angled(angle) * ARC_RADIUS
+        })
+        .collect();
+    painter.add(egui::Shape::line(points, stroke));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cgmath::Vector3;
+
+    fn measurement(kind: MeasureKind, points: &[[f32; 3]]) -> Measurement {
+        Measurement {
+            kind,
+            points: points.iter().copied().map(Point3::from).collect(),
+        }
+    }
+
+    fn assert_near(actual: f32, expected: f32) {
+        assert!((actual - expected).abs() < 1e-5, "{actual} != {expected}");
+    }
+
+    #[test]
+    fn measures_distances() {
+        let distance = measurement(MeasureKind::Distance, &[[1.0, 1.0, 1.0], [4.0, 5.0, 1.0]]);
+        assert_eq!(distance.value(), Some(5.0));
+        assert_eq!(
+            measurement(MeasureKind::Distance, &[[0.0; 3]]).value(),
+            None
+        );
+    }
+
+    #[test]
+    fn measures_angles_at_the_second_point() {
+        let angle = |points: &[[f32; 3]]| measurement(MeasureKind::Angle, points).value();
+        assert_near(
+            angle(&[[3.0, 0.0, 0.0], [0.0; 3], [0.0, 0.0, 2.0]]).unwrap(),
+            std::f32::consts::FRAC_PI_2,
+        );
+        let height = 3.0_f32.sqrt();
+        assert_near(
+            angle(&[[0.0, 0.0, 0.0], [2.0, 0.0, 0.0], [1.0, height, 0.0]]).unwrap(),
+            std::f32::consts::FRAC_PI_3,
+        );
+        assert_near(
+            angle(&[[-1.0, 0.0, 0.0], [0.0; 3], [2.0, 0.0, 0.0]]).unwrap(),
+            std::f32::consts::PI,
+        );
+        // Without a direction on either side there is no angle.
+        assert_eq!(angle(&[[0.0; 3], [0.0; 3], [1.0, 0.0, 0.0]]), None);
+        assert_eq!(angle(&[[1.0, 0.0, 0.0], [0.0; 3]]), None);
+    }
+
+    #[test]
+    fn measures_areas_of_flat_polygons() {
+        let area = |points: &[[f32; 3]]| measurement(MeasureKind::Area, points).value();
+        assert_near(
+            area(&[[0.0; 3], [3.0, 0.0, 0.0], [0.0, 4.0, 0.0]]).unwrap(),
+            6.0,
+        );
+        // Concave, and in a tilted plane.
+        let l_shape = [
+            [0.0, 0.0],
+            [2.0, 0.0],
+            [2.0, 1.0],
+            [1.0, 1.0],
+            [1.0, 2.0],
+            [0.0, 2.0],
+        ]
+        .map(|[u, v]| [u, v * 0.6, v * 0.8]);
+        assert_near(area(&l_shape).unwrap(), 3.0);
+        // Either winding.
+        let mut reversed = l_shape;
+        reversed.reverse();
+        assert_near(area(&reversed).unwrap(), 3.0);
+        assert_eq!(area(&[[0.0; 3], [1.0, 0.0, 0.0]]), None);
+    }
+
+    #[test]
+    fn converts_lengths_between_units() {
+        let units = |scene, display| MeasureUnits {
+            scene,
+            display,
+            ..Default::default()
+        };
+        for unit in LengthUnit::ALL {
+            assert_eq!(
+                units(unit, unit).length(1.5),
+                format!("1.500 {}", unit.symbol())
+            );
+        }
+        assert_eq!(
+            units(LengthUnit::Meters, LengthUnit::Millimeters).length(1.0),
+            "1000.000 mm"
+        );
+        assert_eq!(
+            units(LengthUnit::Kilometers, LengthUnit::Centimeters).length(0.5),
+            "50000.000 cm"
+        );
+        assert_eq!(
+            units(LengthUnit::Feet, LengthUnit::Inches).length(2.0),
+            "24.000 in"
+        );
+        assert_eq!(
+            units(LengthUnit::Inches, LengthUnit::Meters).length(100.0),
+            "2.540 m"
+        );
+        assert_eq!(
+            units(LengthUnit::Meters, LengthUnit::Feet).length(0.3048),
+            "1.000 ft"
+        );
+    }
+
+    #[test]
+    fn converts_areas_by_the_square_of_the_length() {
+        let units = MeasureUnits {
+            scene: LengthUnit::Meters,
+            display: LengthUnit::Centimeters,
+            decimals: 1,
+            ..Default::default()
+        };
+        assert_eq!(units.area(2.0), "20000.0 cm²");
+        let units = MeasureUnits {
+            scene: LengthUnit::Feet,
+            display: LengthUnit::Inches,
+            decimals: 0,
+            ..Default::default()
+        };
+        assert_eq!(units.area(1.0), "144 in²");
+    }
+
+    #[test]
+    fn shows_angles_in_degrees_or_radians() {
+        let mut units = MeasureUnits::default();
+        assert_eq!(units.angle(std::f32::consts::FRAC_PI_2), "90.000°");
+        units.angle = AngleUnit::Radians;
+        units.decimals = 2;
+        assert_eq!(units.angle(std::f32::consts::FRAC_PI_2), "1.57 rad");
+    }
+
+    #[test]
+    fn finds_the_closest_point_on_a_segment() {
+        let (a, b) = (Point3::new(0.0, 0.0, 0.0), Point3::new(2.0, 0.0, 0.0));
+        let down = |x: f32| Ray {
+            origin: Point3::new(x, 1.0, 5.0),
+            direction: Vector3::new(0.0, 0.0, -3.0),
+        };
+        assert_eq!(
+            closest_on_segment(a, b, &down(0.5)),
+            Point3::new(0.5, 0.0, 0.0)
+        );
+        // Clamped to the ends.
+        assert_eq!(closest_on_segment(a, b, &down(3.0)), b);
+        assert_eq!(closest_on_segment(a, b, &down(-1.0)), a);
+        // A ray along the segment's line has no single closest point.
+        let along = Ray {
+            origin: Point3::new(-1.0, 0.0, 0.0),
+            direction: Vector3::unit_x(),
+        };
+        assert_eq!(closest_on_segment(a, b, &along), a);
+    }
+}
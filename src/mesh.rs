@@ -0,0 +1,439 @@
+use egui_wgpu::wgpu;
+use serde::{Deserialize, Serialize};
+use std::ops::Range;
+
+use crate::preprocessor::ShaderDefines;
+
+pub trait VertexTrait {
+    fn desc(&self) -> wgpu::VertexBufferLayout<'_>;
+}
+
+/// A per-vertex attribute a mesh can carry.
+///
+/// Every attribute has a fixed `@location` in the shaders, so any combination
+/// of them can be bound to the same shader module.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum VertexAttribute {
+    Position,
+    TexCoords,
+    Normal,
+    /// Tangent in xyz, with the bitangent's handedness in w.
+    Tangent,
+    Color,
+    /// A second UV set, typically used for lightmaps.
+    TexCoords1,
+}
+
+impl VertexAttribute {
+    pub const ALL: [VertexAttribute; 6] = [
+        VertexAttribute::Position,
+        VertexAttribute::TexCoords,
+        VertexAttribute::Normal,
+        VertexAttribute::Tangent,
+        VertexAttribute::Color,
+        VertexAttribute::TexCoords1,
+    ];
+
+    pub fn location(self) -> u32 {
+        match self {
+            VertexAttribute::Position => 0,
+            VertexAttribute::TexCoords => 1,
+            VertexAttribute::Normal => 2,
+            VertexAttribute::Tangent => 3,
+            VertexAttribute::Color => 4,
+            VertexAttribute::TexCoords1 => 5,
+        }
+    }
+
+    pub fn format(self) -> wgpu::VertexFormat {
+        match self {
+            VertexAttribute::Position | VertexAttribute::Normal => wgpu::VertexFormat::Float32x3,
+            VertexAttribute::TexCoords | VertexAttribute::TexCoords1 => {
+                wgpu::VertexFormat::Float32x2
+            }
+            VertexAttribute::Tangent | VertexAttribute::Color => wgpu::VertexFormat::Float32x4,
+        }
+    }
+
+    /// Defined while preprocessing shaders for meshes that carry the attribute.
+    fn shader_define(self) -> Option<&'static str> {
+        match self {
+            VertexAttribute::Position => None,
+            VertexAttribute::TexCoords => Some("VERTEX_TEX_COORDS"),
+            VertexAttribute::Normal => Some("VERTEX_NORMAL"),
+            VertexAttribute::Tangent => Some("VERTEX_TANGENT"),
+            VertexAttribute::Color => Some("VERTEX_COLOR"),
+            VertexAttribute::TexCoords1 => Some("VERTEX_TEX_COORDS1"),
+        }
+    }
+}
+
+/// The attributes stored in one vertex buffer, interleaved in the order they
+/// were added.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct VertexLayout {
+    array_stride: wgpu::BufferAddress,
+    step_mode: wgpu::VertexStepMode,
+    attributes: Vec<wgpu::VertexAttribute>,
+}
+
+impl Default for VertexLayout {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl VertexLayout {
+    /// A layout holding only [`VertexAttribute::Position`].
+    pub fn new() -> Self {
+        Self::custom(wgpu::VertexStepMode::Vertex, &[]).with(VertexAttribute::Position)
+    }
+
+    /// A layout of raw attributes that don't correspond to a [`VertexAttribute`].
+    pub fn custom(
+        step_mode: wgpu::VertexStepMode,
+        attributes: &[(wgpu::ShaderLocation, wgpu::VertexFormat)],
+    ) -> Self {
+        let mut layout = Self {
+            array_stride: 0,
+            step_mode,
+            attributes: Vec::new(),
+        };
+        for &(location, format) in attributes {
+            layout.push(location, format);
+        }
+        layout
+    }
+
+    /// Appends `attribute`, unless the layout already has it.
+    pub fn with(mut self, attribute: VertexAttribute) -> Self {
+        if !self.contains(attribute) {
+            self.push(attribute.location(), attribute.format());
+        }
+        self
+    }
+
+    pub fn contains(&self, attribute: VertexAttribute) -> bool {
+        self.attributes
+            .iter()
+            .any(|a| a.shader_location == attribute.location())
+    }
+
+    /// Flags telling the shaders which optional attributes are present.
+    pub fn shader_defines(&self) -> ShaderDefines {
+        VertexAttribute::ALL
+            .into_iter()
+            .filter(|&attribute| self.contains(attribute))
+            .filter_map(VertexAttribute::shader_define)
+            .fold(ShaderDefines::default(), ShaderDefines::flag)
+    }
+
+    fn push(&mut self, shader_location: wgpu::ShaderLocation, format: wgpu::VertexFormat) {
+        self.attributes.push(wgpu::VertexAttribute {
+            format,
+            offset: self.array_stride,
+            shader_location,
+        });
+        self.array_stride += format.size();
+    }
+}
+
+impl VertexTrait for VertexLayout {
+    fn desc(&self) -> wgpu::VertexBufferLayout<'_> {
+        wgpu::VertexBufferLayout {
+            array_stride: self.array_stride,
+            step_mode: self.step_mode,
+            attributes: &self.attributes,
+        }
+    }
+}
+
+/// Triangle mesh data, one `Vec` per attribute.
+///
+/// An optional attribute is present when it has exactly one value per
+/// position, and left out of the vertex buffer otherwise.
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Mesh {
+    pub positions: Vec<[f32; 3]>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tex_coords: Vec<[f32; 2]>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub normals: Vec<[f32; 3]>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tangents: Vec<[f32; 4]>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub colors: Vec<[f32; 4]>,
+    #[serde(skip_serializing_if = "Vec::is_empty")]
+    pub tex_coords1: Vec<[f32; 2]>,
+    pub indices: Vec<u32>,
+}
+
+impl Mesh {
+    pub fn vertex_count(&self) -> usize {
+        self.positions.len()
+    }
+
+    fn attribute_data(&self, attribute: VertexAttribute) -> &[f32] {
+        match attribute {
+            VertexAttribute::Position => bytemuck::cast_slice(&self.positions),
+            VertexAttribute::TexCoords => bytemuck::cast_slice(&self.tex_coords),
+            VertexAttribute::Normal => bytemuck::cast_slice(&self.normals),
+            VertexAttribute::Tangent => bytemuck::cast_slice(&self.tangents),
+            VertexAttribute::Color => bytemuck::cast_slice(&self.colors),
+            VertexAttribute::TexCoords1 => bytemuck::cast_slice(&self.tex_coords1),
+        }
+    }
+
+    /// Whether the mesh carries `attribute` for every vertex.
+    pub fn has(&self, attribute: VertexAttribute) -> bool {
+        let components = attribute.format().size() as usize / std::mem::size_of::<f32>();
+        self.attribute_data(attribute).len() == self.vertex_count() * components
+    }
+
+    /// The layout of [`Mesh::vertex_data`], with every attribute the mesh carries.
+    pub fn layout(&self) -> VertexLayout {
+        VertexAttribute::ALL
+            .into_iter()
+            .filter(|&attribute| self.has(attribute))
+            .fold(VertexLayout::new(), VertexLayout::with)
+    }
+
+    /// The vertices interleaved as described by [`Mesh::layout`].
+    pub fn vertex_data(&self) -> Vec<f32> {
+        let attributes: Vec<_> = VertexAttribute::ALL
+            .into_iter()
+            .filter(|&attribute| self.has(attribute))
+            .map(|attribute| {
+                let data = self.attribute_data(attribute);
+                (data, data.len() / self.vertex_count().max(1))
+            })
+            .collect();
+
+        let stride = attributes
+            .iter()
+            .map(|(_, components)| components)
+            .sum::<usize>();
+        let mut data = Vec::with_capacity(stride * self.vertex_count());
+        for vertex in 0..self.vertex_count() {
+            for (values, components) in &attributes {
+                data.extend_from_slice(&values[vertex * components..(vertex + 1) * components]);
+            }
+        }
+        data
+    }
+
+    /// The indices in the smallest format that can address every vertex.
+    pub fn index_data(&self) -> (Vec<u8>, wgpu::IndexFormat) {
+        if self.vertex_count() <= u16::MAX as usize + 1 {
+            let indices: Vec<u16> = self.indices.iter().map(|&i| i as u16).collect();
+            (
+                bytemuck::cast_slice(&indices).to_vec(),
+                wgpu::IndexFormat::Uint16,
+            )
+        } else {
+            (
+                bytemuck::cast_slice(&self.indices).to_vec(),
+                wgpu::IndexFormat::Uint32,
+            )
+        }
+    }
+
+    /// A copy of the triangles in `triangles`, with their vertices written out
+    /// in order and no indices.
+    pub fn unindexed(&self, triangles: Range<usize>) -> Mesh {
+        self.subset(
+            &self.indices[3 * triangles.start..3 * triangles.end],
+            Vec::new(),
+        )
+    }
+
+    /// Splits the triangles into consecutive meshes with at most `max_vertices`
+    /// vertices and `max_triangles` triangles each, so that every part fits in
+    /// its own buffers.
+    pub fn split(&self, max_vertices: usize, max_triangles: usize) -> Vec<Mesh> {
+        const UNUSED: u32 = u32::MAX;
+        let max_vertices = max_vertices.max(3);
+        let max_triangles = max_triangles.max(1);
+
+        let mut parts = Vec::new();
+        // Maps a vertex of `self` to its index in the part being built.
+        let mut remap = vec![UNUSED; self.vertex_count()];
+        let mut vertices: Vec<u32> = Vec::new();
+        let mut indices: Vec<u32> = Vec::new();
+        for triangle in self.indices.chunks_exact(3) {
+            let new_vertices = triangle
+                .iter()
+                .filter(|&&i| remap[i as usize] == UNUSED)
+                .count();
+            if vertices.len() + new_vertices > max_vertices || indices.len() / 3 == max_triangles {
+                parts.push(self.subset(&vertices, std::mem::take(&mut indices)));
+                for vertex in vertices.drain(..) {
+                    remap[vertex as usize] = UNUSED;
+                }
+            }
+            for &i in triangle {
+                if remap[i as usize] == UNUSED {
+                    remap[i as usize] = vertices.len() as u32;
+                    vertices.push(i);
+                }
+                indices.push(remap[i as usize]);
+            }
+        }
+        if !indices.is_empty() {
+            parts.push(self.subset(&vertices, indices));
+        }
+        parts
+    }
+
+    /// The vertices listed in `vertices`, in that order, with new `indices`.
+    fn subset(&self, vertices: &[u32], indices: Vec<u32>) -> Mesh {
+        fn gather<T: Copy>(values: &[T], present: bool, vertices: &[u32]) -> Vec<T> {
+            if !present {
+                return Vec::new();
+            }
+            vertices.iter().map(|&i| values[i as usize]).collect()
+        }
+
+        Mesh {
+            positions: gather(&self.positions, true, vertices),
+            tex_coords: gather(
+                &self.tex_coords,
+                self.has(VertexAttribute::TexCoords),
+                vertices,
+            ),
+            normals: gather(&self.normals, self.has(VertexAttribute::Normal), vertices),
+            tangents: gather(&self.tangents, self.has(VertexAttribute::Tangent), vertices),
+            colors: gather(&self.colors, self.has(VertexAttribute::Color), vertices),
+            tex_coords1: gather(
+                &self.tex_coords1,
+                self.has(VertexAttribute::TexCoords1),
+                vertices,
+            ),
+            indices,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// `count` triangles sharing no vertices, each at its own x.
+    fn separate_triangles(count: usize) -> Mesh {
+        let positions = (0..count)
+            .flat_map(|i| {
+                let x = i as f32;
+                [[x, 0.0, 0.0], [x, 1.0, 0.0], [x, 0.0, 1.0]]
+            })
+            .collect();
+        Mesh {
+            positions,
+            indices: (0..3 * count as u32).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// A fan of `count` triangles around the first vertex.
+    fn fan(count: usize) -> Mesh {
+        let positions = (0..count + 2)
+            .map(|i| [i as f32, (i * i) as f32, 0.0])
+            .collect();
+        Mesh {
+            positions,
+            indices: (1..=count as u32).flat_map(|i| [0, i, i + 1]).collect(),
+            ..Default::default()
+        }
+    }
+
+    /// Every triangle of `mesh` by the positions of its corners.
+    fn triangles(mesh: &Mesh) -> Vec<[[f32; 3]; 3]> {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]].map(|i| mesh.positions[i as usize]))
+            .collect()
+    }
+
+    fn with_vertex_count(count: usize) -> Mesh {
+        Mesh {
+            positions: vec![[0.0; 3]; count],
+            indices: vec![0, 1, count as u32 - 1],
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn picks_the_smallest_index_format() {
+        let (data, format) = with_vertex_count(u16::MAX as usize + 1).index_data();
+        assert_eq!(format, wgpu::IndexFormat::Uint16);
+        assert_eq!(bytemuck::cast_slice::<u8, u16>(&data), [0, 1, u16::MAX]);
+
+        let (data, format) = with_vertex_count(u16::MAX as usize + 2).index_data();
+        assert_eq!(format, wgpu::IndexFormat::Uint32);
+        assert_eq!(
+            bytemuck::cast_slice::<u8, u32>(&data),
+            [0, 1, u16::MAX as u32 + 1]
+        );
+    }
+
+    #[test]
+    fn splits_at_the_vertex_limit() {
+        let mesh = separate_triangles(4);
+        let parts = mesh.split(6, usize::MAX);
+        assert_eq!(parts.len(), 2);
+        assert!(parts.iter().all(|part| part.vertex_count() == 6));
+
+        // One vertex short of two triangles leaves one in each part.
+        let parts = mesh.split(5, usize::MAX);
+        assert_eq!(parts.len(), 4);
+        assert!(parts.iter().all(|part| part.vertex_count() == 3));
+    }
+
+    #[test]
+    fn splits_at_the_triangle_limit() {
+        let parts = separate_triangles(4).split(usize::MAX, 3);
+        let counts: Vec<_> = parts.iter().map(|part| part.indices.len() / 3).collect();
+        assert_eq!(counts, [3, 1]);
+    }
+
+    #[test]
+    fn split_parts_share_vertices_within_a_part() {
+        let mesh = fan(10);
+        let parts = mesh.split(6, usize::MAX);
+        for part in &parts {
+            assert!(part.vertex_count() <= 6);
+            assert!(part
+                .indices
+                .iter()
+                .all(|&i| (i as usize) < part.vertex_count()));
+        }
+        // The first part takes the hub and five rim vertices, four triangles.
+        assert_eq!(parts[0].indices.len() / 3, 4);
+        let rejoined: Vec<_> = parts.iter().flat_map(triangles).collect();
+        assert_eq!(rejoined, triangles(&mesh));
+    }
+
+    #[test]
+    fn split_keeps_attributes_with_their_vertices() {
+        let mut mesh = separate_triangles(3);
+        mesh.colors = (0..9).map(|i| [i as f32; 4]).collect();
+        for part in mesh.split(3, usize::MAX) {
+            assert!(part.has(VertexAttribute::Color));
+            assert!(!part.has(VertexAttribute::Normal));
+            for (position, color) in part.positions.iter().zip(&part.colors) {
+                let original = mesh.positions.iter().position(|p| p == position).unwrap();
+                assert_eq!(*color, mesh.colors[original]);
+            }
+        }
+    }
+
+    #[test]
+    fn unindexed_writes_out_each_corner() {
+        let mesh = fan(3);
+        let unindexed = mesh.unindexed(1..3);
+        assert!(unindexed.indices.is_empty());
+        assert_eq!(unindexed.vertex_count(), 6);
+        let expected: Vec<_> = triangles(&mesh)[1..3].iter().flatten().copied().collect();
+        assert_eq!(unindexed.positions, expected);
+    }
+}
@@ -0,0 +1,211 @@
+use std::collections::HashMap;
+
+use crate::mesh::Mesh;
+
+/// Reads the positions, texture coordinates, normals and faces of a Wavefront
+/// OBJ file into a single mesh.
+///
+/// Faces with more than three corners are split into a triangle fan. Groups,
+/// objects and materials are ignored.
+pub fn parse(source: &str) -> Result<Mesh, String> {
+    let mut positions: Vec<[f32; 3]> = Vec::new();
+    let mut tex_coords: Vec<[f32; 2]> = Vec::new();
+    let mut normals: Vec<[f32; 3]> = Vec::new();
+
+    let mut mesh = Mesh::default();
+    // Every distinct position/texture/normal combination becomes one vertex.
+    let mut vertices: HashMap<(usize, Option<usize>, Option<usize>), u32> = HashMap::new();
+    let (mut has_tex_coords, mut has_normals) = (true, true);
+
+    for (number, line) in source.lines().enumerate() {
+        let error = |message: String| format!("line {}: {message}", number + 1);
+        let mut words = line.split_whitespace();
+        match words.next() {
+            Some("v") => positions.push(floats(words).map_err(error)?),
+            Some("vt") => {
+                let [u, v] = floats(words.take(2)).map_err(error)?;
+                // OBJ puts the origin at the bottom left, textures at the top left.
+                tex_coords.push([u, 1.0 - v]);
+            }
+            Some("vn") => normals.push(floats(words).map_err(error)?),
+            Some("f") => {
+                let mut corners = Vec::new();
+                for corner in words {
+                    let key =
+                        corner_indices(corner, [positions.len(), tex_coords.len(), normals.len()])
+                            .map_err(error)?;
+                    has_tex_coords &= key.1.is_some();
+                    has_normals &= key.2.is_some();
+                    let index = *vertices.entry(key).or_insert_with(|| {
+                        mesh.positions.push(positions[key.0]);
+                        mesh.tex_coords
+                            .push(key.1.map_or([0.0; 2], |i| tex_coords[i]));
+                        mesh.normals.push(key.2.map_or([0.0; 3], |i| normals[i]));
+                        mesh.positions.len() as u32 - 1
+                    });
+                    corners.push(index);
+                }
+                if corners.len() < 3 {
+                    return Err(error("a face needs at least three corners".to_owned()));
+                }
+                for i in 1..corners.len() - 1 {
+                    mesh.indices
+                        .extend_from_slice(&[corners[0], corners[i], corners[i + 1]]);
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Partially specified attributes would be garbage, so leave them out.
+    if !has_tex_coords {
+        mesh.tex_coords.clear();
+    }
+    if !has_normals {
+        mesh.normals.clear();
+    }
+    Ok(mesh)
+}
+
+fn floats<'a, const N: usize>(words: impl Iterator<Item = &'a str>) -> Result<[f32; N], String> {
+    let mut values = [0.0; N];
+    let mut count = 0;
+    for word in words.take(N) {
+        values[count] = word
+            .parse()
+            .map_err(|_| format!("\"{word}\" is not a number"))?;
+        count += 1;
+    }
+    if count < N {
+        return Err(format!("expected {N} numbers"));
+    }
+    Ok(values)
+}
+
+/// Parses a `v`, `v/vt`, `v//vn` or `v/vt/vn` face corner into zero-based
+/// indices, given how many of each element have been read so far.
+fn corner_indices(
+    corner: &str,
+    counts: [usize; 3],
+) -> Result<(usize, Option<usize>, Option<usize>), String> {
+    let mut indices = [None; 3];
+    for (i, part) in corner.split('/').enumerate().take(3) {
+        if part.is_empty() {
+            continue;
+        }
+        let index: i64 = part
+            .parse()
+            .map_err(|_| format!("\"{corner}\" is not a face corner"))?;
+        // Negative indices count back from the last element read.
+        let resolved = if index < 0 {
+            counts[i] as i64 + index
+        } else {
+            index - 1
+        };
+        if resolved < 0 || resolved >= counts[i] as i64 {
+            return Err(format!("\"{corner}\" refers to a missing element"));
+        }
+        indices[i] = Some(resolved as usize);
+    }
+    let position = indices[0].ok_or_else(|| format!("\"{corner}\" has no position"))?;
+    Ok((position, indices[1], indices[2]))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SQUARE: &str = "\
+v 0 0 0
+v 1 0 0
+v 1 1 0
+v 0 1 0
+";
+
+    fn parse_err(source: &str) -> String {
+        let Err(error) = parse(source) else {
+            panic!("parsing should have failed");
+        };
+        error
+    }
+
+    #[test]
+    fn splits_quads_and_polygons_into_fans() {
+        let mesh = parse(&format!("{SQUARE}f 1 2 3 4\n")).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3]);
+
+        let mesh = parse(&format!("{SQUARE}v 0.5 2 0\nf 1 2 3 5 4\n")).unwrap();
+        assert_eq!(mesh.indices, [0, 1, 2, 0, 2, 3, 0, 3, 4]);
+        assert_eq!(mesh.positions[3], [0.5, 2.0, 0.0]);
+    }
+
+    #[test]
+    fn negative_indices_count_back_from_the_last_element() {
+        let relative = parse(&format!("{SQUARE}f -4 -3 -2\n")).unwrap();
+        let absolute = parse(&format!("{SQUARE}f 1 2 3\n")).unwrap();
+        assert_eq!(relative.positions, absolute.positions);
+        assert_eq!(relative.indices, absolute.indices);
+
+        // Only the elements read before the face count.
+        let mesh = parse("v 0 0 0\nv 1 0 0\nv 1 1 0\nf -1 -2 -3\nv 5 5 5\n").unwrap();
+        assert_eq!(
+            mesh.positions,
+            [[1.0, 1.0, 0.0], [1.0, 0.0, 0.0], [0.0, 0.0, 0.0]]
+        );
+    }
+
+    #[test]
+    fn reads_every_corner_form() {
+        let source = format!("{SQUARE}vt 0 0\nvt 1 0.25\nvn 0 0 1\n");
+
+        let mesh = parse(&format!("{source}f 1/1 2/2 3/1\n")).unwrap();
+        assert_eq!(mesh.tex_coords, [[0.0, 1.0], [1.0, 0.75], [0.0, 1.0]]);
+        assert!(mesh.normals.is_empty());
+
+        let mesh = parse(&format!("{source}f 1//1 2//1 3//1\n")).unwrap();
+        assert!(mesh.tex_coords.is_empty());
+        assert_eq!(mesh.normals, [[0.0, 0.0, 1.0]; 3]);
+
+        let mesh = parse(&format!("{source}f 1/1/1 2/2/1 3/2/1\n")).unwrap();
+        assert_eq!(mesh.tex_coords, [[0.0, 1.0], [1.0, 0.75], [1.0, 0.75]]);
+        assert_eq!(mesh.normals, [[0.0, 0.0, 1.0]; 3]);
+    }
+
+    #[test]
+    fn shares_vertices_with_the_same_attributes() {
+        let source = format!("{SQUARE}vn 0 0 1\nvn 0 0 -1\n");
+        let mesh = parse(&format!("{source}f 1//1 2//1 3//1\nf 1//1 3//1 4//1\n")).unwrap();
+        assert_eq!(mesh.positions.len(), 4);
+
+        // The same position with another normal is another vertex.
+        let mesh = parse(&format!("{source}f 1//1 2//1 3//1\nf 1//2 3//2 4//2\n")).unwrap();
+        assert_eq!(mesh.positions.len(), 6);
+    }
+
+    #[test]
+    fn rejects_missing_elements() {
+        assert!(parse_err(&format!("{SQUARE}f 1 2 5\n")).starts_with("line 5:"));
+        assert!(parse_err(&format!("{SQUARE}f 0 1 2\n")).contains("missing element"));
+        assert!(parse_err(&format!("{SQUARE}f -5 1 2\n")).contains("missing element"));
+        assert!(parse_err(&format!("{SQUARE}f 1/1 2/1 3/1\n")).contains("missing element"));
+        assert!(parse_err(&format!("{SQUARE}vt 0 0\nf /1 2 3\n")).contains("no position"));
+        assert!(parse_err(&format!("{SQUARE}f 1 2\n")).contains("three corners"));
+        assert!(parse_err(&format!("{SQUARE}f 1 x 3\n")).contains("not a face corner"));
+        assert!(parse_err("v 0 0\n").contains("expected 3 numbers"));
+    }
+
+    #[test]
+    fn drops_attributes_missing_from_some_faces() {
+        let source = format!("{SQUARE}vt 0 0\nvn 0 0 1\n");
+        let mesh = parse(&format!("{source}f 1/1/1 2/1/1 3/1/1\nf 1 3 4\n")).unwrap();
+        // Corners without attributes are vertices of their own.
+        assert_eq!(mesh.positions.len(), 6);
+        assert!(mesh.tex_coords.is_empty());
+        assert!(mesh.normals.is_empty());
+
+        let mesh = parse(&format!("{source}f 1/1/1 2/1/1 3/1/1\nf 1//1 3//1 4//1\n")).unwrap();
+        assert!(mesh.tex_coords.is_empty());
+        assert_eq!(mesh.normals.len(), mesh.positions.len());
+    }
+}
@@ -0,0 +1,472 @@
+//! Outlines the selected nodes in screen space.
+//!
+//! The selected instances are drawn into a mask with the picking pipelines.
+//! A jump flood then spreads the position of the nearest masked pixel over the
+//! viewport, taking only a few passes however wide the outline is, and the
+//! distance to it becomes an anti-aliased outline composited over the scene.
+
+use egui_wgpu::{self, wgpu};
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    camera::CameraResources,
+    light::LightResources,
+    picking::PICK_FORMAT,
+    pipeline::{self, PipelineCache, DEPTH_FORMAT},
+    preprocessor::ShaderRegistry,
+    renderer::{Resource, ScenePass, SceneResources},
+    scene::NodeId,
+    texture::TextureResource,
+};
+
+/// Holds the position of the nearest selected pixel, see `outline.wgsl`.
+const SEED_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Rg16Uint;
+
+/// The widest outline, in physical pixels.
+pub const MAX_WIDTH: f32 = 32.0;
+
+/// Flood passes needed for a [`MAX_WIDTH`] outline, with steps of 32 down to 1.
+const MAX_FLOOD_PASSES: usize = 6;
+
+/// How selected nodes are highlighted.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct OutlineSettings {
+    /// Unmultiplied RGBA.
+    pub color: [f32; 4],
+    /// In physical pixels, up to [`MAX_WIDTH`].
+    pub width: f32,
+    /// Whether selected nodes are also tinted with `overlay_color`.
+    pub overlay: bool,
+    pub overlay_color: [f32; 4],
+}
+
+impl Default for OutlineSettings {
+    fn default() -> Self {
+        Self {
+            color: [1.0, 0.6, 0.1, 1.0],
+            width: 3.0,
+            overlay: false,
+            overlay_color: [1.0, 0.6, 0.1, 0.25],
+        }
+    }
+}
+
+impl OutlineSettings {
+    /// The steps of the flood passes, largest first.
+    fn flood_steps(&self) -> impl Iterator<Item = u32> {
+        let width = self.width.clamp(1.0, MAX_WIDTH).ceil() as u32;
+        let first = width.next_power_of_two();
+        std::iter::successors(Some(first), |step| (*step > 1).then_some(step / 2))
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct OutlineParams {
+    color: [f32; 4],
+    overlay_color: [f32; 4],
+    /// Top left of the viewport in the egui render target, in pixels.
+    origin: [f32; 2],
+    width: f32,
+    step: f32,
+}
+
+/// The mask and seed textures, sized to the viewport.
+struct OutlineTargets {
+    size: [u32; 2],
+    mask_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+    seed_views: [wgpu::TextureView; 2],
+    /// Bind group `i` reads the mask and seed texture `i`.
+    bind_groups: [wgpu::BindGroup; 2],
+}
+
+impl OutlineTargets {
+    fn new(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        params: &wgpu::Buffer,
+        size: [u32; 2],
+    ) -> Self {
+        let create = |label, format, usage| {
+            device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: size[0],
+                        height: size[1],
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count: 1,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&wgpu::TextureViewDescriptor::default())
+        };
+        let sampled = wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::TEXTURE_BINDING;
+        let mask_view = create("outline_mask", PICK_FORMAT, sampled);
+        let depth_view = create(
+            "outline_depth",
+            DEPTH_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        let seed_views = [
+            create("outline_seeds_0", SEED_FORMAT, sampled),
+            create("outline_seeds_1", SEED_FORMAT, sampled),
+        ];
+        let params_size = wgpu::BufferSize::new(std::mem::size_of::<OutlineParams>() as u64);
+        let bind_groups = [0, 1].map(|index| {
+            device.create_bind_group(&wgpu::BindGroupDescriptor {
+                label: Some("outline_bind_group"),
+                layout,
+                entries: &[
+                    wgpu::BindGroupEntry {
+                        binding: 0,
+                        resource: wgpu::BindingResource::TextureView(&mask_view),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 1,
+                        resource: wgpu::BindingResource::TextureView(&seed_views[index]),
+                    },
+                    wgpu::BindGroupEntry {
+                        binding: 2,
+                        resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                            buffer: params,
+                            offset: 0,
+                            size: params_size,
+                        }),
+                    },
+                ],
+            })
+        });
+        Self {
+            size,
+            mask_view,
+            depth_view,
+            seed_views,
+            bind_groups,
+        }
+    }
+}
+
+pub struct OutlineResources {
+    bind_group_layout: wgpu::BindGroupLayout,
+    /// The composite's [`OutlineParams`], followed by one per flood pass.
+    params_buffer: wgpu::Buffer,
+    /// Distance between consecutive params in the buffer.
+    params_stride: wgpu::BufferAddress,
+    seed_pipeline: wgpu::RenderPipeline,
+    flood_pipeline: wgpu::RenderPipeline,
+    composite_pipeline: wgpu::RenderPipeline,
+    /// Kept to rebuild the pipelines when the shaders change on disk.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    layout: wgpu::PipelineLayout,
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    target_format: wgpu::TextureFormat,
+    targets: Option<OutlineTargets>,
+    /// The bind group holding this frame's flooded seeds, if anything is
+    /// selected.
+    result: Option<usize>,
+}
+
+impl Resource for OutlineResources {}
+
+impl OutlineResources {
+    pub fn new(
+        device: &wgpu::Device,
+        target_format: wgpu::TextureFormat,
+        shaders: &ShaderRegistry,
+    ) -> Self {
+        let texture_entry = |binding| wgpu::BindGroupLayoutEntry {
+            binding,
+            visibility: wgpu::ShaderStages::FRAGMENT,
+            ty: wgpu::BindingType::Texture {
+                sample_type: wgpu::TextureSampleType::Uint,
+                view_dimension: wgpu::TextureViewDimension::D2,
+                multisampled: false,
+            },
+            count: None,
+        };
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("outline_bind_group_layout"),
+            entries: &[
+                texture_entry(0),
+                texture_entry(1),
+                wgpu::BindGroupLayoutEntry {
+                    binding: 2,
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<OutlineParams>() as u64,
+                        ),
+                    },
+                    count: None,
+                },
+            ],
+        });
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let size = std::mem::size_of::<OutlineParams>() as u64;
+        let params_stride = (size + alignment - 1) / alignment * alignment;
+        let params_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("outline_params"),
+            size: params_stride * (1 + MAX_FLOOD_PASSES as u64),
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+
+        let layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+            label: Some("outline"),
+            bind_group_layouts: &[&bind_group_layout],
+            push_constant_ranges: &[],
+        });
+        let [seed_pipeline, flood_pipeline, composite_pipeline] =
+            Self::create_pipelines(device, shaders, &layout, target_format)
+                .expect("built-in shaders should compile");
+
+        Self {
+            bind_group_layout,
+            params_buffer,
+            params_stride,
+            seed_pipeline,
+            flood_pipeline,
+            composite_pipeline,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            layout,
+            #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+            target_format,
+            targets: None,
+            result: None,
+        }
+    }
+
+    /// The seed, flood and composite pipelines.
+    fn create_pipelines(
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        layout: &wgpu::PipelineLayout,
+        target_format: wgpu::TextureFormat,
+    ) -> Result<[wgpu::RenderPipeline; 3], String> {
+        let shader = pipeline::shader_module(device, shaders, "outline", &Default::default())?;
+        let create_pipeline = |entry_point, format, blend, depth_stencil| {
+            device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                label: Some(entry_point),
+                layout: Some(layout),
+                vertex: wgpu::VertexState {
+                    module: &shader,
+                    entry_point: "vs_main",
+                    buffers: &[],
+                },
+                fragment: Some(wgpu::FragmentState {
+                    module: &shader,
+                    entry_point,
+                    targets: &[Some(wgpu::ColorTargetState {
+                        format,
+                        blend,
+                        write_mask: wgpu::ColorWrites::ALL,
+                    })],
+                }),
+                primitive: wgpu::PrimitiveState::default(),
+                depth_stencil,
+                multisample: wgpu::MultisampleState::default(),
+                multiview: None,
+            })
+        };
+        Ok([
+            create_pipeline("fs_seed", SEED_FORMAT, None, None),
+            create_pipeline("fs_flood", SEED_FORMAT, None, None),
+            // Drawn in egui's render pass, which has a depth buffer.
+            create_pipeline(
+                "fs_composite",
+                target_format,
+                Some(wgpu::BlendState::ALPHA_BLENDING),
+                Some(wgpu::DepthStencilState {
+                    format: DEPTH_FORMAT,
+                    depth_write_enabled: false,
+                    depth_compare: wgpu::CompareFunction::Always,
+                    stencil: wgpu::StencilState::default(),
+                    bias: wgpu::DepthBiasState::default(),
+                }),
+            ),
+        ])
+    }
+
+    /// Makes sure the targets match the viewport and uploads this frame's
+    /// parameters.
+    fn begin(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        viewport: egui::Rect,
+        settings: &OutlineSettings,
+    ) {
+        let size = [viewport.width() as u32, viewport.height() as u32];
+        if self.targets.as_ref().map(|targets| targets.size) != Some(size) {
+            self.targets = Some(OutlineTargets::new(
+                device,
+                &self.bind_group_layout,
+                &self.params_buffer,
+                size,
+            ));
+        }
+
+        let params = |step| OutlineParams {
+            color: settings.color,
+            overlay_color: if settings.overlay {
+                settings.overlay_color
+            } else {
+                [0.0; 4]
+            },
+            origin: viewport.min.into(),
+            width: settings.width.clamp(1.0, MAX_WIDTH),
+            step: step as f32,
+        };
+        let mut data = Vec::new();
+        for (index, step) in std::iter::once(0).chain(settings.flood_steps()).enumerate() {
+            data.extend_from_slice(bytemuck::bytes_of(&params(step)));
+            data.resize((index + 1) * self.params_stride as usize, 0);
+        }
+        queue.write_buffer(&self.params_buffer, 0, &data);
+    }
+
+    /// Composites the outline over the scene, if anything is selected.
+    pub fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
+        let (Some(targets), Some(result)) = (&self.targets, self.result) else {
+            return;
+        };
+        render_pass.set_pipeline(&self.composite_pipeline);
+        render_pass.set_bind_group(0, &targets.bind_groups[result], &[0]);
+        render_pass.draw(0..3, 0..1);
+    }
+}
+
+/// Draws the mask of the `selected` nodes and floods it, ready for
+/// [`OutlineResources::paint`].
+///
+/// Must follow the preparation of the scene, camera and lights for this
+/// frame, including the picking pipelines.
+pub(crate) fn render(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    encoder: &mut wgpu::CommandEncoder,
+    viewport: egui::Rect,
+    settings: &OutlineSettings,
+    selected: &[NodeId],
+    resources: &mut egui_wgpu::CallbackResources,
+) {
+    {
+        let outline: &mut OutlineResources = resources.get_mut().unwrap();
+        if selected.is_empty() || viewport.width() < 1.0 || viewport.height() < 1.0 {
+            outline.result = None;
+            return;
+        }
+        outline.begin(device, queue, viewport, settings);
+    }
+
+    let result = {
+        let outline: &OutlineResources = resources.get().unwrap();
+        let scene_resources: &SceneResources = resources.get().unwrap();
+        let pipeline_cache: &PipelineCache = resources.get().unwrap();
+        let camera_resources: &CameraResources = resources.get().unwrap();
+        let light_resources: &LightResources = resources.get().unwrap();
+        let texture_resource: &TextureResource = resources.get().unwrap();
+        let targets = outline.targets.as_ref().unwrap();
+
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("outline_mask"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &targets.mask_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            camera_resources.paint(&mut render_pass);
+            light_resources.paint(&mut render_pass);
+            scene_resources.paint_nodes(
+                &mut render_pass,
+                ScenePass::Picking,
+                pipeline_cache,
+                texture_resource,
+                selected,
+            );
+        }
+
+        // Every pass reads one seed texture and writes the other.
+        let mut fullscreen_pass = |label, pipeline, source: usize, target: usize, offset| {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some(label),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &targets.seed_views[target],
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: None,
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            render_pass.set_pipeline(pipeline);
+            render_pass.set_bind_group(0, &targets.bind_groups[source], &[offset]);
+            render_pass.draw(0..3, 0..1);
+        };
+        fullscreen_pass("outline_seed", &outline.seed_pipeline, 1, 0, 0);
+        let mut result = 0;
+        for index in 0..settings.flood_steps().count() {
+            let offset = (index as u64 + 1) * outline.params_stride;
+            fullscreen_pass(
+                "outline_flood",
+                &outline.flood_pipeline,
+                result,
+                1 - result,
+                offset as wgpu::DynamicOffset,
+            );
+            result = 1 - result;
+        }
+        result
+    };
+    let outline: &mut OutlineResources = resources.get_mut().unwrap();
+    outline.result = Some(result);
+}
+
+#[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+impl crate::hot_reload::ReloadPipelines for OutlineResources {
+    type Pipelines = [wgpu::RenderPipeline; 3];
+
+    fn build_pipelines(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+    ) -> Result<Self::Pipelines, String> {
+        Self::create_pipelines(device, shaders, &self.layout, self.target_format)
+    }
+
+    fn set_pipelines(&mut self, pipelines: Self::Pipelines) {
+        [
+            self.seed_pipeline,
+            self.flood_pipeline,
+            self.composite_pipeline,
+        ] = pipelines;
+    }
+}
@@ -0,0 +1,308 @@
+use serde::{Deserialize, Serialize};
+
+use crate::{
+    clipping::ClipSettings,
+    history::History,
+    light::Lights,
+    scene::{NodeId, Scene},
+    selection::{Selection, SelectionSet},
+};
+
+/// Which side of the window a panel is docked to.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DockSide {
+    Left,
+    Right,
+}
+
+impl DockSide {
+    pub const ALL: [DockSide; 2] = [DockSide::Left, DockSide::Right];
+
+    pub fn label(self) -> &'static str {
+        match self {
+            DockSide::Left => "Left",
+            DockSide::Right => "Right",
+        }
+    }
+
+    pub fn opposite(self) -> Self {
+        match self {
+            DockSide::Left => DockSide::Right,
+            DockSide::Right => DockSide::Left,
+        }
+    }
+
+    /// An empty side panel docked to this side.
+    pub fn panel(self, id: &'static str) -> egui::SidePanel {
+        match self {
+            DockSide::Left => egui::SidePanel::left(id),
+            DockSide::Right => egui::SidePanel::right(id),
+        }
+    }
+}
+
+/// A change made in the outliner, applied to the scene after the tree is drawn.
+enum Change {
+    Visible(NodeId, bool),
+    Rename(NodeId, String),
+    Reparent(NodeId, Option<NodeId>),
+}
+
+/// A node name being edited in place.
+#[derive(Clone)]
+struct Rename {
+    node: NodeId,
+    name: String,
+    /// Whether the text field has been given keyboard focus yet.
+    focused: bool,
+}
+
+/// A side panel listing the scene hierarchy, followed by the camera, lights
+/// and clipping planes.
+///
+/// Nodes can be expanded and collapsed, hidden, renamed with a double click and
+/// dragged onto another node to reparent them.
+#[derive(Clone, Serialize, Deserialize)]
+#[serde(default)]
+pub struct Outliner {
+    pub open: bool,
+    pub side: DockSide,
+    #[serde(skip)]
+    rename: Option<Rename>,
+}
+
+impl Default for Outliner {
+    fn default() -> Self {
+        Self {
+            open: true,
+            side: DockSide::Left,
+            rename: None,
+        }
+    }
+}
+
+impl Outliner {
+    /// Shows the panel, if open, and applies any edits to `scene` through
+    /// `history`.
+    ///
+    /// Clicking an entry selects it, or adds it to the `selection` with shift
+    /// or ctrl held.
+    pub fn show(
+        &mut self,
+        ctx: &egui::Context,
+        scene: &mut Scene,
+        history: &mut History,
+        lights: &Lights,
+        clipping: &mut ClipSettings,
+        selection: &mut SelectionSet,
+    ) {
+        if !self.open {
+            return;
+        }
+        let mut changes = Vec::new();
+        let mut edited_clipping = *clipping;
+        self.side
+            .panel("outliner")
+            .resizable(true)
+            .default_width(200.0)
+            .show(ctx, |ui| {
+                ui.heading("Outliner");
+                ui.separator();
+                egui::ScrollArea::vertical()
+                    .drag_to_scroll(false)
+                    .show(ui, |ui| {
+                        for &root in scene.roots() {
+                            self.show_node(ui, scene, root, selection, &mut changes);
+                        }
+                        ui.add_space(4.0);
+                        let (_, dropped) = ui.dnd_drop_zone::<NodeId>(
+                            egui::Frame::none().inner_margin(4.0),
+                            |ui| {
+                                ui.weak("Drop here to move to the root");
+                            },
+                        );
+                        if let Some(node) = dropped {
+                            changes.push(Change::Reparent(*node, None));
+                        }
+
+                        ui.separator();
+                        select_row(ui, selection, Selection::Camera, "Camera");
+                        egui::CollapsingHeader::new(format!("Lights ({})", lights.len()))
+                            .default_open(false)
+                            .show(ui, |ui| {
+                                for (index, light) in lights.iter().enumerate() {
+                                    let name = format!("{} {index}", light.kind());
+                                    select_row(ui, selection, Selection::Light(index), name);
+                                }
+                            });
+                        egui::CollapsingHeader::new("Clipping")
+                            .default_open(false)
+                            .show(ui, |ui| clipping_rows(ui, &mut edited_clipping, selection));
+                    });
+            });
+
+        history.edit_clipping(clipping, |clipping| *clipping = edited_clipping);
+        for change in changes {
+            match change {
+                Change::Visible(node, visible) => {
+                    history.edit_node(scene, node, |node| node.visible = visible);
+                }
+                Change::Rename(node, name) => {
+                    history.edit_node(scene, node, |node| node.name = name)
+                }
+                Change::Reparent(node, parent) => {
+                    if let Err(error) = history.set_parent(scene, node, parent) {
+                        log::warn!("{error}");
+                    }
+                }
+            }
+        }
+    }
+
+    fn show_node(
+        &mut self,
+        ui: &mut egui::Ui,
+        scene: &Scene,
+        id: NodeId,
+        selection: &mut SelectionSet,
+        changes: &mut Vec<Change>,
+    ) {
+        let children = scene.node(id).children();
+        if children.is_empty() {
+            ui.horizontal(|ui| {
+                // Line up with the rows that have an expand button.
+                ui.add_space(ui.spacing().indent - ui.spacing().item_spacing.x);
+                self.show_row(ui, scene, id, selection, changes);
+            });
+        } else {
+            let state_id = ui.make_persistent_id(("outliner_node", id));
+            egui::collapsing_header::CollapsingState::load_with_default_open(
+                ui.ctx(),
+                state_id,
+                true,
+            )
+            .show_header(ui, |ui| self.show_row(ui, scene, id, selection, changes))
+            .body(|ui| {
+                for &child in children {
+                    self.show_node(ui, scene, child, selection, changes);
+                }
+            });
+        }
+    }
+
+    /// The visibility toggle and name of one node.
+    fn show_row(
+        &mut self,
+        ui: &mut egui::Ui,
+        scene: &Scene,
+        id: NodeId,
+        selection: &mut SelectionSet,
+        changes: &mut Vec<Change>,
+    ) {
+        let node = scene.node(id);
+        let mut visible = node.visible;
+        if ui
+            .checkbox(&mut visible, "")
+            .on_hover_text("Show or hide the node and its children")
+            .changed()
+        {
+            changes.push(Change::Visible(id, visible));
+        }
+
+        if let Some(rename) = self.rename.as_mut().filter(|rename| rename.node == id) {
+            let response = ui.text_edit_singleline(&mut rename.name);
+            if !rename.focused {
+                response.request_focus();
+                rename.focused = true;
+            } else if response.lost_focus() {
+                if !ui.input(|i| i.key_pressed(egui::Key::Escape)) {
+                    changes.push(Change::Rename(id, rename.name.clone()));
+                }
+                self.rename = None;
+            }
+            return;
+        }
+
+        let row = ui
+            .selectable_label(selection.contains(Selection::Node(id)), &node.name)
+            .on_hover_text("Double-click to rename, drag onto another node to reparent");
+        // Sensing drags on the label would swallow its clicks, so pick the
+        // node up once the pointer pressed on it has clearly moved.
+        let ctx = ui.ctx();
+        if row.is_pointer_button_down_on()
+            && ui.input(|i| i.pointer.is_decidedly_dragging())
+            && !egui::DragAndDrop::has_any_payload(ctx)
+        {
+            egui::DragAndDrop::set_payload(ctx, id);
+        }
+        if egui::DragAndDrop::payload::<NodeId>(ctx).is_some_and(|dragged| *dragged == id) {
+            egui::show_tooltip_at_pointer(ctx, egui::Id::new(("outliner_drag", id)), |ui| {
+                ui.label(&node.name);
+            });
+        }
+        if row.clicked() {
+            selection.click(Some(Selection::Node(id)), ui.input(|i| i.modifiers));
+        }
+        if row.double_clicked() {
+            self.rename = Some(Rename {
+                node: id,
+                name: node.name.clone(),
+                focused: false,
+            });
+        }
+
+        if let Some(dragged) = row.dnd_hover_payload::<NodeId>() {
+            if *dragged != id {
+                ui.painter()
+                    .rect_stroke(row.rect.expand(1.0), 2.0, ui.visuals().selection.stroke);
+            }
+        }
+        if let Some(dragged) = row.dnd_release_payload::<NodeId>() {
+            if *dragged != id {
+                changes.push(Change::Reparent(*dragged, Some(id)));
+            }
+        }
+    }
+}
+
+/// The clipping planes and section box, each with a toggle, and a button for
+/// adding planes.
+fn clipping_rows(ui: &mut egui::Ui, clipping: &mut ClipSettings, selection: &mut SelectionSet) {
+    for (index, plane) in clipping.planes.iter_mut().enumerate() {
+        if let Some(plane) = plane {
+            ui.horizontal(|ui| {
+                ui.checkbox(&mut plane.enabled, "");
+                let name = format!("Plane {}", index + 1);
+                select_row(ui, selection, Selection::ClipPlane(index), name);
+            });
+        }
+    }
+    ui.horizontal(|ui| {
+        ui.checkbox(&mut clipping.section_box.enabled, "");
+        select_row(ui, selection, Selection::SectionBox, "Section box");
+    });
+    let full = clipping.planes.iter().all(Option::is_some);
+    if ui
+        .add_enabled(!full, egui::Button::new("Add plane"))
+        .clicked()
+    {
+        if let Some(index) = clipping.add_plane() {
+            selection.click(Some(Selection::ClipPlane(index)), egui::Modifiers::NONE);
+        }
+    }
+}
+
+/// A row that selects `item` when clicked.
+fn select_row(
+    ui: &mut egui::Ui,
+    selection: &mut SelectionSet,
+    item: Selection,
+    name: impl Into<egui::WidgetText>,
+) {
+    if ui
+        .selectable_label(selection.contains(item), name)
+        .clicked()
+    {
+        selection.click(Some(item), ui.input(|i| i.modifiers));
+    }
+}
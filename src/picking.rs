@@ -0,0 +1,302 @@
+//! Finds the node under the cursor by drawing every instance's index into an
+//! integer texture and reading back the clicked pixel.
+//!
+//! The readback completes a frame or more after the click, so the app asks
+//! [`Picking`] for the result on later frames.
+
+use egui_wgpu::{self, wgpu};
+use std::{
+    ops::Range,
+    sync::{Arc, OnceLock, RwLock},
+};
+
+use crate::{
+    camera::CameraResources,
+    light::{self, LightResources},
+    mesh::VertexLayout,
+    pipeline::{PipelineCache, PipelineKey, DEPTH_FORMAT},
+    renderer::{Resource, ScenePass, SceneResources},
+    scene::{InstanceRaw, NodeId},
+    texture::TextureResource,
+};
+
+/// Holds the index of the instance covering each pixel, plus one.
+pub(crate) const PICK_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::R32Uint;
+
+/// The pipeline that draws meshes with `vertex_layout` into the pick target.
+pub(crate) fn pipeline_key(vertex_layout: &VertexLayout) -> PipelineKey {
+    PipelineKey::new(
+        "forward",
+        vec![vertex_layout.clone(), InstanceRaw::layout()],
+    )
+    .defines(
+        light::shader_defines()
+            .flag("PICKING")
+            .extend(&vertex_layout.shader_defines()),
+    )
+    .target_format(PICK_FORMAT)
+}
+
+/// Pick requests and results, shared between the app and [`PickResources`].
+#[derive(Default)]
+pub struct Picking {
+    /// The pixel to pick, relative to the top left of the viewport.
+    request: Option<[u32; 2]>,
+    pending: bool,
+    /// The node under the last picked pixel, if any, until taken.
+    result: Option<Option<NodeId>>,
+}
+
+impl Picking {
+    /// Asks for the node under `pixel`, in physical pixels from the top left
+    /// of the viewport.
+    pub fn request(&mut self, pixel: [u32; 2]) {
+        self.request = Some(pixel);
+        self.pending = true;
+    }
+
+    /// Whether a requested pick has yet to produce a result.
+    pub fn is_pending(&self) -> bool {
+        self.pending
+    }
+
+    /// The result of the last pick, once it is available.
+    pub fn take_result(&mut self) -> Option<Option<NodeId>> {
+        self.result.take()
+    }
+
+    fn finish(&mut self, node: Option<NodeId>) {
+        self.pending = self.request.is_some();
+        self.result = Some(node);
+    }
+}
+
+/// The id and depth textures, sized to the viewport.
+struct PickTarget {
+    size: [u32; 2],
+    color: wgpu::Texture,
+    color_view: wgpu::TextureView,
+    depth_view: wgpu::TextureView,
+}
+
+impl PickTarget {
+    fn new(device: &wgpu::Device, size: [u32; 2]) -> Self {
+        let create = |label, format, usage| {
+            device.create_texture(&wgpu::TextureDescriptor {
+                label: Some(label),
+                size: wgpu::Extent3d {
+                    width: size[0],
+                    height: size[1],
+                    depth_or_array_layers: 1,
+                },
+                mip_level_count: 1,
+                sample_count: 1,
+                dimension: wgpu::TextureDimension::D2,
+                format,
+                usage,
+                view_formats: &[],
+            })
+        };
+        let color = create(
+            "pick_ids",
+            PICK_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT | wgpu::TextureUsages::COPY_SRC,
+        );
+        let depth = create(
+            "pick_depth",
+            DEPTH_FORMAT,
+            wgpu::TextureUsages::RENDER_ATTACHMENT,
+        );
+        Self {
+            size,
+            color_view: color.create_view(&wgpu::TextureViewDescriptor::default()),
+            color,
+            depth_view: depth.create_view(&wgpu::TextureViewDescriptor::default()),
+        }
+    }
+}
+
+/// A pixel being copied back from the GPU.
+struct InFlight {
+    /// Set once the readback buffer is mapped, to whether mapping succeeded.
+    mapped: Arc<OnceLock<bool>>,
+    /// Which node owned each range of instances when the ids were drawn.
+    instance_owners: Vec<(Range<u32>, NodeId)>,
+}
+
+pub struct PickResources {
+    picking: Arc<RwLock<Picking>>,
+    target: Option<PickTarget>,
+    readback_buffer: wgpu::Buffer,
+    in_flight: Option<InFlight>,
+}
+
+impl Resource for PickResources {}
+
+impl PickResources {
+    pub fn new(device: &wgpu::Device, picking: Arc<RwLock<Picking>>) -> Self {
+        let readback_buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("pick_readback"),
+            size: std::mem::size_of::<u32>() as wgpu::BufferAddress,
+            usage: wgpu::BufferUsages::COPY_DST | wgpu::BufferUsages::MAP_READ,
+            mapped_at_creation: false,
+        });
+        Self {
+            picking,
+            target: None,
+            readback_buffer,
+            in_flight: None,
+        }
+    }
+
+    /// Hands the picked node to [`Picking`] if the GPU has finished copying it.
+    pub fn read_back(&mut self, device: &wgpu::Device) {
+        let Some(in_flight) = &self.in_flight else {
+            return;
+        };
+        device.poll(wgpu::Maintain::Poll);
+        let Some(&mapped) = in_flight.mapped.get() else {
+            return;
+        };
+
+        let node = mapped
+            .then(|| {
+                let data = self.readback_buffer.slice(..).get_mapped_range();
+                let id = u32::from_ne_bytes([data[0], data[1], data[2], data[3]]);
+                drop(data);
+                self.readback_buffer.unmap();
+                // Zero is the clear value, where no instance was drawn.
+                let instance = id.checked_sub(1)?;
+                in_flight
+                    .instance_owners
+                    .iter()
+                    .find(|(instances, _)| instances.contains(&instance))
+                    .map(|&(_, node)| node)
+            })
+            .flatten();
+        self.in_flight = None;
+        self.picking.write().unwrap().finish(node);
+    }
+
+    /// Takes the pixel to pick this frame, if any, and makes sure the target
+    /// textures match the viewport.
+    ///
+    /// Only one pick is read back at a time; a newer request waits for the
+    /// previous one to finish.
+    pub fn begin(&mut self, device: &wgpu::Device, viewport_size: egui::Vec2) -> Option<[u32; 2]> {
+        if self.in_flight.is_some() {
+            return None;
+        }
+        let mut picking = self.picking.write().unwrap();
+        let pixel = picking.request.take()?;
+        let size = [viewport_size.x as u32, viewport_size.y as u32];
+        if pixel[0] >= size[0] || pixel[1] >= size[1] {
+            picking.finish(None);
+            return None;
+        }
+        if self.target.as_ref().map(|target| target.size) != Some(size) {
+            self.target = Some(PickTarget::new(device, size));
+        }
+        Some(pixel)
+    }
+}
+
+/// Draws the instance ids and starts copying `pixel` back to the CPU.
+///
+/// Must follow [`PickResources::begin`], and the preparation of the scene,
+/// camera and lights for this frame.
+pub(crate) fn render(
+    device: &wgpu::Device,
+    queue: &wgpu::Queue,
+    pixel: [u32; 2],
+    resources: &mut egui_wgpu::CallbackResources,
+) {
+    let in_flight = {
+        let pick_resources: &PickResources = resources.get().unwrap();
+        let scene_resources: &SceneResources = resources.get().unwrap();
+        let pipeline_cache: &PipelineCache = resources.get().unwrap();
+        let camera_resources: &CameraResources = resources.get().unwrap();
+        let light_resources: &LightResources = resources.get().unwrap();
+        let texture_resource: &TextureResource = resources.get().unwrap();
+        let target = pick_resources.target.as_ref().unwrap();
+
+        let mut encoder = device.create_command_encoder(&wgpu::CommandEncoderDescriptor {
+            label: Some("picking"),
+        });
+        {
+            let mut render_pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("picking"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: &target.color_view,
+                    resolve_target: None,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(wgpu::Color::TRANSPARENT),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &target.depth_view,
+                    depth_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(1.0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                    stencil_ops: None,
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            camera_resources.paint(&mut render_pass);
+            light_resources.paint(&mut render_pass);
+            scene_resources.paint(
+                &mut render_pass,
+                ScenePass::Picking,
+                pipeline_cache,
+                texture_resource,
+            );
+        }
+        encoder.copy_texture_to_buffer(
+            wgpu::ImageCopyTexture {
+                texture: &target.color,
+                mip_level: 0,
+                origin: wgpu::Origin3d {
+                    x: pixel[0],
+                    y: pixel[1],
+                    z: 0,
+                },
+                aspect: wgpu::TextureAspect::All,
+            },
+            wgpu::ImageCopyBuffer {
+                buffer: &pick_resources.readback_buffer,
+                layout: wgpu::ImageDataLayout::default(),
+            },
+            wgpu::Extent3d {
+                width: 1,
+                height: 1,
+                depth_or_array_layers: 1,
+            },
+        );
+        // Mapping has to wait for the copy to be submitted, so this can't go
+        // through egui's encoder.
+        queue.submit(Some(encoder.finish()));
+
+        let mapped = Arc::new(OnceLock::new());
+        pick_resources
+            .readback_buffer
+            .slice(..)
+            .map_async(wgpu::MapMode::Read, {
+                let mapped = Arc::clone(&mapped);
+                move |result| {
+                    if let Err(error) = &result {
+                        log::error!("Failed to read back the picked pixel: {error}");
+                    }
+                    let _ = mapped.set(result.is_ok());
+                }
+            });
+        InFlight {
+            mapped,
+            instance_owners: scene_resources.instance_owners(),
+        }
+    };
+    let pick_resources: &mut PickResources = resources.get_mut().unwrap();
+    pick_resources.in_flight = Some(in_flight);
+}
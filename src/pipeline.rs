@@ -0,0 +1,279 @@
+use egui_wgpu::{self, wgpu, RenderState};
+use std::{collections::HashMap, sync::Arc};
+
+use crate::{
+    mesh::{VertexLayout, VertexTrait},
+    preprocessor::{ShaderDefines, ShaderRegistry},
+    renderer::Resource,
+};
+
+/// The depth buffer eframe is asked for, in bits.
+pub const DEPTH_BUFFER_BITS: u8 = 32;
+
+/// The format eframe picks for a [`DEPTH_BUFFER_BITS`] depth buffer without stencil.
+pub const DEPTH_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth32Float;
+
+/// Preprocesses module `name` of `shaders` and compiles it.
+///
+/// In development builds the result is validated with naga first, so that a
+/// module edited on disk is reported rather than panicking wgpu.
+pub fn shader_module(
+    device: &wgpu::Device,
+    shaders: &ShaderRegistry,
+    name: &str,
+    defines: &ShaderDefines,
+) -> Result<wgpu::ShaderModule, String> {
+    let label = format!("{name} [{defines}]");
+    let source = shaders
+        .preprocess(name, defines)
+        .map_err(|error| error.to_string())?;
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    crate::hot_reload::validate_wgsl(&source, &label)?;
+
+    Ok(device.create_shader_module(wgpu::ShaderModuleDescriptor {
+        label: Some(&label),
+        source: wgpu::ShaderSource::Wgsl(source.into()),
+    }))
+}
+
+/// Everything that distinguishes one render pipeline from another.
+///
+/// Two draws with equal keys share a pipeline.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct PipelineKey {
+    /// Name of the shader module in the [`ShaderRegistry`], with `vs_main` and
+    /// `fs_main` entry points.
+    pub shader: &'static str,
+    /// Selects the shader permutation.
+    pub defines: ShaderDefines,
+    pub vertex_layouts: Vec<VertexLayout>,
+    pub blend: Option<wgpu::BlendState>,
+    pub cull_mode: Option<wgpu::Face>,
+    pub topology: wgpu::PrimitiveTopology,
+    pub depth_compare: wgpu::CompareFunction,
+    pub depth_write: bool,
+    /// The color target's format, or `None` for the egui render target.
+    pub target_format: Option<wgpu::TextureFormat>,
+}
+
+impl PipelineKey {
+    /// An opaque, back-face culled and depth tested triangle list drawn with `shader`.
+    pub fn new(shader: &'static str, vertex_layouts: Vec<VertexLayout>) -> Self {
+        Self {
+            shader,
+            defines: ShaderDefines::default(),
+            vertex_layouts,
+            blend: None,
+            cull_mode: Some(wgpu::Face::Back),
+            topology: wgpu::PrimitiveTopology::TriangleList,
+            depth_compare: wgpu::CompareFunction::Less,
+            depth_write: true,
+            target_format: None,
+        }
+    }
+
+    pub fn defines(mut self, defines: ShaderDefines) -> Self {
+        self.defines = defines;
+        self
+    }
+
+    pub fn blend(mut self, blend: Option<wgpu::BlendState>) -> Self {
+        self.blend = blend;
+        self
+    }
+
+    pub fn cull_mode(mut self, cull_mode: Option<wgpu::Face>) -> Self {
+        self.cull_mode = cull_mode;
+        self
+    }
+
+    pub fn depth(mut self, compare: wgpu::CompareFunction, write: bool) -> Self {
+        self.depth_compare = compare;
+        self.depth_write = write;
+        self
+    }
+
+    pub fn target_format(mut self, format: wgpu::TextureFormat) -> Self {
+        self.target_format = Some(format);
+        self
+    }
+
+    fn label(&self) -> String {
+        format!("{} [{}]", self.shader, self.defines)
+    }
+}
+
+/// Builds render pipelines on first use and keeps them for later frames.
+///
+/// Pipelines can only be created while the callback resources are mutable, so
+/// callbacks request theirs with [`PipelineCache::get_or_create`] in `prepare`
+/// and look them up again with [`PipelineCache::get`] in `paint`.
+pub struct PipelineCache {
+    target_format: wgpu::TextureFormat,
+    pipeline_layout: wgpu::PipelineLayout,
+    shaders: ShaderRegistry,
+    /// Whether `shaders` were reloaded from disk rather than built in.
+    reloaded: bool,
+    pipelines: HashMap<PipelineKey, Arc<wgpu::RenderPipeline>>,
+}
+
+impl Resource for PipelineCache {}
+
+impl PipelineCache {
+    pub fn new(
+        wgpu_render_state: &RenderState,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        shaders: ShaderRegistry,
+    ) -> Self {
+        let pipeline_layout =
+            wgpu_render_state
+                .device
+                .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                    label: Some("scene"),
+                    bind_group_layouts,
+                    push_constant_ranges: &[],
+                });
+
+        Self {
+            target_format: wgpu_render_state.target_format,
+            pipeline_layout,
+            shaders,
+            reloaded: false,
+            pipelines: HashMap::new(),
+        }
+    }
+
+    pub fn get(&self, key: &PipelineKey) -> Option<&wgpu::RenderPipeline> {
+        self.pipelines.get(key).map(Arc::as_ref)
+    }
+
+    /// Returns the pipeline for `key`, building it if this is the first request.
+    pub fn get_or_create(
+        &mut self,
+        device: &wgpu::Device,
+        key: &PipelineKey,
+    ) -> Result<Arc<wgpu::RenderPipeline>, String> {
+        if let Some(pipeline) = self.pipelines.get(key) {
+            return Ok(Arc::clone(pipeline));
+        }
+
+        let pipeline = match self.create_checked(device, &self.shaders, key) {
+            Ok(pipeline) => pipeline,
+            // A permutation first needed after a reload can fail where the
+            // ones rebuilt by the reload didn't, so fall back to the shaders
+            // built into the binary until the files on disk are fixed.
+            Err(error) if self.reloaded => {
+                log::warn!("{error}, using the built-in shaders instead");
+                self.create_checked(device, &ShaderRegistry::default(), key)
+                    .map_err(|_| error)?
+            }
+            Err(error) => return Err(error),
+        };
+        let pipeline = Arc::new(pipeline);
+        self.pipelines.insert(key.clone(), Arc::clone(&pipeline));
+        Ok(pipeline)
+    }
+
+    /// Rebuilds every cached pipeline from `shaders`, which are used for all
+    /// pipelines created afterwards.
+    ///
+    /// On error the pipelines currently in use are left untouched.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    pub fn reload(&mut self, device: &wgpu::Device, shaders: ShaderRegistry) -> Result<(), String> {
+        let pipelines: Result<HashMap<_, _>, String> = self
+            .pipelines
+            .keys()
+            .map(|key| {
+                let pipeline = self.create_checked(device, &shaders, key)?;
+                Ok((key.clone(), Arc::new(pipeline)))
+            })
+            .collect();
+
+        self.pipelines = pipelines?;
+        self.shaders = shaders;
+        self.reloaded = true;
+        Ok(())
+    }
+
+    /// Like [`Self::create_pipeline`], but also turns wgpu validation errors
+    /// into an `Err`, so that shaders edited on disk can't panic the app.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    fn create_checked(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        key: &PipelineKey,
+    ) -> Result<wgpu::RenderPipeline, String> {
+        crate::hot_reload::checked(device, &key.label(), || {
+            self.create_pipeline(device, shaders, key)
+        })
+    }
+
+    /// The built-in shaders can't change while the app runs, so there is
+    /// nothing to catch.
+    #[cfg(not(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32"))))]
+    fn create_checked(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        key: &PipelineKey,
+    ) -> Result<wgpu::RenderPipeline, String> {
+        self.create_pipeline(device, shaders, key)
+    }
+
+    fn create_pipeline(
+        &self,
+        device: &wgpu::Device,
+        shaders: &ShaderRegistry,
+        key: &PipelineKey,
+    ) -> Result<wgpu::RenderPipeline, String> {
+        let label = key.label();
+        let shader = shader_module(device, shaders, key.shader, &key.defines)?;
+
+        let buffers: Vec<_> = key.vertex_layouts.iter().map(VertexTrait::desc).collect();
+        let pipeline = device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+            label: Some(&label),
+            layout: Some(&self.pipeline_layout),
+            vertex: wgpu::VertexState {
+                module: &shader,
+                entry_point: "vs_main",
+                buffers: &buffers,
+            },
+            fragment: Some(wgpu::FragmentState {
+                module: &shader,
+                entry_point: "fs_main",
+                targets: &[Some(wgpu::ColorTargetState {
+                    format: key.target_format.unwrap_or(self.target_format),
+                    blend: key.blend,
+                    write_mask: wgpu::ColorWrites::ALL,
+                })],
+            }),
+            primitive: wgpu::PrimitiveState {
+                topology: key.topology,
+                strip_index_format: None,
+                front_face: wgpu::FrontFace::Ccw,
+                cull_mode: key.cull_mode,
+                // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
+                polygon_mode: wgpu::PolygonMode::Fill,
+                // Requires Features::DEPTH_CLIP_CONTROL
+                unclipped_depth: false,
+                // Requires Features::CONSERVATIVE_RASTERIZATION
+                conservative: false,
+            },
+            depth_stencil: Some(wgpu::DepthStencilState {
+                format: DEPTH_FORMAT,
+                depth_write_enabled: key.depth_write,
+                depth_compare: key.depth_compare,
+                stencil: wgpu::StencilState::default(),
+                bias: wgpu::DepthBiasState::default(),
+            }),
+            multisample: wgpu::MultisampleState {
+                count: 1,
+                mask: !0,
+                alpha_to_coverage_enabled: false,
+            },
+            multiview: None,
+        });
+        Ok(pipeline)
+    }
+}
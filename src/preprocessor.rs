@@ -0,0 +1,430 @@
+//! A small WGSL preprocessor.
+//!
+//! Shader modules are plain WGSL with a handful of line directives:
+//!
+//! - `#include "name"` pastes in another registered module. Every module is
+//!   included at most once per output, so shared definitions can be included
+//!   from several places.
+//! - `#define NAME` and `#define NAME value` define a flag or a value. Values
+//!   replace every later occurrence of `NAME` as a whole identifier.
+//! - `#undef NAME` removes a definition.
+//! - `#ifdef NAME`, `#ifndef NAME`, `#else` and `#endif` select lines by
+//!   whether a name is defined, and may be nested.
+
+use std::{
+    collections::{BTreeMap, HashMap, HashSet},
+    fmt,
+};
+
+/// The shader modules compiled into the binary.
+const BUILTIN_MODULES: &[(&str, &str)] = &[
+    ("common", include_str!("./shaders/common.wgsl")),
+    ("lights", include_str!("./shaders/lights.wgsl")),
+    ("lighting", include_str!("./shaders/lighting.wgsl")),
+    ("debug", include_str!("./shaders/debug.wgsl")),
+    ("forward", include_str!("./shaders/forward.wgsl")),
+    ("cluster", include_str!("./shaders/cluster.wgsl")),
+    ("outline", include_str!("./shaders/outline.wgsl")),
+    ("lines", include_str!("./shaders/lines.wgsl")),
+    ("grid", include_str!("./shaders/grid.wgsl")),
+    ("annotations", include_str!("./shaders/annotations.wgsl")),
+    ("clipping", include_str!("./shaders/clipping.wgsl")),
+];
+
+/// The set of names defined before a module is preprocessed.
+///
+/// Two pipelines built from the same module with equal defines share the
+/// same shader permutation.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Hash)]
+pub struct ShaderDefines(BTreeMap<String, String>);
+
+impl ShaderDefines {
+    /// Defines `name` as a flag, for use with `#ifdef`.
+    pub fn flag(mut self, name: impl Into<String>) -> Self {
+        self.0.insert(name.into(), String::new());
+        self
+    }
+
+    /// Defines `name` to be replaced by `value`.
+    pub fn value(mut self, name: impl Into<String>, value: impl ToString) -> Self {
+        self.0.insert(name.into(), value.to_string());
+        self
+    }
+
+    /// Adds every definition in `other`, overriding existing ones.
+    pub fn extend(mut self, other: &ShaderDefines) -> Self {
+        self.0
+            .extend(other.0.iter().map(|(k, v)| (k.clone(), v.clone())));
+        self
+    }
+}
+
+impl fmt::Display for ShaderDefines {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        for (index, (name, value)) in self.0.iter().enumerate() {
+            if index > 0 {
+                f.write_str(" ")?;
+            }
+            if value.is_empty() {
+                write!(f, "{name}")?;
+            } else {
+                write!(f, "{name}={value}")?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[derive(Debug)]
+pub struct PreprocessError {
+    pub module: String,
+    /// One-based line in `module`, or zero if the error isn't tied to a line.
+    pub line: usize,
+    pub message: String,
+}
+
+impl fmt::Display for PreprocessError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.line == 0 {
+            write!(f, "{}: {}", self.module, self.message)
+        } else {
+            write!(f, "{}:{}: {}", self.module, self.line, self.message)
+        }
+    }
+}
+
+impl std::error::Error for PreprocessError {}
+
+/// Named WGSL modules that can be included into each other.
+#[derive(Clone)]
+pub struct ShaderRegistry {
+    modules: HashMap<String, String>,
+}
+
+impl Default for ShaderRegistry {
+    fn default() -> Self {
+        let mut registry = Self::empty();
+        for (name, source) in BUILTIN_MODULES {
+            registry.register(*name, *source);
+        }
+        registry
+    }
+}
+
+impl ShaderRegistry {
+    pub fn empty() -> Self {
+        Self {
+            modules: HashMap::new(),
+        }
+    }
+
+    /// Loads every `*.wgsl` file in `dir`, named after its file stem.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    pub fn from_dir(dir: impl AsRef<std::path::Path>) -> std::io::Result<Self> {
+        let mut registry = Self::empty();
+        for entry in std::fs::read_dir(dir)? {
+            let path = entry?.path();
+            if path.extension().is_some_and(|ext| ext == "wgsl") {
+                if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                    registry.register(name, std::fs::read_to_string(&path)?);
+                }
+            }
+        }
+        Ok(registry)
+    }
+
+    /// Adds or replaces the module called `name`.
+    pub fn register(&mut self, name: impl Into<String>, source: impl Into<String>) {
+        self.modules.insert(name.into(), source.into());
+    }
+
+    /// Expands the directives in module `name` into a single WGSL source.
+    pub fn preprocess(
+        &self,
+        name: &str,
+        defines: &ShaderDefines,
+    ) -> Result<String, PreprocessError> {
+        let mut state = State {
+            defines: defines.0.clone(),
+            included: HashSet::new(),
+            stack: Vec::new(),
+            output: String::new(),
+        };
+        self.expand(name, None, &mut state)?;
+        Ok(state.output)
+    }
+
+    fn expand(
+        &self,
+        name: &str,
+        included_from: Option<(&str, usize)>,
+        state: &mut State,
+    ) -> Result<(), PreprocessError> {
+        let error_at = |message: String| match included_from {
+            Some((module, line)) => PreprocessError {
+                module: module.to_owned(),
+                line,
+                message,
+            },
+            None => PreprocessError {
+                module: name.to_owned(),
+                line: 0,
+                message,
+            },
+        };
+
+        let source = self
+            .modules
+            .get(name)
+            .ok_or_else(|| error_at(format!("unknown shader module `{name}`")))?;
+        if state.stack.iter().any(|module| module == name) {
+            return Err(error_at(format!(
+                "`{name}` includes itself through {}",
+                state.stack.join(" -> ")
+            )));
+        }
+        if !state.included.insert(name.to_owned()) {
+            return Ok(());
+        }
+        state.stack.push(name.to_owned());
+
+        let mut conditions: Vec<Condition> = Vec::new();
+        for (index, line) in source.lines().enumerate() {
+            let line_number = index + 1;
+            let error = |message: String| PreprocessError {
+                module: name.to_owned(),
+                line: line_number,
+                message,
+            };
+            let active = conditions.last().map_or(true, |c| c.active);
+
+            let Some(directive) = line.trim_start().strip_prefix('#') else {
+                if active {
+                    substitute(line, &state.defines, &mut state.output);
+                    state.output.push('\n');
+                }
+                continue;
+            };
+
+            let (keyword, argument) = directive
+                .split_once(char::is_whitespace)
+                .map_or((directive, ""), |(k, a)| (k, a.trim()));
+            match keyword {
+                "ifdef" | "ifndef" => {
+                    let defined = state.defines.contains_key(argument);
+                    let taken = defined == (keyword == "ifdef");
+                    conditions.push(Condition {
+                        active: active && taken,
+                        parent_active: active,
+                        taken,
+                        seen_else: false,
+                    });
+                }
+                "else" => {
+                    let condition = conditions
+                        .last_mut()
+                        .ok_or_else(|| error("`#else` without `#ifdef`".to_owned()))?;
+                    if condition.seen_else {
+                        return Err(error("duplicate `#else`".to_owned()));
+                    }
+                    condition.seen_else = true;
+                    condition.active = condition.parent_active && !condition.taken;
+                }
+                "endif" => {
+                    conditions
+                        .pop()
+                        .ok_or_else(|| error("`#endif` without `#ifdef`".to_owned()))?;
+                }
+                _ if !active => {}
+                "define" => {
+                    let (define, value) = argument
+                        .split_once(char::is_whitespace)
+                        .map_or((argument, ""), |(d, v)| (d, v.trim()));
+                    if define.is_empty() {
+                        return Err(error("`#define` without a name".to_owned()));
+                    }
+                    state.defines.insert(define.to_owned(), value.to_owned());
+                }
+                "undef" => {
+                    state.defines.remove(argument);
+                }
+                "include" => {
+                    let module = argument
+                        .strip_prefix('"')
+                        .and_then(|a| a.strip_suffix('"'))
+                        .ok_or_else(|| {
+                            error(format!("expected `#include \"name\"`, got `{line}`"))
+                        })?;
+                    self.expand(module, Some((name, line_number)), state)?;
+                }
+                _ => return Err(error(format!("unknown directive `#{keyword}`"))),
+            }
+        }
+
+        if !conditions.is_empty() {
+            return Err(error_at(format!("unterminated `#ifdef` in `{name}`")));
+        }
+        state.stack.pop();
+        Ok(())
+    }
+}
+
+struct State {
+    defines: BTreeMap<String, String>,
+    included: HashSet<String>,
+    stack: Vec<String>,
+    output: String,
+}
+
+struct Condition {
+    active: bool,
+    parent_active: bool,
+    taken: bool,
+    seen_else: bool,
+}
+
+/// Copies `line` to `output`, replacing identifiers that have a defined value.
+fn substitute(line: &str, defines: &BTreeMap<String, String>, output: &mut String) {
+    let is_identifier = |c: char| c.is_ascii_alphanumeric() || c == '_';
+    let mut rest = line;
+    while let Some(start) = rest.find(|c: char| c.is_ascii_alphabetic() || c == '_') {
+        let (before, word_start) = rest.split_at(start);
+        output.push_str(before);
+        let end = word_start
+            .find(|c: char| !is_identifier(c))
+            .unwrap_or(word_start.len());
+        let (word, after) = word_start.split_at(end);
+        match defines.get(word) {
+            Some(value) if !value.is_empty() => output.push_str(value),
+            _ => output.push_str(word),
+        }
+        rest = after;
+    }
+    output.push_str(rest);
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn registry(modules: &[(&str, &str)]) -> ShaderRegistry {
+        let mut registry = ShaderRegistry::empty();
+        for (name, source) in modules {
+            registry.register(*name, *source);
+        }
+        registry
+    }
+
+    fn lines(output: &str) -> Vec<&str> {
+        output.lines().map(str::trim).collect()
+    }
+
+    #[test]
+    fn includes_each_module_once() {
+        let registry = registry(&[
+            ("main", "#include \"a\"\n#include \"b\"\nmain"),
+            ("a", "#include \"common\"\na"),
+            ("b", "#include \"common\"\nb"),
+            ("common", "common"),
+        ]);
+        let output = registry
+            .preprocess("main", &ShaderDefines::default())
+            .unwrap();
+        assert_eq!(lines(&output), ["common", "a", "b", "main"]);
+    }
+
+    #[test]
+    fn rejects_include_cycles() {
+        let registry = registry(&[("a", "#include \"b\""), ("b", "\n#include \"a\"")]);
+        let error = registry
+            .preprocess("a", &ShaderDefines::default())
+            .unwrap_err();
+        assert_eq!(error.module, "b");
+        assert_eq!(error.line, 2);
+        assert!(error.message.contains("a -> b"), "{error}");
+    }
+
+    #[test]
+    fn rejects_unknown_modules() {
+        let registry = registry(&[("a", "#include \"missing\"")]);
+        let error = registry
+            .preprocess("a", &ShaderDefines::default())
+            .unwrap_err();
+        assert_eq!((error.module.as_str(), error.line), ("a", 1));
+    }
+
+    #[test]
+    fn selects_nested_branches() {
+        let source = "\
+#ifdef OUTER
+  #ifdef INNER
+    both
+  #else
+    outer only
+  #endif
+#else
+  #ifndef INNER
+    neither
+  #else
+    inner only
+  #endif
+#endif";
+        let registry = registry(&[("main", source)]);
+        let cases = [
+            (ShaderDefines::default(), "neither"),
+            (ShaderDefines::default().flag("INNER"), "inner only"),
+            (ShaderDefines::default().flag("OUTER"), "outer only"),
+            (ShaderDefines::default().flag("OUTER").flag("INNER"), "both"),
+        ];
+        for (defines, expected) in cases {
+            let output = registry.preprocess("main", &defines).unwrap();
+            assert_eq!(lines(&output), [expected], "with {defines}");
+        }
+    }
+
+    #[test]
+    fn rejects_unbalanced_conditions() {
+        for source in [
+            "#ifdef A",
+            "#endif",
+            "#else",
+            "#ifdef A\n#else\n#else\n#endif",
+        ] {
+            let registry = registry(&[("main", source)]);
+            assert!(
+                registry
+                    .preprocess("main", &ShaderDefines::default())
+                    .is_err(),
+                "{source:?}"
+            );
+        }
+    }
+
+    #[test]
+    fn substitutes_whole_identifiers() {
+        let registry = registry(&[(
+            "main",
+            "#define SIZE 4u\nSIZE SIZED _SIZE SIZE_2 SIZE;\n#undef SIZE\nSIZE",
+        )]);
+        let output = registry
+            .preprocess("main", &ShaderDefines::default())
+            .unwrap();
+        assert_eq!(lines(&output), ["4u SIZED _SIZE SIZE_2 4u;", "SIZE"]);
+    }
+
+    #[test]
+    fn later_defines_override_given_ones() {
+        let registry = registry(&[("main", "COUNT\n#define COUNT 2\nCOUNT")]);
+        let defines = ShaderDefines::default().value("COUNT", 1);
+        let output = registry.preprocess("main", &defines).unwrap();
+        assert_eq!(lines(&output), ["1", "2"]);
+    }
+
+    #[test]
+    fn flags_are_not_substituted() {
+        let registry = registry(&[("main", "#ifdef FLAG\nFLAG\n#endif")]);
+        let defines = ShaderDefines::default().flag("FLAG");
+        let output = registry.preprocess("main", &defines).unwrap();
+        assert_eq!(lines(&output), ["FLAG"]);
+    }
+}
@@ -0,0 +1,627 @@
+//! Meshes generated from a few parameters, for test scenes and for standing in
+//! for assets that are still loading.
+//!
+//! Every primitive is centered on the origin with +Y up, and has normals,
+//! tangents and texture coordinates. Texture coordinates start at the top of
+//! the image, so they run against the bitangent, `cross(normal, tangent)`,
+//! which is recorded as -1 in the tangent's w.
+
+use cgmath::{InnerSpace, Vector3};
+use serde::{Deserialize, Serialize};
+use std::{collections::HashMap, f32::consts::PI};
+
+use crate::mesh::Mesh;
+
+/// The handedness stored with every tangent, see the module docs.
+const HANDEDNESS: f32 = -1.0;
+
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub enum Primitive {
+    Cube {
+        size: f32,
+    },
+    /// Split into quads along lines of longitude and latitude.
+    UvSphere {
+        radius: f32,
+        segments: u32,
+        rings: u32,
+    },
+    /// A subdivided icosahedron, with evenly sized triangles.
+    Icosphere {
+        radius: f32,
+        subdivisions: u32,
+    },
+    /// A square facing +Y.
+    Plane {
+        size: f32,
+        subdivisions: u32,
+    },
+    Cylinder {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    /// With its point up.
+    Cone {
+        radius: f32,
+        height: f32,
+        segments: u32,
+    },
+    /// Lying flat, around the Y axis.
+    Torus {
+        /// From the center to the middle of the tube.
+        major_radius: f32,
+        /// Of the tube.
+        minor_radius: f32,
+        major_segments: u32,
+        minor_segments: u32,
+    },
+    /// A cylinder with rounded ends.
+    Capsule {
+        radius: f32,
+        /// From end to end, including the rounded ends.
+        height: f32,
+        segments: u32,
+        /// Of each rounded end.
+        rings: u32,
+    },
+}
+
+impl Primitive {
+    /// One of each kind, about one unit across.
+    pub const DEFAULTS: [Primitive; 8] = [
+        Primitive::Cube { size: 1.0 },
+        Primitive::UvSphere {
+            radius: 0.5,
+            segments: 32,
+            rings: 16,
+        },
+        Primitive::Icosphere {
+            radius: 0.5,
+            subdivisions: 3,
+        },
+        Primitive::Plane {
+            size: 2.0,
+            subdivisions: 1,
+        },
+        Primitive::Cylinder {
+            radius: 0.5,
+            height: 1.0,
+            segments: 32,
+        },
+        Primitive::Cone {
+            radius: 0.5,
+            height: 1.0,
+            segments: 32,
+        },
+        Primitive::Torus {
+            major_radius: 0.5,
+            minor_radius: 0.15,
+            major_segments: 48,
+            minor_segments: 16,
+        },
+        Primitive::Capsule {
+            radius: 0.25,
+            height: 1.0,
+            segments: 32,
+            rings: 8,
+        },
+    ];
+
+    pub fn label(&self) -> &'static str {
+        match self {
+            Primitive::Cube { .. } => "Cube",
+            Primitive::UvSphere { .. } => "UV Sphere",
+            Primitive::Icosphere { .. } => "Icosphere",
+            Primitive::Plane { .. } => "Plane",
+            Primitive::Cylinder { .. } => "Cylinder",
+            Primitive::Cone { .. } => "Cone",
+            Primitive::Torus { .. } => "Torus",
+            Primitive::Capsule { .. } => "Capsule",
+        }
+    }
+
+    /// Generates the mesh. Counts below what a closed shape needs are raised
+    /// to the minimum.
+    pub fn mesh(&self) -> Mesh {
+        let mut builder = Builder::default();
+        match *self {
+            Primitive::Cube { size } => cube(&mut builder, size / 2.0),
+            Primitive::UvSphere {
+                radius,
+                segments,
+                rings,
+            } => uv_sphere(&mut builder, radius, segments.max(3), rings.max(2)),
+            Primitive::Icosphere {
+                radius,
+                subdivisions,
+            } => icosphere(&mut builder, radius, subdivisions),
+            Primitive::Plane { size, subdivisions } => {
+                let subdivisions = subdivisions.max(1);
+                builder.grid(subdivisions, subdivisions, |u, v| Vertex {
+                    position: Vector3::new(u - 0.5, 0.0, 0.5 - v) * size,
+                    normal: Vector3::unit_y(),
+                    tangent: Vector3::unit_x(),
+                    tex_coords: [u, 1.0 - v],
+                });
+            }
+            Primitive::Cylinder {
+                radius,
+                height,
+                segments,
+            } => cone(&mut builder, radius, radius, height, segments.max(3)),
+            Primitive::Cone {
+                radius,
+                height,
+                segments,
+            } => cone(&mut builder, radius, 0.0, height, segments.max(3)),
+            Primitive::Torus {
+                major_radius,
+                minor_radius,
+                major_segments,
+                minor_segments,
+            } => builder.grid(major_segments.max(3), minor_segments.max(3), |u, v| {
+                let (around, across) = (u * 2.0 * PI, v * 2.0 * PI);
+                let outward = Vector3::new(around.sin(), 0.0, around.cos());
+                let normal = outward * across.cos() + Vector3::unit_y() * across.sin();
+                Vertex {
+                    position: outward * major_radius + normal * minor_radius,
+                    normal,
+                    tangent: Vector3::new(around.cos(), 0.0, -around.sin()),
+                    tex_coords: [u, 1.0 - v],
+                }
+            }),
+            Primitive::Capsule {
+                radius,
+                height,
+                segments,
+                rings,
+            } => capsule(&mut builder, radius, height, segments.max(3), rings.max(1)),
+        }
+        builder.mesh
+    }
+}
+
+struct Vertex {
+    position: Vector3<f32>,
+    normal: Vector3<f32>,
+    tangent: Vector3<f32>,
+    tex_coords: [f32; 2],
+}
+
+#[derive(Default)]
+struct Builder {
+    mesh: Mesh,
+}
+
+impl Builder {
+    fn vertex(&mut self, vertex: Vertex) -> u32 {
+        let mesh = &mut self.mesh;
+        mesh.positions.push(vertex.position.into());
+        mesh.normals.push(vertex.normal.into());
+        mesh.tangents.push(vertex.tangent.extend(HANDEDNESS).into());
+        mesh.tex_coords.push(vertex.tex_coords);
+        mesh.positions.len() as u32 - 1
+    }
+
+    /// Adds a grid of `columns` by `rows` quads over a surface, with
+    /// `vertex(u, v)` giving its vertices for `u` and `v` from 0 to 1.
+    ///
+    /// The quads face the side of `cross(du, dv)`, where `du` and `dv` are the
+    /// directions the surface moves in as `u` and `v` grow.
+    fn grid(&mut self, columns: u32, rows: u32, vertex: impl Fn(f32, f32) -> Vertex) {
+        let start = self.mesh.vertex_count() as u32;
+        for row in 0..=rows {
+            for column in 0..=columns {
+                self.vertex(vertex(
+                    column as f32 / columns as f32,
+                    row as f32 / rows as f32,
+                ));
+            }
+        }
+        let index = |column: u32, row: u32| start + row * (columns + 1) + column;
+        for row in 0..rows {
+            for column in 0..columns {
+                let [a, b, c, d] = [
+                    index(column, row),
+                    index(column + 1, row),
+                    index(column + 1, row + 1),
+                    index(column, row + 1),
+                ];
+                self.mesh.indices.extend([a, b, c, a, c, d]);
+            }
+        }
+    }
+
+    /// Adds a flat disk around `center`, facing `normal`, as a fan of
+    /// `segments` triangles. The texture is stretched over the disk with its
+    /// top towards `cross(normal, tangent)`.
+    fn disk(
+        &mut self,
+        center: Vector3<f32>,
+        normal: Vector3<f32>,
+        tangent: Vector3<f32>,
+        radius: f32,
+        segments: u32,
+    ) {
+        let bitangent = normal.cross(tangent);
+        let middle = self.vertex(Vertex {
+            position: center,
+            normal,
+            tangent,
+            tex_coords: [0.5, 0.5],
+        });
+        for segment in 0..=segments {
+            let angle = segment as f32 / segments as f32 * 2.0 * PI;
+            let (sin, cos) = angle.sin_cos();
+            self.vertex(Vertex {
+                position: center + (tangent * cos + bitangent * sin) * radius,
+                normal,
+                tangent,
+                tex_coords: [0.5 + cos / 2.0, 0.5 - sin / 2.0],
+            });
+        }
+        for segment in 0..segments {
+            let rim = middle + 1 + segment;
+            self.mesh.indices.extend([middle, rim, rim + 1]);
+        }
+    }
+}
+
+/// Six faces, each with its own corners so that edges stay sharp.
+fn cube(builder: &mut Builder, half_size: f32) {
+    let faces = [
+        (Vector3::unit_x(), -Vector3::unit_z()),
+        (-Vector3::unit_x(), Vector3::unit_z()),
+        (Vector3::unit_y(), Vector3::unit_x()),
+        (-Vector3::unit_y(), Vector3::unit_x()),
+        (Vector3::unit_z(), Vector3::unit_x()),
+        (-Vector3::unit_z(), -Vector3::unit_x()),
+    ];
+    for (normal, tangent) in faces {
+        let bitangent = normal.cross(tangent);
+        builder.grid(1, 1, |u, v| Vertex {
+            position: (normal + tangent * (2.0 * u - 1.0) + bitangent * (2.0 * v - 1.0))
+                * half_size,
+            normal,
+            tangent,
+            tex_coords: [u, 1.0 - v],
+        });
+    }
+}
+
+/// The point on the unit sphere at `around` radians around the Y axis from +Z,
+/// and `down` radians down from the top.
+fn sphere_point(around: f32, down: f32) -> Vector3<f32> {
+    Vector3::new(
+        down.sin() * around.sin(),
+        down.cos(),
+        down.sin() * around.cos(),
+    )
+}
+
+/// The direction around the Y axis at `around` radians from +Z.
+fn around_tangent(around: f32) -> Vector3<f32> {
+    Vector3::new(around.cos(), 0.0, -around.sin())
+}
+
+fn uv_sphere(builder: &mut Builder, radius: f32, segments: u32, rings: u32) {
+    builder.grid(segments, rings, |u, v| {
+        let around = u * 2.0 * PI;
+        let normal = sphere_point(around, (1.0 - v) * PI);
+        Vertex {
+            position: normal * radius,
+            normal,
+            tangent: around_tangent(around),
+            tex_coords: [u, 1.0 - v],
+        }
+    });
+}
+
+/// An icosahedron with every triangle split into four `subdivisions` times,
+/// pushed out onto the sphere.
+///
+/// Texture coordinates are mapped like the UV sphere's. Triangles crossing the
+/// seam at the back get their own copies of the corners past it, and corners
+/// on the poles take the horizontal texture coordinate of their triangle.
+fn icosphere(builder: &mut Builder, radius: f32, subdivisions: u32) {
+    let t = (1.0 + 5f32.sqrt()) / 2.0;
+    let mut points: Vec<Vector3<f32>> = [
+        [-1.0, t, 0.0],
+        [1.0, t, 0.0],
+        [-1.0, -t, 0.0],
+        [1.0, -t, 0.0],
+        [0.0, -1.0, t],
+        [0.0, 1.0, t],
+        [0.0, -1.0, -t],
+        [0.0, 1.0, -t],
+        [t, 0.0, -1.0],
+        [t, 0.0, 1.0],
+        [-t, 0.0, -1.0],
+        [-t, 0.0, 1.0],
+    ]
+    .map(|point| Vector3::from(point).normalize())
+    .to_vec();
+    #[rustfmt::skip]
+    let mut triangles: Vec<[u32; 3]> = vec![
+        [0, 11, 5], [0, 5, 1], [0, 1, 7], [0, 7, 10], [0, 10, 11],
+        [1, 5, 9], [5, 11, 4], [11, 10, 2], [10, 7, 6], [7, 1, 8],
+        [3, 9, 4], [3, 4, 2], [3, 2, 6], [3, 6, 8], [3, 8, 9],
+        [4, 9, 5], [2, 4, 11], [6, 2, 10], [8, 6, 7], [9, 8, 1],
+    ];
+
+    for _ in 0..subdivisions {
+        // Shared edges share their midpoint.
+        let mut midpoints = HashMap::new();
+        let mut midpoint = |a: u32, b: u32| {
+            *midpoints.entry((a.min(b), a.max(b))).or_insert_with(|| {
+                points.push((points[a as usize] + points[b as usize]).normalize());
+                points.len() as u32 - 1
+            })
+        };
+        triangles = triangles
+            .into_iter()
+            .flat_map(|[a, b, c]| {
+                let (ab, bc, ca) = (midpoint(a, b), midpoint(b, c), midpoint(c, a));
+                [[a, ab, ca], [b, bc, ab], [c, ca, bc], [ab, bc, ca]]
+            })
+            .collect();
+    }
+
+    let around = |point: Vector3<f32>| point.x.atan2(point.z);
+    let is_pole = |point: Vector3<f32>| point.y.abs() > 1.0 - 1e-6;
+    let mut vertices = HashMap::new();
+    for triangle in triangles {
+        let corners = triangle.map(|index| points[index as usize]);
+        let mut u = corners.map(|point| (around(point) / (2.0 * PI)).rem_euclid(1.0));
+        // Past the seam, the corners on the near side wrap around.
+        let not_poles = || (0..3).filter(|&i| !is_pole(corners[i]));
+        let max = not_poles().map(|i| u[i]).fold(0.0, f32::max);
+        for i in not_poles() {
+            if max - u[i] > 0.5 {
+                u[i] += 1.0;
+            }
+        }
+        for i in 0..3 {
+            if is_pole(corners[i]) {
+                u[i] = not_poles().map(|j| u[j]).sum::<f32>() / 2.0;
+            }
+        }
+
+        for i in 0..3 {
+            let point = corners[i];
+            let key = (triangle[i], u[i].to_bits());
+            let index = *vertices.entry(key).or_insert_with(|| {
+                builder.vertex(Vertex {
+                    position: point * radius,
+                    normal: point,
+                    tangent: around_tangent(u[i] * 2.0 * PI),
+                    tex_coords: [u[i], point.y.clamp(-1.0, 1.0).acos() / PI],
+                })
+            });
+            builder.mesh.indices.push(index);
+        }
+    }
+}
+
+/// A cylinder, or a cone if `top_radius` is zero, with flat ends.
+fn cone(builder: &mut Builder, radius: f32, top_radius: f32, height: f32, segments: u32) {
+    let half_height = height / 2.0;
+    // The side leans in by the change in radius over the height, so its
+    // normals lean up by the same ratio.
+    let slope = (radius - top_radius) / height.max(f32::EPSILON);
+    builder.grid(segments, 1, |u, v| {
+        let around = u * 2.0 * PI;
+        let outward = Vector3::new(around.sin(), 0.0, around.cos());
+        let ring_radius = radius + (top_radius - radius) * v;
+        Vertex {
+            position: outward * ring_radius + Vector3::unit_y() * (v * height - half_height),
+            normal: (outward + Vector3::unit_y() * slope).normalize(),
+            tangent: around_tangent(around),
+            tex_coords: [u, 1.0 - v],
+        }
+    });
+    if top_radius > 0.0 {
+        let top = Vector3::unit_y() * half_height;
+        builder.disk(
+            top,
+            Vector3::unit_y(),
+            Vector3::unit_x(),
+            top_radius,
+            segments,
+        );
+    }
+    let bottom = -Vector3::unit_y() * half_height;
+    builder.disk(
+        bottom,
+        -Vector3::unit_y(),
+        Vector3::unit_x(),
+        radius,
+        segments,
+    );
+}
+
+/// A sphere split at the equator, with a cylinder of the remaining height
+/// between the halves. The texture is spread over the whole height.
+fn capsule(builder: &mut Builder, radius: f32, height: f32, segments: u32, rings: u32) {
+    let half_middle = (height / 2.0 - radius).max(0.0);
+    let total = 2.0 * (half_middle + radius);
+    // Each half takes `rings` rows, and the cylinder one more.
+    let rows = 2 * rings + 1;
+    builder.grid(segments, rows, |u, v| {
+        let around = u * 2.0 * PI;
+        let row = (v * rows as f32).round() as u32;
+        let (down, center) = if row <= rings {
+            let down = PI - row as f32 / rings as f32 * PI / 2.0;
+            (down, -half_middle)
+        } else {
+            let down = (rows - row) as f32 / rings as f32 * PI / 2.0;
+            (down, half_middle)
+        };
+        let normal = sphere_point(around, down);
+        let position = normal * radius + Vector3::unit_y() * center;
+        Vertex {
+            position,
+            normal,
+            tangent: around_tangent(around),
+            tex_coords: [u, 0.5 - position.y / total.max(f32::EPSILON)],
+        }
+    });
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn triangles(mesh: &Mesh) -> impl Iterator<Item = [usize; 3]> + '_ {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]].map(|i| i as usize))
+    }
+
+    /// The triangle's normal from its winding, scaled by twice its area.
+    fn winding_normal(mesh: &Mesh, [a, b, c]: [usize; 3]) -> Vector3<f32> {
+        let [a, b, c] = [a, b, c].map(|i| Vector3::from(mesh.positions[i]));
+        (b - a).cross(c - a)
+    }
+
+    fn counts(primitive: Primitive) -> (usize, usize) {
+        let mesh = primitive.mesh();
+        (mesh.vertex_count(), mesh.indices.len())
+    }
+
+    #[test]
+    fn generate_the_expected_counts() {
+        assert_eq!(counts(Primitive::Cube { size: 1.0 }), (24, 36));
+        let uv_sphere = Primitive::UvSphere {
+            radius: 1.0,
+            segments: 8,
+            rings: 4,
+        };
+        assert_eq!(counts(uv_sphere), (9 * 5, 6 * 8 * 4));
+        for subdivisions in 0..3 {
+            let (vertices, indices) = counts(Primitive::Icosphere {
+                radius: 1.0,
+                subdivisions,
+            });
+            let triangles = 20 * 4usize.pow(subdivisions);
+            assert_eq!(indices, 3 * triangles);
+            // Every point of the sphere once, some again past the seam.
+            let points = triangles / 2 + 2;
+            assert!((points..points + triangles).contains(&vertices));
+        }
+        // The side, with one more column of vertices for the seam, and the
+        // bottom with a vertex in the middle.
+        let cone = Primitive::Cone {
+            radius: 1.0,
+            height: 1.0,
+            segments: 8,
+        };
+        assert_eq!(counts(cone), (2 * 9 + 10, 6 * 8 + 3 * 8));
+        let cylinder = Primitive::Cylinder {
+            radius: 1.0,
+            height: 1.0,
+            segments: 8,
+        };
+        assert_eq!(counts(cylinder), (2 * 9 + 2 * 10, 6 * 8 + 2 * 3 * 8));
+        let capsule = Primitive::Capsule {
+            radius: 0.5,
+            height: 2.0,
+            segments: 8,
+            rings: 3,
+        };
+        assert_eq!(counts(capsule), (9 * 8, 6 * 8 * 7));
+    }
+
+    #[test]
+    fn raise_counts_to_the_minimum() {
+        let uv_sphere = Primitive::UvSphere {
+            radius: 1.0,
+            segments: 0,
+            rings: 0,
+        };
+        assert_eq!(counts(uv_sphere), (4 * 3, 6 * 3 * 2));
+        let cone = Primitive::Cone {
+            radius: 1.0,
+            height: 1.0,
+            segments: 1,
+        };
+        assert_eq!(counts(cone), (2 * 4 + 5, 6 * 3 + 3 * 3));
+    }
+
+    #[test]
+    fn indices_normals_and_tangents_are_valid() {
+        for primitive in Primitive::DEFAULTS {
+            let mesh = primitive.mesh();
+            let label = primitive.label();
+            assert_eq!(mesh.indices.len() % 3, 0, "{label}");
+            assert!(
+                mesh.indices
+                    .iter()
+                    .all(|&i| (i as usize) < mesh.vertex_count()),
+                "{label}"
+            );
+            assert_eq!(mesh.normals.len(), mesh.vertex_count(), "{label}");
+            assert_eq!(mesh.tangents.len(), mesh.vertex_count(), "{label}");
+            assert_eq!(mesh.tex_coords.len(), mesh.vertex_count(), "{label}");
+            for normal in &mesh.normals {
+                let length = Vector3::from(*normal).magnitude();
+                assert!((length - 1.0).abs() < 1e-4, "{label}: {normal:?}");
+            }
+            for tangent in &mesh.tangents {
+                let length = Vector3::new(tangent[0], tangent[1], tangent[2]).magnitude();
+                assert!((length - 1.0).abs() < 1e-4, "{label}: {tangent:?}");
+                assert_eq!(tangent[3], HANDEDNESS, "{label}");
+            }
+        }
+    }
+
+    #[test]
+    fn triangles_wind_towards_their_normals() {
+        for primitive in Primitive::DEFAULTS {
+            let mesh = primitive.mesh();
+            for triangle in triangles(&mesh) {
+                let winding = winding_normal(&mesh, triangle);
+                // The rows of vertices on a pole make some triangles empty.
+                if winding.magnitude2() < 1e-12 {
+                    continue;
+                }
+                let normal: Vector3<f32> = triangle
+                    .iter()
+                    .map(|&i| Vector3::from(mesh.normals[i]))
+                    .sum();
+                assert!(
+                    winding.dot(normal) > 0.0,
+                    "{}: {triangle:?}",
+                    primitive.label()
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn closed_primitives_wind_outwards() {
+        let convex = Primitive::DEFAULTS.into_iter().filter(|primitive| {
+            !matches!(primitive, Primitive::Plane { .. } | Primitive::Torus { .. })
+        });
+        for primitive in convex {
+            let mesh = primitive.mesh();
+            for triangle in triangles(&mesh) {
+                let winding = winding_normal(&mesh, triangle);
+                if winding.magnitude2() < 1e-12 {
+                    continue;
+                }
+                let centroid: Vector3<f32> = triangle
+                    .iter()
+                    .map(|&i| Vector3::from(mesh.positions[i]))
+                    .sum::<Vector3<f32>>()
+                    / 3.0;
+                assert!(
+                    winding.dot(centroid) > 0.0,
+                    "{}: {triangle:?}",
+                    primitive.label()
+                );
+            }
+        }
+    }
+}
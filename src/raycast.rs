@@ -0,0 +1,244 @@
+use cgmath::{InnerSpace, Point3, SquareMatrix, Transform as _, Vector3};
+
+use crate::{
+    bounds::Ray,
+    mesh::{Mesh, VertexAttribute},
+    scene::{self, NodeId, Scene},
+};
+
+/// Where a ray first meets a drawn mesh.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Hit {
+    pub node: NodeId,
+    /// Index into the node's instances.
+    pub instance: usize,
+    /// Index of the triangle within the mesh.
+    pub triangle: usize,
+    /// How far along the ray the hit is, in multiples of its direction.
+    pub t: f32,
+    /// In world space.
+    pub position: Point3<f32>,
+    /// The world space surface normal, facing back along the ray.
+    ///
+    /// Interpolated from the vertex normals if the mesh has them.
+    pub normal: Vector3<f32>,
+    /// Weights of the triangle's three corners at the hit.
+    pub barycentrics: [f32; 3],
+}
+
+impl Hit {
+    /// The world space corners of the triangle that was hit.
+    pub fn corners(&self, scene: &Scene) -> [Point3<f32>; 3] {
+        let node = scene.node(self.node);
+        let mesh = &scene.meshes()[node.mesh.expect("hit nodes have meshes").index()];
+        let model = scene.world_matrices()[self.node.index()]
+            * node.instances[self.instance].transform.matrix();
+        let corners = &mesh.indices[self.triangle * 3..self.triangle * 3 + 3];
+        [0, 1, 2].map(|i| model.transform_point(Point3::from(mesh.positions[corners[i] as usize])))
+    }
+}
+
+/// The closest hit of a world space `ray` on the visible meshes of `scene`,
+/// among the points `keep` returns true for, such as those not clipped away.
+///
+/// Every instance is first tested against its mesh's bounding sphere and box,
+/// so only the triangles of meshes the ray passes near are tested.
+pub fn raycast(scene: &Scene, ray: &Ray, keep: impl Fn(Point3<f32>) -> bool) -> Option<Hit> {
+    let world_matrices = scene.world_matrices();
+    let mut closest: Option<Hit> = None;
+    for (id, node) in scene.drawn_nodes() {
+        let Some(mesh_id) = node.mesh else {
+            continue;
+        };
+        let Some(bounds) = scene.mesh_bounds(mesh_id) else {
+            continue;
+        };
+        let mesh = &scene.meshes()[mesh_id.index()];
+        for (index, instance) in node.instances.iter().enumerate() {
+            let model = world_matrices[id.index()] * instance.transform.matrix();
+            let Some(inverse) = model.invert() else {
+                continue;
+            };
+            // The direction is left unnormalized, so `t` is the same in both spaces.
+            let local_ray = ray.transformed(&inverse);
+            if !bounds.sphere.intersects_ray(&local_ray) {
+                continue;
+            }
+            let Some((near, _)) = bounds.aabb.intersect_ray(&local_ray) else {
+                continue;
+            };
+            if closest.is_some_and(|hit| hit.t < near) {
+                continue;
+            }
+            let Some((triangle, t, barycentrics)) =
+                intersect_mesh(mesh, &local_ray, |t| keep(ray.at(t)))
+            else {
+                continue;
+            };
+            if closest.is_some_and(|hit| hit.t <= t) {
+                continue;
+            }
+
+            let normal = scene::normal_matrix(model) * local_normal(mesh, triangle, barycentrics);
+            let normal = normal.normalize();
+            closest = Some(Hit {
+                node: id,
+                instance: index,
+                triangle,
+                t,
+                position: ray.at(t),
+                normal: if normal.dot(ray.direction) > 0.0 {
+                    -normal
+                } else {
+                    normal
+                },
+                barycentrics,
+            });
+        }
+    }
+    closest
+}
+
+/// The closest triangle of `mesh` the ray crosses where `keep` returns true
+/// for its `t`, with the hit's `t` and barycentric weights.
+fn intersect_mesh(
+    mesh: &Mesh,
+    ray: &Ray,
+    keep: impl Fn(f32) -> bool,
+) -> Option<(usize, f32, [f32; 3])> {
+    let corner = |index: u32| Point3::from(mesh.positions[index as usize]);
+    mesh.indices
+        .chunks_exact(3)
+        .enumerate()
+        .filter_map(|(triangle, corners)| {
+            let (t, u, v) =
+                ray.intersect_triangle(corner(corners[0]), corner(corners[1]), corner(corners[2]))?;
+            if !keep(t) {
+                return None;
+            }
+            Some((triangle, t, [1.0 - u - v, u, v]))
+        })
+        .min_by(|a, b| a.1.total_cmp(&b.1))
+}
+
+/// The normal at a point of a triangle, in the mesh's own space.
+fn local_normal(mesh: &Mesh, triangle: usize, barycentrics: [f32; 3]) -> Vector3<f32> {
+    let corners = &mesh.indices[triangle * 3..triangle * 3 + 3];
+    if mesh.has(VertexAttribute::Normal) {
+        corners
+            .iter()
+            .zip(barycentrics)
+            .map(|(&index, weight)| Vector3::from(mesh.normals[index as usize]) * weight)
+            .sum()
+    } else {
+        let [a, b, c] = [0, 1, 2].map(|i| Point3::from(mesh.positions[corners[i] as usize]));
+        (b - a).cross(c - a)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{scene::MeshId, scene_file::MeshSource, transform::Transform};
+
+    /// A unit square in the xy plane, facing +z.
+    fn square() -> Mesh {
+        Mesh {
+            positions: vec![
+                [0.0, 0.0, 0.0],
+                [1.0, 0.0, 0.0],
+                [1.0, 1.0, 0.0],
+                [0.0, 1.0, 0.0],
+            ],
+            indices: vec![0, 1, 2, 0, 2, 3],
+            ..Default::default()
+        }
+    }
+
+    fn at_z(z: f32) -> Transform {
+        Transform {
+            translation: Vector3::new(0.0, 0.0, z),
+            ..Default::default()
+        }
+    }
+
+    fn scene_with_square() -> (Scene, MeshId) {
+        let mut scene = Scene::default();
+        let mesh = scene.add_mesh(square(), MeshSource::Inline(square()));
+        (scene, mesh)
+    }
+
+    /// Straight down the z axis from `z`, through the square's upper triangle.
+    fn down_from(z: f32) -> Ray {
+        Ray {
+            origin: Point3::new(0.25, 0.5, z),
+            direction: Vector3::new(0.0, 0.0, -1.0),
+        }
+    }
+
+    #[test]
+    fn hits_the_front_of_a_mesh() {
+        let (mut scene, mesh) = scene_with_square();
+        let node = scene.add_instances(mesh, &[at_z(-1.0)]);
+
+        let hit = raycast(&scene, &down_from(4.0), |_| true).unwrap();
+        assert_eq!(hit.node, node);
+        assert_eq!(hit.triangle, 1);
+        assert_eq!(hit.t, 5.0);
+        assert_eq!(hit.position, Point3::new(0.25, 0.5, -1.0));
+        assert_eq!(hit.normal, Vector3::unit_z());
+        let corners = hit.corners(&scene);
+        assert_eq!(corners[0], Point3::new(0.0, 0.0, -1.0));
+    }
+
+    #[test]
+    fn misses_beside_and_behind_meshes() {
+        let (mut scene, mesh) = scene_with_square();
+        scene.add_instances(mesh, &[Transform::default()]);
+
+        let beside = Ray {
+            origin: Point3::new(2.0, 0.5, 1.0),
+            ..down_from(1.0)
+        };
+        assert_eq!(raycast(&scene, &beside, |_| true), None);
+        assert_eq!(raycast(&scene, &down_from(-1.0), |_| true), None);
+    }
+
+    #[test]
+    fn hits_backfaces_with_the_normal_facing_the_ray() {
+        let (mut scene, mesh) = scene_with_square();
+        scene.add_instances(mesh, &[Transform::default()]);
+
+        let up = Ray {
+            direction: Vector3::unit_z(),
+            ..down_from(-2.0)
+        };
+        let hit = raycast(&scene, &up, |_| true).unwrap();
+        assert_eq!(hit.t, 2.0);
+        assert_eq!(hit.normal, -Vector3::unit_z());
+    }
+
+    #[test]
+    fn returns_the_nearest_hit_across_instances_and_nodes() {
+        let (mut scene, mesh) = scene_with_square();
+        scene.add_instances(mesh, &[at_z(-3.0)]);
+        let near = scene.add_instances(mesh, &[at_z(-2.0), at_z(1.0), at_z(-1.0)]);
+
+        let hit = raycast(&scene, &down_from(4.0), |_| true).unwrap();
+        assert_eq!((hit.node, hit.instance, hit.t), (near, 1, 3.0));
+
+        scene.node_mut(near).visible = false;
+        let hit = raycast(&scene, &down_from(4.0), |_| true).unwrap();
+        assert_eq!(hit.t, 7.0);
+    }
+
+    #[test]
+    fn skips_hits_keep_rejects() {
+        let (mut scene, mesh) = scene_with_square();
+        let node = scene.add_instances(mesh, &[at_z(1.0), at_z(-1.0)]);
+
+        let hit = raycast(&scene, &down_from(4.0), |point| point.z < 0.0).unwrap();
+        assert_eq!((hit.node, hit.instance, hit.t), (node, 1, 5.0));
+        assert_eq!(raycast(&scene, &down_from(4.0), |_| false), None);
+    }
+}
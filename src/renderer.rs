@@ -1,119 +1,234 @@
 use egui_wgpu::{
     self,
-    wgpu::{self, util::DeviceExt, VertexBufferLayout},
+    wgpu::{self, util::DeviceExt},
     RenderState,
 };
-use std::sync::Arc;
 
-use crate::{camera::CameraResources, texture::TextureResource};
+use serde::{Deserialize, Serialize};
+use std::{
+    ops::Range,
+    sync::{Arc, RwLock},
+};
+
+use crate::{
+    annotation::{self, AnnotationSettings},
+    camera::CameraResources,
+    clipping::{ClipSettings, ClipUniform},
+    debug_draw::DebugDrawResources,
+    grid::{GridResources, GridSettings},
+    light::{self, LightResources},
+    mesh::{Mesh, VertexLayout, VertexTrait},
+    outline::{self, OutlineResources, OutlineSettings},
+    picking::{self, PickResources},
+    pipeline::{PipelineCache, PipelineKey},
+    preprocessor::{ShaderDefines, ShaderRegistry},
+    scene::{self, InstanceRaw, MaterialId, MeshId, NodeId, Scene},
+    texture::TextureResource,
+};
 
 pub trait Resource: Send + Sync + 'static {}
 
-trait VertexTrait {
-    fn desc() -> VertexBufferLayout<'static>;
+/// The textured pentagon shown until scenes can be loaded.
+#[rustfmt::skip]
+pub fn pentagon() -> Mesh {
+    Mesh {
+        positions: vec![
+            [-0.0868241, 0.49240386, 0.0], // A
+            [-0.49513406, 0.06958647, 0.0], // B
+            [-0.21918549, -0.44939706, 0.0], // C
+            [0.35966998, -0.3473291, 0.0], // D
+            [0.44147372, 0.2347359, 0.0], // E
+        ],
+        tex_coords: vec![
+            [0.4131759, 0.00759614],
+            [0.0048659444, 0.43041354],
+            [0.28081453, 0.949397],
+            [0.85967, 0.84732914],
+            [0.9414737, 0.2652641],
+        ],
+        normals: vec![[0.0, 0.0, 1.0]; 5],
+        indices: vec![
+            0, 1, 4,
+            1, 2, 4,
+            2, 3, 4,
+        ],
+        ..Default::default()
+    }
 }
 
-
-#[repr(C)]
-#[derive(Copy, Clone, Debug, bytemuck::Pod, bytemuck::Zeroable)]
-struct Vertex {
-    position: [f32; 3],
-    tex_coords: [f32; 2], // NEW!
+/// What the fragment shader outputs, used to diagnose assets and lighting.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum RenderMode {
+    Shaded,
+    LightComplexity,
+    Normals,
+    TexCoords,
+    Depth,
+    Overdraw,
+    Wireframe,
 }
 
+impl RenderMode {
+    pub const ALL: [RenderMode; 7] = [
+        RenderMode::Shaded,
+        RenderMode::LightComplexity,
+        RenderMode::Normals,
+        RenderMode::TexCoords,
+        RenderMode::Depth,
+        RenderMode::Overdraw,
+        RenderMode::Wireframe,
+    ];
 
-// lib.rs
-#[rustfmt::skip]
-const VERTICES: &[Vertex] = &[
-    // Changed
-    Vertex { position: [-0.0868241, 0.49240386, 0.0], tex_coords: [0.4131759, 0.00759614], }, // A
-    Vertex { position: [-0.49513406, 0.06958647, 0.0], tex_coords: [0.0048659444, 0.43041354], }, // B
-    Vertex { position: [-0.21918549, -0.44939706, 0.0], tex_coords: [0.28081453, 0.949397], }, // C
-    Vertex { position: [0.35966998, -0.3473291, 0.0], tex_coords: [0.85967, 0.84732914], }, // D
-    Vertex { position: [0.44147372, 0.2347359, 0.0], tex_coords: [0.9414737, 0.2652641], }, // E
-];
+    pub fn label(self) -> &'static str {
+        match self {
+            RenderMode::Shaded => "Shaded",
+            RenderMode::LightComplexity => "Light complexity",
+            RenderMode::Normals => "Normals",
+            RenderMode::TexCoords => "Texture coordinates",
+            RenderMode::Depth => "Linear depth",
+            RenderMode::Overdraw => "Overdraw",
+            RenderMode::Wireframe => "Wireframe",
+        }
+    }
 
+    /// The defines that select this mode's permutation of `forward.wgsl`.
+    fn shader_defines(self) -> ShaderDefines {
+        let debug_view = match self {
+            RenderMode::Shaded => return light::shader_defines(),
+            RenderMode::LightComplexity => "LIGHT_COMPLEXITY",
+            RenderMode::Normals => "NORMALS",
+            RenderMode::TexCoords => "TEX_COORDS",
+            RenderMode::Depth => "DEPTH",
+            RenderMode::Overdraw => "OVERDRAW",
+            RenderMode::Wireframe => "WIREFRAME",
+        };
+        light::shader_defines().flag("DEBUG_VIEW").flag(debug_view)
+    }
 
+    /// The pipeline this mode draws meshes with `vertex_layout` with.
+    fn pipeline_key(self, vertex_layout: &VertexLayout) -> PipelineKey {
+        let defines = self
+            .shader_defines()
+            .extend(&vertex_layout.shader_defines());
+        let key = match self {
+            // Back faces stay visible so hidden edges show through.
+            RenderMode::Wireframe => PipelineKey::new(
+                "forward",
+                vec![
+                    vertex_layout.clone(),
+                    barycentric_layout(),
+                    InstanceRaw::layout(),
+                ],
+            )
+            .cull_mode(None),
+            _ => PipelineKey::new(
+                "forward",
+                vec![vertex_layout.clone(), InstanceRaw::layout()],
+            ),
+        };
+        let key = key.defines(defines).blend(self.blend());
+        match self {
+            // Every layer counts, including the hidden ones.
+            RenderMode::Overdraw | RenderMode::Wireframe => {
+                key.depth(wgpu::CompareFunction::Always, false)
+            }
+            _ => key,
+        }
+    }
 
-#[rustfmt::skip]
-const INDICES: &[u16] =&[ 
-    0, 1, 4,
-    1, 2, 4, 
-    2, 3, 4
-];
-
-impl VertexTrait for Vertex {
-     fn desc() -> wgpu::VertexBufferLayout<'static> {
-        use std::mem;
-        wgpu::VertexBufferLayout {
-            array_stride: mem::size_of::<Vertex>() as wgpu::BufferAddress,
-            step_mode: wgpu::VertexStepMode::Vertex,
-            attributes: &[
-                wgpu::VertexAttribute {
-                    offset: 0,
-                    shader_location: 0,
-                    format: wgpu::VertexFormat::Float32x3,
-                },
-                wgpu::VertexAttribute {
-                    offset: mem::size_of::<[f32; 3]>() as wgpu::BufferAddress,
-                    shader_location: 1,
-                    format: wgpu::VertexFormat::Float32x2, // NEW!
+    fn blend(self) -> Option<wgpu::BlendState> {
+        match self {
+            RenderMode::Overdraw => Some(wgpu::BlendState {
+                color: wgpu::BlendComponent {
+                    src_factor: wgpu::BlendFactor::One,
+                    dst_factor: wgpu::BlendFactor::One,
+                    operation: wgpu::BlendOperation::Add,
                 },
-            ]
-        }
-    }
-}
-
-pub fn create_render_pipeline(
-    wgpu_render_state: &RenderState,
-    bind_group_layouts: &[&wgpu::BindGroupLayout],
-) -> wgpu::RenderPipeline {
-    let device = &wgpu_render_state.device;
-
-    let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
-        label: Some("custom3d"),
-        source: wgpu::ShaderSource::Wgsl(include_str!("./shader.wgsl").into()),
-    });
-
-    let pipeline_layout = device.create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
-        label: Some("custom3d"),
-        bind_group_layouts,
-        push_constant_ranges: &[],
-    });
-
-    device.create_render_pipeline(&wgpu::RenderPipelineDescriptor {
-        label: Some("custom3d"),
-        layout: Some(&pipeline_layout),
-        vertex: wgpu::VertexState {
-            module: &shader,
-            entry_point: "vs_main",
-            buffers: &[Vertex::desc()],
-        },
-        fragment: Some(wgpu::FragmentState {
-            module: &shader,
-            entry_point: "fs_main",
-            targets: &[Some(wgpu_render_state.target_format.into())],
-        }),
-        primitive: wgpu::PrimitiveState {
-            topology: wgpu::PrimitiveTopology::TriangleList, // 1.
-            strip_index_format: None,
-            front_face: wgpu::FrontFace::Ccw, // 2.
-            cull_mode: Some(wgpu::Face::Back),
-            // Setting this to anything other than Fill requires Features::NON_FILL_POLYGON_MODE
-            polygon_mode: wgpu::PolygonMode::Fill,
-            // Requires Features::DEPTH_CLIP_CONTROL
-            unclipped_depth: false,
-            // Requires Features::CONSERVATIVE_RASTERIZATION
-            conservative: false,
-        },
-        depth_stencil: None,
-        multisample: wgpu::MultisampleState {
-            count: 1,
-            mask: !0,
-            alpha_to_coverage_enabled: false,
-        },
-        multiview: None,
-    })
+                alpha: wgpu::BlendComponent::OVER,
+            }),
+            RenderMode::Wireframe => Some(wgpu::BlendState::ALPHA_BLENDING),
+            _ => None,
+        }
+    }
+}
+
+/// What a pass over the scene writes, which picks the pipelines it draws with.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum ScenePass {
+    /// The colors shown in the viewport.
+    Color(RenderMode),
+    /// Instance ids, see [`picking`].
+    Picking,
+}
+
+impl ScenePass {
+    fn pipeline_key(self, vertex_layout: &VertexLayout) -> PipelineKey {
+        match self {
+            ScenePass::Color(mode) => mode.pipeline_key(vertex_layout),
+            ScenePass::Picking => picking::pipeline_key(vertex_layout),
+        }
+    }
+
+    fn wireframe(self) -> bool {
+        self == ScenePass::Color(RenderMode::Wireframe)
+    }
+}
+
+/// Settings that change how the scene is drawn, chosen in the UI every frame.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct RenderSettings {
+    pub mode: RenderMode,
+    /// View distance that maps to white in [`RenderMode::Depth`].
+    pub max_depth: f32,
+    pub outline: OutlineSettings,
+    pub grid: GridSettings,
+    pub annotations: AnnotationSettings,
+    pub clipping: ClipSettings,
+}
+
+impl Default for RenderSettings {
+    fn default() -> Self {
+        Self {
+            mode: RenderMode::Shaded,
+            max_depth: 10.0,
+            outline: OutlineSettings::default(),
+            grid: GridSettings::default(),
+            annotations: AnnotationSettings::default(),
+            clipping: ClipSettings::default(),
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct RenderSettingsUniform {
+    // max depth in x
+    depth: [f32; 4],
+    clipping: ClipUniform,
+}
+
+impl RenderSettingsUniform {
+    pub fn new(settings: &RenderSettings) -> Self {
+        Self {
+            depth: [settings.max_depth, 0.0, 0.0, 0.0],
+            clipping: ClipUniform::new(&settings.clipping),
+        }
+    }
+}
+
+/// Barycentric coordinates of a triangle's corners, used to draw wireframes
+/// without `Features::NON_FILL_POLYGON_MODE`.
+const BARYCENTRICS: [[f32; 3]; 3] = [[1.0, 0.0, 0.0], [0.0, 1.0, 0.0], [0.0, 0.0, 1.0]];
+
+/// Bound after the last [`VertexAttribute`](crate::mesh::VertexAttribute).
+const BARYCENTRIC_LOCATION: wgpu::ShaderLocation = 6;
+
+fn barycentric_layout() -> VertexLayout {
+    VertexLayout::custom(
+        wgpu::VertexStepMode::Vertex,
+        &[(BARYCENTRIC_LOCATION, wgpu::VertexFormat::Float32x3)],
+    )
 }
 
 pub(crate) struct Renderer {
@@ -121,20 +236,73 @@ pub(crate) struct Renderer {
 }
 
 impl Renderer {
-    pub fn new(wgpu_render_state: &RenderState, pipeline: &Arc<wgpu::RenderPipeline>) -> Self {
-        // Because the graphics pipeline must have the same lifetime as the egui render pass,
-        // instead of storing the pipeline in our `Custom3D` struct, we insert it into the
-        // `paint_callback_resources` type map, which is stored alongside the render pass.
-        wgpu_render_state
-            .renderer
-            .write()
-            .callback_resources
-            .insert(PipelineResources {
-                pipeline: Arc::clone(&pipeline),
-            });
-        Self {
+    pub fn new(
+        wgpu_render_state: &RenderState,
+        bind_group_layouts: &[&wgpu::BindGroupLayout],
+        shaders: ShaderRegistry,
+    ) -> Self {
+        let renderer = Self {
             render_state: wgpu_render_state.clone(),
+        };
+
+        // Because the graphics pipelines must have the same lifetime as the egui render pass,
+        // instead of storing them in our `Custom3D` struct, we insert them into the
+        // `paint_callback_resources` type map, which is stored alongside the render pass.
+        renderer.add_resource(PipelineCache::new(
+            wgpu_render_state,
+            bind_group_layouts,
+            shaders,
+        ));
+        renderer
+    }
+
+    /// Rebuilds every pipeline from `shaders`, both those in the
+    /// [`PipelineCache`] and those the other resources build themselves.
+    ///
+    /// On error the pipelines currently in use are left untouched.
+    #[cfg(all(debug_assertions, feature = "hot_reload", not(target_arch = "wasm32")))]
+    pub fn reload_shaders(&self, shaders: ShaderRegistry) -> Result<(), String> {
+        use crate::{annotation::AnnotationResources, hot_reload::ReloadPipelines};
+
+        fn build<R: ReloadPipelines>(
+            resources: &egui_wgpu::CallbackResources,
+            device: &wgpu::Device,
+            shaders: &ShaderRegistry,
+        ) -> Result<Option<R::Pipelines>, String> {
+            let Some(resource) = resources.get::<R>() else {
+                return Ok(None);
+            };
+            let label = std::any::type_name::<R>();
+            crate::hot_reload::checked(device, label, || resource.build_pipelines(device, shaders))
+                .map(Some)
+        }
+
+        fn set<R: ReloadPipelines>(
+            resources: &mut egui_wgpu::CallbackResources,
+            pipelines: Option<R::Pipelines>,
+        ) {
+            if let (Some(resource), Some(pipelines)) = (resources.get_mut::<R>(), pipelines) {
+                resource.set_pipelines(pipelines);
+            }
         }
+
+        let device = &self.render_state.device;
+        let mut renderer = self.render_state.renderer.write();
+        let resources = &mut renderer.callback_resources;
+        let lights = build::<LightResources>(resources, device, &shaders)?;
+        let outline = build::<OutlineResources>(resources, device, &shaders)?;
+        let debug_draw = build::<DebugDrawResources>(resources, device, &shaders)?;
+        let grid = build::<GridResources>(resources, device, &shaders)?;
+        let annotations = build::<AnnotationResources>(resources, device, &shaders)?;
+
+        let pipeline_cache: &mut PipelineCache = resources.get_mut().unwrap();
+        pipeline_cache.reload(device, shaders)?;
+        set::<LightResources>(resources, lights);
+        set::<OutlineResources>(resources, outline);
+        set::<DebugDrawResources>(resources, debug_draw);
+        set::<GridResources>(resources, grid);
+        set::<AnnotationResources>(resources, annotations);
+        Ok(())
     }
 
     pub fn add_resource(&self, resource: impl Resource) {
@@ -146,25 +314,114 @@ impl Renderer {
     }
 }
 
-pub struct CustomTriangleCallback {}
+pub struct CustomTriangleCallback {
+    /// Where the callback is painted, in points.
+    pub rect: egui::Rect,
+    pub settings: RenderSettings,
+    /// The nodes to outline.
+    pub selected: Vec<NodeId>,
+}
+
+impl CustomTriangleCallback {
+    /// The viewport egui sets for this callback, in physical pixels.
+    fn viewport_in_pixels(&self, screen_descriptor: &egui_wgpu::ScreenDescriptor) -> egui::Rect {
+        let viewport = egui::PaintCallbackInfo {
+            viewport: self.rect,
+            clip_rect: self.rect,
+            pixels_per_point: screen_descriptor.pixels_per_point,
+            screen_size_px: screen_descriptor.size_in_pixels,
+        }
+        .viewport_in_pixels();
+        egui::Rect::from_min_size(
+            egui::pos2(viewport.left_px as f32, viewport.top_px as f32),
+            egui::vec2(viewport.width_px as f32, viewport.height_px as f32),
+        )
+    }
+}
 
 impl egui_wgpu::CallbackTrait for CustomTriangleCallback {
     fn prepare(
         &self,
         device: &wgpu::Device,
         queue: &wgpu::Queue,
-        _screen_descriptor: &egui_wgpu::ScreenDescriptor,
-        _egui_encoder: &mut wgpu::CommandEncoder,
+        screen_descriptor: &egui_wgpu::ScreenDescriptor,
+        egui_encoder: &mut wgpu::CommandEncoder,
         resources: &mut egui_wgpu::CallbackResources,
     ) -> Vec<wgpu::CommandBuffer> {
+        let viewport = self.viewport_in_pixels(screen_descriptor);
+        let pick = {
+            let pick_resources: &mut PickResources = resources.get_mut().unwrap();
+            pick_resources.read_back(device);
+            pick_resources.begin(device, viewport.size())
+        };
+        let mut passes = vec![ScenePass::Color(self.settings.mode)];
+        // The outline mask and the depth annotations are tested against are
+        // drawn with the picking pipelines too.
+        if pick.is_some() || !self.selected.is_empty() || self.settings.annotations.visible {
+            passes.push(ScenePass::Picking);
+        }
+        {
+            let scene_resources: &mut SceneResources = resources.get_mut().unwrap();
+            scene_resources.prepare(
+                device,
+                queue,
+                &self.settings.clipping,
+                self.settings.mode == RenderMode::Wireframe,
+            );
+            let keys: Vec<_> = scene_resources
+                .vertex_layouts()
+                .flat_map(|layout| {
+                    passes
+                        .iter()
+                        .map(|&pass| scene_resources.pipeline_key(pass, layout))
+                })
+                .collect();
+            let pipeline_cache: &mut PipelineCache = resources.get_mut().unwrap();
+            for key in keys {
+                if let Err(error) = pipeline_cache.get_or_create(device, &key) {
+                    log::error!("Failed to create pipeline {key:?}: {error}");
+                }
+            }
+        }
         {
             let camera_render_resources: &mut CameraResources = resources.get_mut().unwrap();
-            camera_render_resources.prepare(device, queue);
+            camera_render_resources.prepare(device, queue, &self.settings);
+        }
+        {
+            let grid_resources: &mut GridResources = resources.get_mut().unwrap();
+            grid_resources.prepare(queue, &self.settings.grid);
+        }
+        {
+            let light_resources: &mut LightResources = resources.get_mut().unwrap();
+            light_resources.prepare(queue, egui_encoder, viewport);
         }
         {
             let texture_render_resource: &mut TextureResource = resources.get_mut().unwrap();
             texture_render_resource.prepare(device, queue);
         }
+        {
+            let debug_draw_resources: &mut DebugDrawResources = resources.get_mut().unwrap();
+            debug_draw_resources.prepare(device, queue);
+        }
+        if let Some(pixel) = pick {
+            picking::render(device, queue, pixel, resources);
+        }
+        outline::render(
+            device,
+            queue,
+            egui_encoder,
+            viewport,
+            &self.settings.outline,
+            &self.selected,
+            resources,
+        );
+        annotation::render(
+            device,
+            queue,
+            viewport,
+            &self.settings.annotations,
+            resources,
+        );
         Vec::new()
     }
 
@@ -174,63 +431,502 @@ impl egui_wgpu::CallbackTrait for CustomTriangleCallback {
         render_pass: &mut wgpu::RenderPass<'a>,
         resources: &'a egui_wgpu::CallbackResources,
     ) {
-        let triangle_render_resources: &RenderResources = resources.get().unwrap();
-        let pipeline_resources: &PipelineResources = resources.get().unwrap();
+        let scene_resources: &SceneResources = resources.get().unwrap();
+        let pipeline_cache: &PipelineCache = resources.get().unwrap();
         let camera_render_resources: &CameraResources = resources.get().unwrap();
         let texture_render_resource: &TextureResource = resources.get().unwrap();
+        let light_resources: &LightResources = resources.get().unwrap();
+        let outline_resources: &OutlineResources = resources.get().unwrap();
+        let debug_draw_resources: &DebugDrawResources = resources.get().unwrap();
+        let grid_resources: &GridResources = resources.get().unwrap();
 
-        pipeline_resources.paint(render_pass);
         camera_render_resources.paint(render_pass);
-        texture_render_resource.paint(render_pass);
-        triangle_render_resources.paint(render_pass);
-        render_pass.draw_indexed(0..INDICES.len() as u32 as u32, 0, 0..1);
+        light_resources.paint(render_pass);
+        scene_resources.paint(
+            render_pass,
+            ScenePass::Color(self.settings.mode),
+            pipeline_cache,
+            texture_render_resource,
+        );
+        grid_resources.paint(render_pass);
+        debug_draw_resources.paint(render_pass, camera_render_resources);
+        outline_resources.paint(render_pass);
     }
 }
 
-struct PipelineResources {
-    pub pipeline: Arc<wgpu::RenderPipeline>,
-}
-
-impl Resource for PipelineResources {}
-
-impl PipelineResources {
-    fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
-        render_pass.set_pipeline(&self.pipeline);
-    }
-}
-pub struct RenderResources {
-    pub pipeline: Arc<wgpu::RenderPipeline>,
-    pub vertex_buffer: wgpu::Buffer,
-    pub index_buffer: wgpu::Buffer,
+/// A part of a mesh that fits within the device's buffer size limit.
+struct MeshPart {
+    vertex_buffer: wgpu::Buffer,
+    index_buffer: wgpu::Buffer,
+    index_format: wgpu::IndexFormat,
+    index_count: u32,
 }
 
-impl Resource for RenderResources {}
-
-impl RenderResources {
-    pub fn new(wgpu_render_state: &RenderState, pipeline: &Arc<wgpu::RenderPipeline>) -> Self {
-        let device = &wgpu_render_state.device;
-
+impl MeshPart {
+    fn new(device: &wgpu::Device, mesh: &Mesh) -> Self {
         let vertex_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Vertex Buffer"),
-            contents: bytemuck::cast_slice(VERTICES),
+            contents: bytemuck::cast_slice(&mesh.vertex_data()),
             usage: wgpu::BufferUsages::VERTEX,
         });
 
+        let (indices, index_format) = mesh.index_data();
         let index_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
             label: Some("Index Buffer"),
-            contents: bytemuck::cast_slice(INDICES),
+            contents: &indices,
             usage: wgpu::BufferUsages::INDEX,
         });
 
         Self {
-            pipeline: Arc::clone(pipeline),
             vertex_buffer,
             index_buffer,
+            index_format,
+            index_count: mesh.indices.len() as u32,
         }
     }
+}
 
-    fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
-        render_pass.set_vertex_buffer(0, self.vertex_buffer.slice(..));
-        render_pass.set_index_buffer(self.index_buffer.slice(..), wgpu::IndexFormat::Uint16);
+/// Non-indexed copies of a mesh's triangles, one triangle after the other, so
+/// that every corner can have its own barycentric coordinates.
+struct Wireframe {
+    /// Vertex buffers of as many triangles as fit in one, with their vertex
+    /// counts.
+    chunks: Vec<(wgpu::Buffer, u32)>,
+    /// Shared by every chunk, sized for the largest one.
+    barycentric_buffer: wgpu::Buffer,
+}
+
+impl Wireframe {
+    fn new(device: &wgpu::Device, mesh: &Mesh) -> Self {
+        let stride = mesh.layout().desc().array_stride;
+        let max_triangles = (device.limits().max_buffer_size / (3 * stride)).max(1) as usize;
+        let triangle_count = mesh.indices.len() / 3;
+        let chunks: Vec<_> = (0..triangle_count)
+            .step_by(max_triangles)
+            .map(|start| {
+                let triangles = start..(start + max_triangles).min(triangle_count);
+                let buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("Wireframe Vertex Buffer"),
+                    contents: bytemuck::cast_slice(
+                        &mesh.unindexed(triangles.clone()).vertex_data(),
+                    ),
+                    usage: wgpu::BufferUsages::VERTEX,
+                });
+                (buffer, 3 * triangles.len() as u32)
+            })
+            .collect();
+
+        let max_vertex_count = chunks.iter().map(|(_, count)| *count).max().unwrap_or(0);
+        let barycentrics: Vec<[f32; 3]> = BARYCENTRICS
+            .iter()
+            .copied()
+            .cycle()
+            .take(max_vertex_count as usize)
+            .collect();
+        let barycentric_buffer = device.create_buffer_init(&wgpu::util::BufferInitDescriptor {
+            label: Some("Barycentric Buffer"),
+            contents: bytemuck::cast_slice(&barycentrics),
+            usage: wgpu::BufferUsages::VERTEX,
+        });
+
+        Self {
+            chunks,
+            barycentric_buffer,
+        }
+    }
+}
+
+/// A mesh uploaded to the GPU.
+struct GpuMesh {
+    vertex_layout: VertexLayout,
+    parts: Vec<MeshPart>,
+    /// Three times the size of the indexed mesh, so only built once the mesh
+    /// is first drawn as a wireframe.
+    wireframe: Option<Wireframe>,
+}
+
+impl GpuMesh {
+    fn new(device: &wgpu::Device, mesh: &Mesh) -> Self {
+        let vertex_layout = mesh.layout();
+
+        // Indices may need 32 bits, even where the part ends up using 16.
+        let max_buffer_size = device.limits().max_buffer_size;
+        let max_vertices = (max_buffer_size / vertex_layout.desc().array_stride) as usize;
+        let max_triangles = (max_buffer_size / (3 * std::mem::size_of::<u32>() as u64)) as usize;
+        let parts: Vec<MeshPart> =
+            if mesh.vertex_count() <= max_vertices && mesh.indices.len() / 3 <= max_triangles {
+                vec![MeshPart::new(device, mesh)]
+            } else {
+                let parts = mesh.split(max_vertices, max_triangles);
+                log::info!(
+                    "Split a mesh of {} vertices into {} parts",
+                    mesh.vertex_count(),
+                    parts.len()
+                );
+                parts
+                    .iter()
+                    .map(|part| MeshPart::new(device, part))
+                    .collect()
+            };
+
+        Self {
+            vertex_layout,
+            parts,
+            wireframe: None,
+        }
+    }
+
+    fn paint<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        wireframe: bool,
+        instance_buffer: &'rp wgpu::Buffer,
+        instances: Range<u32>,
+    ) {
+        if wireframe {
+            let Some(wireframe) = &self.wireframe else {
+                return;
+            };
+            for (buffer, vertex_count) in &wireframe.chunks {
+                render_pass.set_vertex_buffer(0, buffer.slice(..));
+                render_pass.set_vertex_buffer(1, wireframe.barycentric_buffer.slice(..));
+                render_pass.set_vertex_buffer(2, instance_buffer.slice(..));
+                render_pass.draw(0..*vertex_count, instances.clone());
+            }
+            return;
+        }
+        for part in self.parts.iter().filter(|part| part.index_count > 0) {
+            render_pass.set_vertex_buffer(0, part.vertex_buffer.slice(..));
+            render_pass.set_vertex_buffer(1, instance_buffer.slice(..));
+            render_pass.set_index_buffer(part.index_buffer.slice(..), part.index_format);
+            render_pass.draw_indexed(0..part.index_count, 0, instances.clone());
+        }
+    }
+}
+
+/// The world transform of one scene node, read by `forward.wgsl`.
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+struct ObjectUniform {
+    model: [[f32; 4]; 4],
+    /// A `mat3x3` in the shader, with every column padded to 16 bytes.
+    normal: [[f32; 4]; 3],
+    /// The material's base color.
+    color: [f32; 4],
+}
+
+impl ObjectUniform {
+    fn new(world: cgmath::Matrix4<f32>, color: [f32; 4]) -> Self {
+        let normal = scene::normal_matrix(world);
+        Self {
+            model: world.into(),
+            normal: [normal.x, normal.y, normal.z].map(|column| column.extend(0.0).into()),
+            color,
+        }
+    }
+}
+
+/// One drawn node's uniforms and instances.
+struct Draw {
+    node: NodeId,
+    mesh: MeshId,
+    material: Option<MaterialId>,
+    instances: Range<u32>,
+    /// Offset of the node's [`ObjectUniform`] in the object buffer.
+    object_offset: wgpu::DynamicOffset,
+}
+
+/// The GPU copy of a [`Scene`], updated whenever the scene changes.
+pub struct SceneResources {
+    scene: Arc<RwLock<Scene>>,
+    /// The [`Scene::revision`] that was last uploaded.
+    revision: Option<u64>,
+    /// The [`Scene::generation`] the meshes and textures belong to.
+    generation: u64,
+    meshes: Vec<GpuMesh>,
+    texture_bind_group_layout: wgpu::BindGroupLayout,
+    /// The texture of every material that has one.
+    material_textures: Vec<Option<wgpu::BindGroup>>,
+    /// Every drawn node's instances, with room to spare so that edits to the
+    /// scene only have to write into it.
+    instance_buffer: wgpu::Buffer,
+    /// Bind group 3, with one [`ObjectUniform`] per drawn node.
+    object_bind_group_layout: wgpu::BindGroupLayout,
+    object_buffer: wgpu::Buffer,
+    object_bind_group: wgpu::BindGroup,
+    /// Distance between consecutive uniforms in the object buffer.
+    object_stride: wgpu::BufferAddress,
+    draws: Vec<Draw>,
+    /// What is cut away, which changes the pipelines every pass draws with.
+    clipping: ClipSettings,
+}
+
+impl Resource for SceneResources {}
+
+impl SceneResources {
+    pub fn new(device: &wgpu::Device, scene: Arc<RwLock<Scene>>) -> Self {
+        let object_bind_group_layout =
+            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+                label: Some("object_bind_group_layout"),
+                entries: &[wgpu::BindGroupLayoutEntry {
+                    binding: 0,
+                    visibility: wgpu::ShaderStages::VERTEX,
+                    ty: wgpu::BindingType::Buffer {
+                        ty: wgpu::BufferBindingType::Uniform,
+                        has_dynamic_offset: true,
+                        min_binding_size: wgpu::BufferSize::new(
+                            std::mem::size_of::<ObjectUniform>() as u64,
+                        ),
+                    },
+                    count: None,
+                }],
+            });
+
+        let alignment = device.limits().min_uniform_buffer_offset_alignment as u64;
+        let size = std::mem::size_of::<ObjectUniform>() as u64;
+        let object_stride = (size + alignment - 1) / alignment * alignment;
+        let (object_buffer, object_bind_group) =
+            Self::create_object_buffer(device, &object_bind_group_layout, object_stride, 1);
+
+        Self {
+            scene,
+            revision: None,
+            generation: 0,
+            meshes: Vec::new(),
+            texture_bind_group_layout: TextureResource::bind_group_layout(device),
+            material_textures: Vec::new(),
+            instance_buffer: Self::create_instance_buffer(device, 64),
+            object_bind_group_layout,
+            object_buffer,
+            object_bind_group,
+            object_stride,
+            draws: Vec::new(),
+            clipping: ClipSettings::default(),
+        }
+    }
+
+    /// The layout of bind group 3, holding the drawn node's world transform.
+    pub fn object_bind_group_layout(&self) -> &wgpu::BindGroupLayout {
+        &self.object_bind_group_layout
+    }
+
+    fn create_instance_buffer(device: &wgpu::Device, capacity: usize) -> wgpu::Buffer {
+        device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Instance Buffer"),
+            size: (capacity * std::mem::size_of::<InstanceRaw>()) as u64,
+            usage: wgpu::BufferUsages::VERTEX | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        })
+    }
+
+    fn create_object_buffer(
+        device: &wgpu::Device,
+        layout: &wgpu::BindGroupLayout,
+        stride: wgpu::BufferAddress,
+        capacity: usize,
+    ) -> (wgpu::Buffer, wgpu::BindGroup) {
+        let buffer = device.create_buffer(&wgpu::BufferDescriptor {
+            label: Some("Object Buffer"),
+            size: stride * capacity as u64,
+            usage: wgpu::BufferUsages::UNIFORM | wgpu::BufferUsages::COPY_DST,
+            mapped_at_creation: false,
+        });
+        let bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("object_bind_group"),
+            layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::Buffer(wgpu::BufferBinding {
+                    buffer: &buffer,
+                    offset: 0,
+                    size: wgpu::BufferSize::new(std::mem::size_of::<ObjectUniform>() as u64),
+                }),
+            }],
+        });
+        (buffer, bind_group)
+    }
+
+    /// Uploads the scene if it changed, and the wireframe copies of the drawn
+    /// meshes that don't have one yet if `wireframe` is set.
+    fn prepare(
+        &mut self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        clipping: &ClipSettings,
+        wireframe: bool,
+    ) {
+        self.clipping = *clipping;
+        let scene = Arc::clone(&self.scene);
+        let scene = scene.read().unwrap();
+        self.upload(device, queue, &scene);
+        if wireframe {
+            for draw in &self.draws {
+                let mesh = &mut self.meshes[draw.mesh.index()];
+                if mesh.wireframe.is_none() {
+                    mesh.wireframe =
+                        Some(Wireframe::new(device, &scene.meshes()[draw.mesh.index()]));
+                }
+            }
+        }
+    }
+
+    fn upload(&mut self, device: &wgpu::Device, queue: &wgpu::Queue, scene: &Scene) {
+        if self.revision == Some(scene.revision()) {
+            return;
+        }
+        self.revision = Some(scene.revision());
+        if self.generation != scene.generation() {
+            self.generation = scene.generation();
+            self.meshes.clear();
+            self.material_textures.clear();
+        }
+
+        // Meshes and materials are only ever added, so only the new ones need uploading.
+        for mesh in &scene.meshes()[self.meshes.len()..] {
+            self.meshes.push(GpuMesh::new(device, mesh));
+        }
+        for material in &scene.materials()[self.material_textures.len()..] {
+            let texture = material.texture.as_ref().map(|texture| {
+                texture.create_bind_group(device, queue, &self.texture_bind_group_layout)
+            });
+            self.material_textures.push(texture);
+        }
+
+        let instances = scene.instance_data();
+        let size = std::mem::size_of_val(instances.as_slice()) as u64;
+        if size > self.instance_buffer.size() {
+            self.instance_buffer =
+                Self::create_instance_buffer(device, instances.len().next_power_of_two());
+        }
+        if !instances.is_empty() {
+            queue.write_buffer(&self.instance_buffer, 0, bytemuck::cast_slice(&instances));
+        }
+
+        let world_matrices = scene.world_matrices();
+        let mut objects = Vec::new();
+        let mut start = 0;
+        self.draws = scene
+            .drawn_nodes()
+            .filter_map(|(id, node)| Some((id, node, node.mesh?)))
+            .enumerate()
+            .map(|(index, (id, node, mesh))| {
+                let color = node.material.map_or(scene::white(), |material| {
+                    scene.materials()[material.index()].base_color
+                });
+                let uniform = ObjectUniform::new(world_matrices[id.index()], color);
+                objects.extend_from_slice(bytemuck::bytes_of(&uniform));
+                objects.resize((index + 1) * self.object_stride as usize, 0);
+
+                let end = start + node.instances.len() as u32;
+                let draw = Draw {
+                    node: id,
+                    mesh,
+                    material: node.material,
+                    instances: start..end,
+                    object_offset: (index as u64 * self.object_stride) as wgpu::DynamicOffset,
+                };
+                start = end;
+                draw
+            })
+            .collect();
+
+        if objects.len() as u64 > self.object_buffer.size() {
+            let capacity = self.draws.len().next_power_of_two();
+            (self.object_buffer, self.object_bind_group) = Self::create_object_buffer(
+                device,
+                &self.object_bind_group_layout,
+                self.object_stride,
+                capacity,
+            );
+        }
+        if !objects.is_empty() {
+            queue.write_buffer(&self.object_buffer, 0, &objects);
+        }
+    }
+
+    /// Which node each range of the instance buffer belongs to.
+    pub(crate) fn instance_owners(&self) -> Vec<(Range<u32>, NodeId)> {
+        self.draws
+            .iter()
+            .map(|draw| (draw.instances.clone(), draw.node))
+            .collect()
+    }
+
+    /// The pipeline `pass` draws meshes with `vertex_layout` with.
+    fn pipeline_key(&self, pass: ScenePass, vertex_layout: &VertexLayout) -> PipelineKey {
+        self.clipping.pipeline_key(pass.pipeline_key(vertex_layout))
+    }
+
+    /// The vertex layouts of every mesh that is drawn.
+    fn vertex_layouts(&self) -> impl Iterator<Item = &VertexLayout> {
+        self.draws
+            .iter()
+            .map(|draw| &self.meshes[draw.mesh.index()].vertex_layout)
+    }
+
+    pub(crate) fn paint<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        pass: ScenePass,
+        pipeline_cache: &'rp PipelineCache,
+        default_texture: &'rp TextureResource,
+    ) {
+        self.paint_draws(
+            render_pass,
+            pass,
+            pipeline_cache,
+            default_texture,
+            self.draws.iter(),
+        );
+    }
+
+    /// Like [`SceneResources::paint`], but only draws the given nodes.
+    pub(crate) fn paint_nodes<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        pass: ScenePass,
+        pipeline_cache: &'rp PipelineCache,
+        default_texture: &'rp TextureResource,
+        nodes: &[NodeId],
+    ) {
+        self.paint_draws(
+            render_pass,
+            pass,
+            pipeline_cache,
+            default_texture,
+            self.draws.iter().filter(|draw| nodes.contains(&draw.node)),
+        );
+    }
+
+    fn paint_draws<'rp>(
+        &'rp self,
+        render_pass: &mut wgpu::RenderPass<'rp>,
+        pass: ScenePass,
+        pipeline_cache: &'rp PipelineCache,
+        default_texture: &'rp TextureResource,
+        draws: impl Iterator<Item = &'rp Draw>,
+    ) {
+        for draw in draws {
+            let mesh = &self.meshes[draw.mesh.index()];
+            let Some(pipeline) = pipeline_cache.get(&self.pipeline_key(pass, &mesh.vertex_layout))
+            else {
+                continue;
+            };
+            render_pass.set_pipeline(pipeline);
+            match draw
+                .material
+                .and_then(|material| self.material_textures[material.index()].as_ref())
+            {
+                Some(texture) => render_pass.set_bind_group(1, texture, &[]),
+                None => default_texture.paint(render_pass),
+            }
+            render_pass.set_bind_group(3, &self.object_bind_group, &[draw.object_offset]);
+            mesh.paint(
+                render_pass,
+                pass.wireframe(),
+                &self.instance_buffer,
+                draw.instances.clone(),
+            );
+        }
     }
 }
@@ -0,0 +1,551 @@
+use cgmath::{Matrix, SquareMatrix};
+use egui_wgpu::wgpu;
+use serde::{Deserialize, Serialize};
+use std::sync::Arc;
+
+use crate::{
+    bounds::{Aabb, MeshBounds},
+    mesh::Mesh,
+    mesh::VertexLayout,
+    scene_file::MeshSource,
+    texture::TextureAsset,
+    transform::Transform,
+};
+
+/// Refers to a mesh added with [`Scene::add_mesh`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MeshId(usize);
+
+impl MeshId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// One copy of a mesh.
+#[derive(Clone, Copy, Debug, PartialEq, Serialize, Deserialize)]
+pub struct Instance {
+    pub transform: Transform,
+    /// Multiplies the mesh's own colors.
+    #[serde(default = "white")]
+    pub color: [f32; 4],
+}
+
+pub(crate) fn white() -> [f32; 4] {
+    [1.0; 4]
+}
+
+impl From<Transform> for Instance {
+    fn from(transform: Transform) -> Self {
+        Self {
+            transform,
+            color: white(),
+        }
+    }
+}
+
+impl Instance {
+    fn to_raw(self) -> InstanceRaw {
+        let model = self.transform.matrix();
+        InstanceRaw {
+            model: model.into(),
+            normal: normal_matrix(model).into(),
+            color: self.color,
+        }
+    }
+}
+
+/// The inverse transpose of `model`, which keeps normals perpendicular to
+/// surfaces under non-uniform scale.
+pub fn normal_matrix(model: cgmath::Matrix4<f32>) -> cgmath::Matrix3<f32> {
+    let normal = model
+        .invert()
+        .map(|inverse| inverse.transpose())
+        .unwrap_or(model);
+    cgmath::Matrix3::from_cols(
+        normal.x.truncate(),
+        normal.y.truncate(),
+        normal.z.truncate(),
+    )
+}
+
+#[repr(C)]
+#[derive(Debug, Copy, Clone, bytemuck::Pod, bytemuck::Zeroable)]
+pub struct InstanceRaw {
+    model: [[f32; 4]; 4],
+    normal: [[f32; 3]; 3],
+    color: [f32; 4],
+}
+
+impl InstanceRaw {
+    /// Bound after the mesh attributes and the wireframe barycentrics.
+    pub fn layout() -> VertexLayout {
+        use wgpu::VertexFormat::{Float32x3, Float32x4};
+        VertexLayout::custom(
+            wgpu::VertexStepMode::Instance,
+            &[
+                (7, Float32x4),
+                (8, Float32x4),
+                (9, Float32x4),
+                (10, Float32x4),
+                (11, Float32x3),
+                (12, Float32x3),
+                (13, Float32x3),
+                (14, Float32x4),
+            ],
+        )
+    }
+}
+
+/// Refers to a material added with [`Scene::add_material`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct MaterialId(usize);
+
+impl MaterialId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// How the surface of a mesh looks.
+#[derive(Clone, Debug)]
+pub struct Material {
+    pub name: String,
+    /// Multiplies the texture and the vertex and instance colors.
+    pub base_color: [f32; 4],
+    /// Drawn instead of the default texture.
+    pub texture: Option<Arc<TextureAsset>>,
+}
+
+/// Refers to a node added with [`Scene::add_node`].
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub struct NodeId(usize);
+
+impl NodeId {
+    pub fn index(self) -> usize {
+        self.0
+    }
+}
+
+/// Where a node sits in the scene tree, so it can be put back there after it
+/// was moved or removed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TreePosition {
+    /// `None` for root nodes.
+    pub parent: Option<NodeId>,
+    /// Among the parent's children, or among the roots.
+    pub index: usize,
+}
+
+/// An element of the scene tree, placed relative to its parent.
+#[derive(Clone, Debug)]
+pub struct Node {
+    pub name: String,
+    /// Relative to the parent, or to the world for root nodes.
+    pub transform: Transform,
+    /// Drawn once per instance, with the instances placed relative to the node.
+    pub mesh: Option<MeshId>,
+    /// Applies to the mesh, or to none with the default texture and white.
+    pub material: Option<MaterialId>,
+    pub instances: Vec<Instance>,
+    /// Hidden nodes are not drawn, and neither are their descendants.
+    pub visible: bool,
+    parent: Option<NodeId>,
+    children: Vec<NodeId>,
+}
+
+impl Node {
+    fn new(name: String) -> Self {
+        Self {
+            name,
+            transform: Transform::default(),
+            mesh: None,
+            material: None,
+            instances: Vec::new(),
+            visible: true,
+            parent: None,
+            children: Vec::new(),
+        }
+    }
+
+    pub fn children(&self) -> &[NodeId] {
+        &self.children
+    }
+}
+
+/// The meshes to draw and the tree of nodes placing them, shared between the
+/// app and the GPU resources.
+#[derive(Default)]
+pub struct Scene {
+    meshes: Vec<Mesh>,
+    /// The bounds of each mesh, or `None` for meshes without vertices.
+    mesh_bounds: Vec<Option<MeshBounds>>,
+    /// Where each mesh came from, for saving.
+    mesh_sources: Vec<MeshSource>,
+    materials: Vec<Material>,
+    nodes: Vec<Node>,
+    roots: Vec<NodeId>,
+    /// Incremented on every change, so the GPU copy knows when to update.
+    revision: u64,
+    /// Incremented by [`Scene::replace`], so the GPU copy knows to drop the
+    /// meshes and textures of the previous scene.
+    generation: u64,
+}
+
+impl Scene {
+    /// Adds `mesh`, saved to scene files as `source`. Meshes built in code
+    /// have no other source than [`MeshSource::Inline`].
+    pub fn add_mesh(&mut self, mesh: Mesh, source: MeshSource) -> MeshId {
+        self.mesh_bounds.push(MeshBounds::new(&mesh.positions));
+        self.meshes.push(mesh);
+        self.mesh_sources.push(source);
+        self.revision += 1;
+        MeshId(self.meshes.len() - 1)
+    }
+
+    pub fn add_material(&mut self, material: Material) -> MaterialId {
+        self.materials.push(material);
+        self.revision += 1;
+        MaterialId(self.materials.len() - 1)
+    }
+
+    /// Swaps in `scene`, such as one loaded from a file, as a change to this scene.
+    pub fn replace(&mut self, scene: Scene) {
+        *self = Scene {
+            revision: self.revision + 1,
+            generation: self.generation + 1,
+            ..scene
+        };
+    }
+
+    /// Adds an empty node under `parent`, or at the root.
+    pub fn add_node(&mut self, name: impl Into<String>, parent: Option<NodeId>) -> NodeId {
+        let id = NodeId(self.nodes.len());
+        self.nodes.push(Node::new(name.into()));
+        self.attach(id, parent);
+        self.revision += 1;
+        id
+    }
+
+    /// Adds a root node that draws `mesh` once for every transform.
+    pub fn add_instances(&mut self, mesh: MeshId, transforms: &[Transform]) -> NodeId {
+        let instances: Vec<Instance> = transforms.iter().copied().map(Instance::from).collect();
+        self.add_colored_instances(mesh, &instances)
+    }
+
+    /// Adds a root node that draws `mesh` once for every instance, tinted with
+    /// the instance's color.
+    pub fn add_colored_instances(&mut self, mesh: MeshId, instances: &[Instance]) -> NodeId {
+        let id = self.add_node(format!("Mesh {}", mesh.index()), None);
+        let node = self.node_mut(id);
+        node.mesh = Some(mesh);
+        node.instances = instances.to_vec();
+        id
+    }
+
+    /// Moves `node` under `parent`, or to the root, keeping its local transform.
+    ///
+    /// Fails if `parent` is the node itself or one of its descendants.
+    pub fn set_parent(&mut self, node: NodeId, parent: Option<NodeId>) -> Result<(), String> {
+        if let Some(parent) = parent {
+            if parent == node || self.ancestors(parent).any(|ancestor| ancestor == node) {
+                return Err(format!(
+                    "\"{}\" cannot be moved under its own descendant \"{}\"",
+                    self.nodes[node.0].name, self.nodes[parent.0].name
+                ));
+            }
+        }
+        if self.nodes[node.0].parent == parent {
+            return Ok(());
+        }
+        self.detach(node);
+        self.attach(node, parent);
+        self.revision += 1;
+        Ok(())
+    }
+
+    /// Where `node` is in the tree.
+    pub fn position(&self, node: NodeId) -> TreePosition {
+        let parent = self.nodes[node.0].parent;
+        let siblings = match parent {
+            Some(parent) => &self.nodes[parent.0].children,
+            None => &self.roots,
+        };
+        TreePosition {
+            parent,
+            index: siblings
+                .iter()
+                .position(|&sibling| sibling == node)
+                .unwrap_or(siblings.len()),
+        }
+    }
+
+    /// Takes `node` and its descendants out of the tree.
+    ///
+    /// They keep their ids, and can be put back with [`Scene::insert`].
+    pub fn remove(&mut self, node: NodeId) {
+        self.detach(node);
+        self.revision += 1;
+    }
+
+    /// Puts `node` back at `position`, such as one taken from
+    /// [`Scene::position`] before it was moved or removed.
+    pub fn insert(&mut self, node: NodeId, position: TreePosition) {
+        self.detach(node);
+        self.nodes[node.0].parent = position.parent;
+        let siblings = match position.parent {
+            Some(parent) => &mut self.nodes[parent.0].children,
+            None => &mut self.roots,
+        };
+        siblings.insert(position.index.min(siblings.len()), node);
+        self.revision += 1;
+    }
+
+    /// Whether `node` is in the tree, rather than removed or left over from a
+    /// scene swapped out by [`Scene::replace`] that had more nodes.
+    pub fn contains(&self, node: NodeId) -> bool {
+        if node.0 >= self.nodes.len() {
+            return false;
+        }
+        let root = self.ancestors(node).last().unwrap_or(node);
+        self.roots.contains(&root)
+    }
+
+    /// The parent of `node`, its grandparent and so on up to the root.
+    pub fn ancestors(&self, node: NodeId) -> impl Iterator<Item = NodeId> + '_ {
+        std::iter::successors(self.nodes[node.0].parent, |&id| self.nodes[id.0].parent)
+    }
+
+    fn detach(&mut self, node: NodeId) {
+        match self.nodes[node.0].parent.take() {
+            Some(parent) => self.nodes[parent.0].children.retain(|&child| child != node),
+            None => self.roots.retain(|&root| root != node),
+        }
+    }
+
+    fn attach(&mut self, node: NodeId, parent: Option<NodeId>) {
+        self.nodes[node.0].parent = parent;
+        match parent {
+            Some(parent) => self.nodes[parent.0].children.push(node),
+            None => self.roots.push(node),
+        }
+    }
+
+    pub fn node(&self, id: NodeId) -> &Node {
+        &self.nodes[id.0]
+    }
+
+    /// Counts as a change to the scene, even if the node is left as it was.
+    pub fn node_mut(&mut self, id: NodeId) -> &mut Node {
+        self.revision += 1;
+        &mut self.nodes[id.0]
+    }
+
+    pub fn material_ids(&self) -> impl Iterator<Item = MaterialId> {
+        (0..self.materials.len()).map(MaterialId)
+    }
+
+    pub fn material(&self, id: MaterialId) -> &Material {
+        &self.materials[id.0]
+    }
+
+    /// Counts as a change to the scene, like [`Scene::node_mut`].
+    pub fn material_mut(&mut self, id: MaterialId) -> &mut Material {
+        self.revision += 1;
+        &mut self.materials[id.0]
+    }
+
+    pub fn roots(&self) -> &[NodeId] {
+        &self.roots
+    }
+
+    pub fn meshes(&self) -> &[Mesh] {
+        &self.meshes
+    }
+
+    pub fn mesh_bounds(&self, id: MeshId) -> Option<&MeshBounds> {
+        self.mesh_bounds[id.0].as_ref()
+    }
+
+    pub fn mesh_sources(&self) -> &[MeshSource] {
+        &self.mesh_sources
+    }
+
+    /// The first mesh that came from `source`, to share instead of adding it
+    /// again.
+    pub fn find_mesh(&self, source: &MeshSource) -> Option<MeshId> {
+        self.mesh_sources
+            .iter()
+            .position(|candidate| candidate == source)
+            .map(MeshId)
+    }
+
+    pub fn materials(&self) -> &[Material] {
+        &self.materials
+    }
+
+    /// Counts the instances of every node in the tree, hidden or not.
+    pub fn instance_count(&self) -> usize {
+        let mut stack = self.roots.clone();
+        let mut count = 0;
+        while let Some(id) = stack.pop() {
+            let node = &self.nodes[id.0];
+            count += node.instances.len();
+            stack.extend(&node.children);
+        }
+        count
+    }
+
+    pub fn revision(&self) -> u64 {
+        self.revision
+    }
+
+    pub fn generation(&self) -> u64 {
+        self.generation
+    }
+
+    /// Every node's world matrix, indexed by [`NodeId::index`].
+    ///
+    /// Computed top-down from the roots, so each parent's matrix is reused by
+    /// all of its children.
+    pub fn world_matrices(&self) -> Vec<cgmath::Matrix4<f32>> {
+        let mut world = vec![cgmath::Matrix4::identity(); self.nodes.len()];
+        let mut stack: Vec<(NodeId, cgmath::Matrix4<f32>)> = self
+            .roots
+            .iter()
+            .map(|&root| (root, cgmath::Matrix4::identity()))
+            .collect();
+        while let Some((node, parent_world)) = stack.pop() {
+            let node_world = parent_world * self.nodes[node.0].transform.matrix();
+            world[node.0] = node_world;
+            stack.extend(
+                self.nodes[node.0]
+                    .children
+                    .iter()
+                    .map(|&child| (child, node_world)),
+            );
+        }
+        world
+    }
+
+    /// The visible nodes that draw a mesh, in the order their instances are
+    /// packed by [`Scene::instance_data`].
+    ///
+    /// Hidden nodes are skipped along with their whole subtree.
+    pub fn drawn_nodes(&self) -> impl Iterator<Item = (NodeId, &Node)> + '_ {
+        let mut stack: Vec<NodeId> = self.roots.iter().rev().copied().collect();
+        std::iter::from_fn(move || {
+            while let Some(id) = stack.pop() {
+                let node = &self.nodes[id.0];
+                if !node.visible {
+                    continue;
+                }
+                stack.extend(node.children.iter().rev());
+                if node.mesh.is_some() && !node.instances.is_empty() {
+                    return Some((id, node));
+                }
+            }
+            None
+        })
+    }
+
+    /// The world space box around every drawn instance, or `None` if nothing
+    /// is drawn.
+    pub fn bounds(&self) -> Option<Aabb> {
+        let world_matrices = self.world_matrices();
+        let mut corners = Vec::new();
+        for (id, node) in self.drawn_nodes() {
+            let Some(bounds) = node.mesh.and_then(|mesh| self.mesh_bounds(mesh)) else {
+                continue;
+            };
+            for instance in &node.instances {
+                let model = world_matrices[id.index()] * instance.transform.matrix();
+                corners.extend(bounds.aabb.transformed(&model).corners());
+            }
+        }
+        Aabb::from_points(corners)
+    }
+
+    /// Every drawn node's instances, packed one node after the other.
+    pub fn instance_data(&self) -> Vec<InstanceRaw> {
+        self.drawn_nodes()
+            .flat_map(|(_, node)| node.instances.iter().map(|instance| instance.to_raw()))
+            .collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn removed_ids_are_not_reused() {
+        let mut scene = Scene::default();
+        let a = scene.add_node("a", None);
+        let child = scene.add_node("child", Some(a));
+        let position = scene.position(a);
+        scene.remove(a);
+        assert!(!scene.contains(a));
+        assert!(!scene.contains(child));
+
+        let b = scene.add_node("b", None);
+        assert_ne!(b, a);
+        assert_ne!(b, child);
+        assert_eq!(scene.node(a).name, "a");
+
+        scene.insert(a, position);
+        assert_eq!(scene.roots(), [a, b]);
+        assert!(scene.contains(child));
+    }
+
+    #[test]
+    fn replace_starts_a_new_generation() {
+        let mut scene = Scene::default();
+        let a = scene.add_node("a", None);
+        let b = scene.add_node("b", Some(a));
+        let (revision, generation) = (scene.revision(), scene.generation());
+
+        let mut smaller = Scene::default();
+        let c = smaller.add_node("c", None);
+        scene.replace(smaller);
+        assert!(scene.revision() > revision);
+        assert_eq!(scene.generation(), generation + 1);
+        // Ids restart with the new scene, so old ones may name its nodes or
+        // none at all.
+        assert_eq!(c, a);
+        assert!(!scene.contains(b));
+    }
+
+    #[test]
+    fn refuses_to_parent_under_a_descendant() {
+        let mut scene = Scene::default();
+        let a = scene.add_node("a", None);
+        let b = scene.add_node("b", Some(a));
+        let c = scene.add_node("c", Some(b));
+        let revision = scene.revision();
+        assert!(scene.set_parent(a, Some(c)).is_err());
+        assert!(scene.set_parent(a, Some(a)).is_err());
+        assert_eq!(scene.revision(), revision);
+
+        scene.set_parent(c, None).unwrap();
+        assert_eq!(scene.roots(), [a, c]);
+        assert_eq!(scene.ancestors(c).count(), 0);
+    }
+
+    #[test]
+    fn hidden_nodes_hide_their_subtree() {
+        let mut scene = Scene::default();
+        let mesh = scene.add_mesh(Mesh::default(), MeshSource::Pentagon);
+        let shown = scene.add_instances(mesh, &[Transform::default()]);
+        let hidden = scene.add_instances(mesh, &[Transform::default(); 2]);
+        let under_hidden = scene.add_instances(mesh, &[Transform::default()]);
+        scene.set_parent(under_hidden, Some(hidden)).unwrap();
+        scene.node_mut(hidden).visible = false;
+
+        let drawn: Vec<_> = scene.drawn_nodes().map(|(id, _)| id).collect();
+        assert_eq!(drawn, [shown]);
+        assert_eq!(scene.instance_data().len(), 1);
+        // Hidden instances still count.
+        assert_eq!(scene.instance_count(), 4);
+    }
+}
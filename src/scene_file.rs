@@ -0,0 +1,335 @@
+use anyhow::Context;
+use serde::{Deserialize, Serialize};
+use std::{
+    path::{Path, PathBuf},
+    sync::Arc,
+};
+
+use crate::{
+    camera::Camera,
+    light::{Light, Lights},
+    mesh::Mesh,
+    primitive::Primitive,
+    renderer,
+    scene::{self, Instance, Material, MaterialId, MeshId, NodeId, Scene},
+    texture::TextureAsset,
+    transform::Transform,
+};
+
+/// Where a mesh comes from, so that scene files can refer to it.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub enum MeshSource {
+    /// The built-in pentagon from `renderer::pentagon`.
+    Pentagon,
+    /// A Wavefront OBJ file.
+    Obj(PathBuf),
+    /// Generated from the parameters.
+    Primitive(Primitive),
+    /// Built in code, with no file or generator to rebuild it from, so the
+    /// scene file carries the mesh itself.
+    Inline(Mesh),
+}
+
+impl MeshSource {
+    /// Builds or loads the mesh, resolving paths relative to `base_dir`.
+    fn load(&self, base_dir: &Path) -> anyhow::Result<Mesh> {
+        match self {
+            MeshSource::Pentagon => Ok(renderer::pentagon()),
+            MeshSource::Obj(path) => {
+                let source = std::fs::read_to_string(base_dir.join(path))
+                    .with_context(|| format!("Failed to read mesh {}", path.display()))?;
+                crate::obj::parse(&source)
+                    .map_err(|error| anyhow::anyhow!("{}: {error}", path.display()))
+            }
+            MeshSource::Primitive(primitive) => Ok(primitive.mesh()),
+            MeshSource::Inline(mesh) => Ok(mesh.clone()),
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct MaterialDescription {
+    pub name: String,
+    #[serde(default = "scene::white")]
+    pub base_color: [f32; 4],
+    /// Drawn instead of the default texture.
+    #[serde(default)]
+    pub texture: Option<PathBuf>,
+}
+
+#[derive(Serialize, Deserialize)]
+pub struct NodeDescription {
+    pub name: String,
+    #[serde(default)]
+    pub transform: Transform,
+    /// Index into [`SceneFile::meshes`].
+    #[serde(default)]
+    pub mesh: Option<usize>,
+    /// Index into [`SceneFile::materials`].
+    #[serde(default)]
+    pub material: Option<usize>,
+    /// Where the mesh is drawn, relative to the node. Left out, the mesh is
+    /// drawn once, at the node itself.
+    #[serde(default = "single_instance")]
+    pub instances: Vec<Instance>,
+    #[serde(default = "visible")]
+    pub visible: bool,
+    #[serde(default)]
+    pub children: Vec<NodeDescription>,
+}
+
+fn visible() -> bool {
+    true
+}
+
+fn single_instance() -> Vec<Instance> {
+    vec![Transform::default().into()]
+}
+
+/// Everything needed to recreate a viewer setup, stored as RON.
+///
+/// Meshes and textures are referred to by path, relative to the scene file.
+#[derive(Default, Serialize, Deserialize)]
+#[serde(default)]
+pub struct SceneFile {
+    pub meshes: Vec<MeshSource>,
+    pub materials: Vec<MaterialDescription>,
+    /// The root nodes, each holding its children.
+    pub nodes: Vec<NodeDescription>,
+    pub lights: Vec<Light>,
+    /// The first camera is the one the scene is viewed from.
+    pub cameras: Vec<Camera>,
+}
+
+impl SceneFile {
+    pub fn new(scene: &Scene, lights: &Lights, camera: &Camera) -> Self {
+        fn describe(scene: &Scene, id: NodeId) -> NodeDescription {
+            let node = scene.node(id);
+            NodeDescription {
+                name: node.name.clone(),
+                transform: node.transform,
+                mesh: node.mesh.map(MeshId::index),
+                material: node.material.map(MaterialId::index),
+                instances: node.instances.clone(),
+                visible: node.visible,
+                children: node
+                    .children()
+                    .iter()
+                    .map(|&child| describe(scene, child))
+                    .collect(),
+            }
+        }
+
+        Self {
+            meshes: scene.mesh_sources().to_vec(),
+            materials: scene
+                .materials()
+                .iter()
+                .map(|material| MaterialDescription {
+                    name: material.name.clone(),
+                    base_color: material.base_color,
+                    texture: material
+                        .texture
+                        .as_ref()
+                        .map(|texture| texture.path.clone()),
+                })
+                .collect(),
+            nodes: scene
+                .roots()
+                .iter()
+                .map(|&root| describe(scene, root))
+                .collect(),
+            lights: lights.iter().cloned().collect(),
+            cameras: vec![camera.clone()],
+        }
+    }
+
+    pub fn open(path: &Path) -> anyhow::Result<Self> {
+        let text = std::fs::read_to_string(path)
+            .with_context(|| format!("Failed to read {}", path.display()))?;
+        ron::from_str(&text).with_context(|| format!("Failed to parse {}", path.display()))
+    }
+
+    pub fn save(&self, path: &Path) -> anyhow::Result<()> {
+        let text = ron::ser::to_string_pretty(self, ron::ser::PrettyConfig::default())?;
+        std::fs::write(path, text).with_context(|| format!("Failed to write {}", path.display()))
+    }
+
+    /// Loads the assets, with paths relative to `base_dir`, and builds the
+    /// scene, its lights and the camera to view it from.
+    pub fn load(self, base_dir: &Path) -> anyhow::Result<(Scene, Lights, Option<Camera>)> {
+        let mut scene = Scene::default();
+        let meshes = self
+            .meshes
+            .into_iter()
+            .map(|source| Ok(scene.add_mesh(source.load(base_dir)?, source)))
+            .collect::<anyhow::Result<Vec<_>>>()?;
+        let materials = self
+            .materials
+            .into_iter()
+            .map(|material| {
+                let texture = match &material.texture {
+                    Some(path) => Some(Arc::new(TextureAsset::load(base_dir, path)?)),
+                    None => None,
+                };
+                Ok(scene.add_material(Material {
+                    name: material.name,
+                    base_color: material.base_color,
+                    texture,
+                }))
+            })
+            .collect::<anyhow::Result<Vec<_>>>()?;
+
+        let mut pending: Vec<(NodeDescription, Option<NodeId>)> = self
+            .nodes
+            .into_iter()
+            .rev()
+            .map(|node| (node, None))
+            .collect();
+        while let Some((description, parent)) = pending.pop() {
+            let id = scene.add_node(description.name, parent);
+            let node = scene.node_mut(id);
+            node.transform = description.transform;
+            node.instances = description.instances;
+            node.visible = description.visible;
+            node.mesh = description
+                .mesh
+                .map(|index| {
+                    meshes.get(index).copied().with_context(|| {
+                        format!("Node \"{}\" uses missing mesh {index}", node.name)
+                    })
+                })
+                .transpose()?;
+            node.material = description
+                .material
+                .map(|index| {
+                    materials.get(index).copied().with_context(|| {
+                        format!("Node \"{}\" uses missing material {index}", node.name)
+                    })
+                })
+                .transpose()?;
+            pending.extend(
+                description
+                    .children
+                    .into_iter()
+                    .rev()
+                    .map(|child| (child, Some(id))),
+            );
+        }
+
+        let mut lights = Lights::default();
+        for light in self.lights {
+            lights.add(light);
+        }
+        Ok((scene, lights, self.cameras.into_iter().next()))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::light::PointLight;
+
+    fn to_ron(file: &SceneFile) -> String {
+        ron::ser::to_string_pretty(file, ron::ser::PrettyConfig::default()).unwrap()
+    }
+
+    fn parse(text: &str) -> anyhow::Result<Scene> {
+        let file: SceneFile = ron::from_str(text)?;
+        Ok(file.load(Path::new(""))?.0)
+    }
+
+    #[test]
+    fn round_trips_through_ron() {
+        let mut scene = Scene::default();
+        let pentagon = scene.add_mesh(
+            MeshSource::Pentagon.load(Path::new("")).unwrap(),
+            MeshSource::Pentagon,
+        );
+        let primitive = Primitive::DEFAULTS[0];
+        let cube = scene.add_mesh(primitive.mesh(), MeshSource::Primitive(primitive));
+        let material = scene.add_material(Material {
+            name: "Red".to_owned(),
+            base_color: [1.0, 0.0, 0.0, 1.0],
+            texture: None,
+        });
+        let parent = scene.add_node("Parent", None);
+        scene.node_mut(parent).transform.translation = cgmath::Vector3::new(1.0, 2.0, 3.0);
+        let child = scene.add_instances(pentagon, &[Transform::default(); 2]);
+        scene.set_parent(child, Some(parent)).unwrap();
+        let hidden = scene.add_colored_instances(
+            cube,
+            &[Instance {
+                transform: Transform::default(),
+                color: [0.5; 4],
+            }],
+        );
+        let node = scene.node_mut(hidden);
+        node.visible = false;
+        node.material = Some(material);
+
+        let mut lights = Lights::default();
+        lights.add(Light::Point(PointLight {
+            position: cgmath::Point3::new(0.0, 1.0, 0.0),
+            color: [1.0, 0.5, 0.25],
+            intensity: 2.0,
+            range: 4.0,
+        }));
+        let mut camera = Camera::new();
+        camera.fovy = 60.0;
+
+        let text = to_ron(&SceneFile::new(&scene, &lights, &camera));
+        let file: SceneFile = ron::from_str(&text).unwrap();
+        let (loaded, loaded_lights, loaded_camera) = file.load(Path::new("")).unwrap();
+        assert_eq!(loaded.roots().len(), 2);
+        assert_eq!(loaded.instance_count(), 3);
+        assert_eq!(loaded_lights.len(), 1);
+        assert_eq!(loaded_camera.unwrap().fovy, 60.0);
+
+        let reloaded = SceneFile::new(&loaded, &loaded_lights, &camera);
+        assert_eq!(to_ron(&reloaded), text);
+    }
+
+    #[test]
+    fn saves_meshes_built_in_code() {
+        let mesh = Mesh {
+            positions: vec![[0.0, 0.0, 0.0], [1.0, 0.0, 0.0], [0.0, 1.0, 0.0]],
+            colors: vec![[1.0, 0.0, 0.0, 1.0]; 3],
+            indices: vec![0, 1, 2],
+            ..Default::default()
+        };
+        let mut scene = Scene::default();
+        let id = scene.add_mesh(mesh.clone(), MeshSource::Inline(mesh.clone()));
+        scene.add_instances(id, &[Transform::default()]);
+
+        let text = to_ron(&SceneFile::new(&scene, &Lights::default(), &Camera::new()));
+        let loaded = parse(&text).unwrap();
+        assert_eq!(loaded.meshes(), [mesh]);
+    }
+
+    #[test]
+    fn left_out_instances_draw_the_mesh_once() {
+        let scene = parse(
+            "(meshes: [Pentagon], nodes: [
+                (name: \"Once\", mesh: Some(0)),
+                (name: \"Never\", mesh: Some(0), instances: []),
+            ])",
+        )
+        .unwrap();
+        let instances: Vec<_> = scene
+            .roots()
+            .iter()
+            .map(|&id| scene.node(id).instances.len())
+            .collect();
+        assert_eq!(instances, [1, 0]);
+        assert_eq!(scene.drawn_nodes().count(), 1);
+    }
+
+    #[test]
+    fn rejects_missing_meshes() {
+        let Err(error) = parse("(nodes: [(name: \"Node\", mesh: Some(1))])") else {
+            panic!("loaded a node with a missing mesh");
+        };
+        assert!(error.to_string().contains("missing mesh 1"), "{error}");
+    }
+}
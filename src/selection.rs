@@ -0,0 +1,79 @@
+use crate::scene::NodeId;
+
+/// Something picked in the outliner or viewport, to be edited in the inspector.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Hash)]
+pub enum Selection {
+    Node(NodeId),
+    /// Index into [`Lights`](crate::light::Lights).
+    Light(usize),
+    Camera,
+    /// Index into [`ClipSettings::planes`](crate::clipping::ClipSettings::planes).
+    ClipPlane(usize),
+    SectionBox,
+}
+
+/// Everything selected, in the order it was clicked.
+///
+/// The last item is the active one, which the inspector shows.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct SelectionSet(Vec<Selection>);
+
+impl SelectionSet {
+    pub fn active(&self) -> Option<Selection> {
+        self.0.last().copied()
+    }
+
+    pub fn contains(&self, item: Selection) -> bool {
+        self.0.contains(&item)
+    }
+
+    pub fn len(&self) -> usize {
+        self.0.len()
+    }
+
+    /// The selected nodes, leaving out everything else.
+    pub fn nodes(&self) -> impl Iterator<Item = NodeId> + '_ {
+        self.0.iter().filter_map(|item| match item {
+            Selection::Node(node) => Some(*node),
+            _ => None,
+        })
+    }
+
+    /// The indices of the selected lights, leaving out everything else.
+    pub fn lights(&self) -> impl Iterator<Item = usize> + '_ {
+        self.0.iter().filter_map(|item| match item {
+            Selection::Light(index) => Some(*index),
+            _ => None,
+        })
+    }
+
+    pub fn clear(&mut self) {
+        self.0.clear();
+    }
+
+    /// Keeps only the items `keep` returns true for, such as after some of
+    /// the selected nodes were deleted.
+    pub fn retain(&mut self, mut keep: impl FnMut(Selection) -> bool) {
+        self.0.retain(|&item| keep(item));
+    }
+
+    /// Updates the selection for a click on `item`, or on nothing.
+    ///
+    /// Holding shift or ctrl adds the item, or removes it if it was already
+    /// selected. A plain click selects only the item.
+    pub fn click(&mut self, item: Option<Selection>, modifiers: egui::Modifiers) {
+        if modifiers.shift || modifiers.command {
+            if let Some(item) = item {
+                match self.0.iter().position(|selected| *selected == item) {
+                    Some(index) => {
+                        self.0.remove(index);
+                    }
+                    None => self.0.push(item),
+                }
+            }
+        } else {
+            self.0.clear();
+            self.0.extend(item);
+        }
+    }
+}
@@ -0,0 +1,66 @@
+// Tests which annotation anchors are hidden behind the scene.
+//
+// Each anchor is drawn as a single point into a row of results, in the column
+// of its index, and compares its own depth with the scene's depth at the
+// pixel of the viewport it falls on.
+
+struct OcclusionParams {
+    view_proj: mat4x4<f32>,
+    // The camera position in xyz.
+    eye: vec4<f32>,
+    // The viewport size in pixels.
+    size: vec2<f32>,
+    // The width of the results row.
+    columns: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: OcclusionParams;
+@group(0) @binding(1)
+var scene_depth: texture_2d<f32>;
+
+// Anchors are moved this fraction of the way to the camera before testing,
+// so ones placed on a surface aren't hidden by it.
+const TOLERANCE: f32 = 0.01;
+
+const VISIBLE: u32 = 1u;
+const HIDDEN: u32 = 2u;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) @interpolate(flat) anchor: vec4<f32>,
+};
+
+@vertex
+fn vs_main(
+    @builtin(instance_index) index: u32,
+    @location(0) position: vec3<f32>,
+) -> VertexOutput {
+    var out: VertexOutput;
+    let column = (f32(index) + 0.5) / params.columns * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(column, 0.0, 0.0, 1.0);
+    let nearer = mix(position, params.eye.xyz, TOLERANCE);
+    out.anchor = params.view_proj * vec4<f32>(nearer, 1.0);
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) u32 {
+    // Anchors off screen are left to the caller.
+    if in.anchor.w <= 0.0 {
+        return VISIBLE;
+    }
+    let ndc = in.anchor.xyz / in.anchor.w;
+    if any(abs(ndc.xy) > vec2<f32>(1.0)) || ndc.z < 0.0 || ndc.z > 1.0 {
+        return VISIBLE;
+    }
+    let size = vec2<i32>(params.size);
+    let pixel = clamp(
+        vec2<i32>((ndc.xy * vec2<f32>(0.5, -0.5) + 0.5) * params.size),
+        vec2<i32>(0),
+        size - 1,
+    );
+    let depth = textureLoad(scene_depth, pixel, 0).x;
+    return select(HIDDEN, VISIBLE, ndc.z <= depth);
+}
@@ -0,0 +1,34 @@
+// Cutting the scene open with the planes and section box of `clipping.rs`,
+// and filling in the cuts.
+#include "common"
+
+// Whether `position` is in front of an enabled plane or outside the enabled
+// section box.
+fn clipped(position: vec3<f32>) -> bool {
+    // Indexed in the uniform itself, as copies of arrays can only be indexed
+    // by constants.
+    for (var i = 0u; i < settings.clipping.counts.x; i++) {
+        let plane = settings.clipping.planes[i];
+        if dot(plane.xyz, position) + plane.w > 0.0 {
+            return true;
+        }
+    }
+    let clipping = settings.clipping;
+    if clipping.counts.y != 0u {
+        return any(position < clipping.section_min.xyz) || any(position > clipping.section_max.xyz);
+    }
+    return false;
+}
+
+// Distance between the hatch's stripes, and their width, in pixels.
+const HATCH_SPACING: f32 = 8.0;
+const HATCH_WIDTH: f32 = 2.0;
+
+// The fill of a cut, drawn over the back faces seen through it: a darker
+// `color` with diagonal stripes fixed to the screen, so cuts stand out from
+// the surfaces around them.
+fn section_cap(pixel: vec2<f32>, color: vec4<f32>) -> vec4<f32> {
+    let stripe = fract((pixel.x + pixel.y) / HATCH_SPACING) * HATCH_SPACING;
+    let line = 1.0 - smoothstep(HATCH_WIDTH - 0.5, HATCH_WIDTH + 0.5, stripe);
+    return vec4<f32>(mix(color.rgb * 0.55, color.rgb * 0.2, line), 1.0);
+}
@@ -0,0 +1,118 @@
+// Bins lights into a grid of view-space clusters.
+//
+// The view frustum is split into `grid.x` by `grid.y` screen tiles and
+// `grid.z` exponentially distributed depth slices. Every invocation builds
+// the view-space AABB of one cluster and records the indices of the lights
+// whose volume intersects it.
+
+#include "lights"
+
+@group(0) @binding(0)
+var<uniform> params: ClusterParams;
+@group(0) @binding(1)
+var<storage, read> lights: array<Light>;
+@group(0) @binding(2)
+var<storage, read_write> cluster_light_counts: array<u32>;
+@group(0) @binding(3)
+var<storage, read_write> cluster_light_indices: array<u32>;
+
+// Unprojects a point given in normalized device coordinates onto the near plane.
+fn ndc_to_view(ndc: vec2<f32>) -> vec3<f32> {
+    let view = params.inverse_projection * vec4<f32>(ndc, 0.0, 1.0);
+    return view.xyz / view.w;
+}
+
+// Intersects the ray from the eye through `point` with the plane `z = -depth`.
+fn ray_at_depth(point: vec3<f32>, depth: f32) -> vec3<f32> {
+    return point * (-depth / point.z);
+}
+
+fn sphere_intersects_aabb(center: vec3<f32>, radius: f32, aabb_min: vec3<f32>, aabb_max: vec3<f32>) -> bool {
+    let closest = clamp(center, aabb_min, aabb_max);
+    let delta = closest - center;
+    return dot(delta, delta) <= radius * radius;
+}
+
+// Cone versus bounding sphere test, see
+// https://bartwronski.com/2017/04/13/cull-that-cone/
+fn cone_intersects_sphere(
+    apex: vec3<f32>,
+    direction: vec3<f32>,
+    range: f32,
+    cos_angle: f32,
+    sin_angle: f32,
+    center: vec3<f32>,
+    radius: f32,
+) -> bool {
+    let v = center - apex;
+    let v_len_sq = dot(v, v);
+    let v1_len = dot(v, direction);
+    let closest = cos_angle * sqrt(max(v_len_sq - v1_len * v1_len, 0.0)) - v1_len * sin_angle;
+    let angle_cull = closest > radius;
+    let front_cull = v1_len > radius + range;
+    let back_cull = v1_len < -radius;
+    return !(angle_cull || front_cull || back_cull);
+}
+
+@compute @workgroup_size(WORKGROUP_SIZE, WORKGROUP_SIZE, WORKGROUP_SIZE)
+fn cs_main(@builtin(global_invocation_id) id: vec3<u32>) {
+    if (any(id >= params.grid.xyz)) {
+        return;
+    }
+
+    let cluster = id.x + id.y * params.grid.x + id.z * params.grid.x * params.grid.y;
+
+    // Screen tiles run top to bottom while NDC y points up.
+    let tile = vec2<f32>(params.grid.xy);
+    let ndc_min = vec2<f32>(
+        -1.0 + 2.0 * f32(id.x) / tile.x,
+        1.0 - 2.0 * f32(id.y + 1u) / tile.y,
+    );
+    let ndc_max = vec2<f32>(
+        -1.0 + 2.0 * f32(id.x + 1u) / tile.x,
+        1.0 - 2.0 * f32(id.y) / tile.y,
+    );
+
+    let near_min = ndc_to_view(ndc_min);
+    let near_max = ndc_to_view(ndc_max);
+    let slice_near = slice_depth(params, id.z);
+    let slice_far = slice_depth(params, id.z + 1u);
+
+    let a = ray_at_depth(near_min, slice_near);
+    let b = ray_at_depth(near_min, slice_far);
+    let c = ray_at_depth(near_max, slice_near);
+    let d = ray_at_depth(near_max, slice_far);
+    let aabb_min = min(min(a, b), min(c, d));
+    let aabb_max = max(max(a, b), max(c, d));
+    let aabb_center = (aabb_min + aabb_max) * 0.5;
+    let aabb_radius = length(aabb_max - aabb_center);
+
+    var count = 0u;
+    let offset = cluster * MAX_LIGHTS_PER_CLUSTER;
+    for (var i = 0u; i < params.grid.w && count < MAX_LIGHTS_PER_CLUSTER; i += 1u) {
+        let light = lights[i];
+        let position = (params.view * vec4<f32>(light.position_range.xyz, 1.0)).xyz;
+        let range = light.position_range.w;
+
+        var visible = sphere_intersects_aabb(position, range, aabb_min, aabb_max);
+        if (visible && light.direction_kind.w == LIGHT_KIND_SPOT) {
+            let direction = normalize((params.view * vec4<f32>(light.direction_kind.xyz, 0.0)).xyz);
+            visible = cone_intersects_sphere(
+                position,
+                direction,
+                range,
+                light.cone.y,
+                light.cone.z,
+                aabb_center,
+                aabb_radius,
+            );
+        }
+
+        if (visible) {
+            cluster_light_indices[offset + count] = i;
+            count += 1u;
+        }
+    }
+
+    cluster_light_counts[cluster] = count;
+}
@@ -0,0 +1,25 @@
+// Camera and render settings, bound to group 0 of every scene pipeline.
+
+struct CameraUniform {
+    view_proj: mat4x4<f32>,
+    view: mat4x4<f32>,
+};
+@group(0) @binding(0)
+var<uniform> camera: CameraUniform;
+
+// See `ClipUniform` in `clipping.rs`.
+struct Clipping {
+    planes: array<vec4<f32>, 6>,
+    section_min: vec4<f32>,
+    section_max: vec4<f32>,
+    // Enabled planes in x, and whether the section box is in y.
+    counts: vec4<u32>,
+};
+
+struct RenderSettings {
+    // max depth in x
+    depth: vec4<f32>,
+    clipping: Clipping,
+};
+@group(0) @binding(1)
+var<uniform> settings: RenderSettings;
@@ -0,0 +1,59 @@
+// Debug views, one `debug_view` per render mode permutation.
+
+#ifdef LIGHT_COMPLEXITY
+// Number of lights in a cluster that maps to the hottest heatmap color.
+const HEATMAP_SCALE: f32 = 32.0;
+
+fn heatmap(t: f32) -> vec3<f32> {
+    let x = clamp(t, 0.0, 1.0);
+    return clamp(
+        vec3<f32>(1.5 - abs(4.0 * x - 3.0), 1.5 - abs(4.0 * x - 2.0), 1.5 - abs(4.0 * x - 1.0)),
+        vec3<f32>(0.0),
+        vec3<f32>(1.0),
+    );
+}
+
+fn debug_view(in: VertexOutput) -> vec4<f32> {
+    let count = cluster_light_counts[cluster_index(in.clip_position.xy, in.view_depth)];
+    return vec4<f32>(heatmap(f32(count) / HEATMAP_SCALE), 1.0);
+}
+#endif
+
+#ifdef NORMALS
+fn debug_view(in: VertexOutput) -> vec4<f32> {
+    return vec4<f32>(surface_normal(in) * 0.5 + 0.5, 1.0);
+}
+#endif
+
+#ifdef TEX_COORDS
+fn debug_view(in: VertexOutput) -> vec4<f32> {
+    return vec4<f32>(fract(in.tex_coords), 0.0, 1.0);
+}
+#endif
+
+#ifdef DEPTH
+fn debug_view(in: VertexOutput) -> vec4<f32> {
+    let depth = clamp(in.view_depth / settings.depth.x, 0.0, 1.0);
+    return vec4<f32>(vec3<f32>(depth), 1.0);
+}
+#endif
+
+#ifdef OVERDRAW
+// Every fragment adds a little heat, so pixels covered many times light up.
+fn debug_view(in: VertexOutput) -> vec4<f32> {
+    return vec4<f32>(0.1, 0.04, 0.01, 1.0);
+}
+#endif
+
+#ifdef WIREFRAME
+const WIREFRAME_COLOR: vec3<f32> = vec3<f32>(0.9, 0.9, 0.9);
+const WIREFRAME_WIDTH: f32 = 1.0;
+
+fn debug_view(in: VertexOutput) -> vec4<f32> {
+    // Distance to the closest edge in pixels, anti-aliased over one pixel.
+    let width = fwidth(in.barycentric);
+    let edge = smoothstep(width * (WIREFRAME_WIDTH - 0.5), width * (WIREFRAME_WIDTH + 0.5), in.barycentric);
+    let coverage = 1.0 - min(edge.x, min(edge.y, edge.z));
+    return vec4<f32>(WIREFRAME_COLOR, coverage);
+}
+#endif
@@ -0,0 +1,186 @@
+#include "common"
+#include "lighting"
+#ifdef CLIPPING
+#include "clipping"
+#endif
+
+// Optional attributes are only declared when the mesh carries them, see
+// `VertexAttribute` in `mesh.rs`.
+struct VertexInput {
+    @location(0) position: vec3<f32>,
+#ifdef VERTEX_TEX_COORDS
+    @location(1) tex_coords: vec2<f32>,
+#endif
+#ifdef VERTEX_NORMAL
+    @location(2) normal: vec3<f32>,
+#endif
+#ifdef VERTEX_TANGENT
+    @location(3) tangent: vec4<f32>,
+#endif
+#ifdef VERTEX_COLOR
+    @location(4) color: vec4<f32>,
+#endif
+#ifdef VERTEX_TEX_COORDS1
+    @location(5) tex_coords1: vec2<f32>,
+#endif
+#ifdef WIREFRAME
+    @location(6) barycentric: vec3<f32>,
+#endif
+}
+
+// One row per column of the instance's model matrix, and of its inverse transpose
+// for normals. See `InstanceRaw` in `scene.rs`.
+struct InstanceInput {
+    @location(7) model_0: vec4<f32>,
+    @location(8) model_1: vec4<f32>,
+    @location(9) model_2: vec4<f32>,
+    @location(10) model_3: vec4<f32>,
+    @location(11) normal_0: vec3<f32>,
+    @location(12) normal_1: vec3<f32>,
+    @location(13) normal_2: vec3<f32>,
+    @location(14) color: vec4<f32>,
+}
+
+// The world transform of the scene node being drawn, applied on top of the
+// instance's. See `ObjectUniform` in `renderer.rs`.
+struct Object {
+    model: mat4x4<f32>,
+    normal: mat3x3<f32>,
+    color: vec4<f32>,
+}
+
+@group(3) @binding(0)
+var<uniform> object: Object;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) tex_coords: vec2<f32>,
+    @location(1) world_position: vec3<f32>,
+    @location(2) world_normal: vec3<f32>,
+    @location(3) view_depth: f32,
+    @location(4) color: vec4<f32>,
+    @location(5) tex_coords1: vec2<f32>,
+    @location(6) world_tangent: vec4<f32>,
+#ifdef WIREFRAME
+    @location(7) barycentric: vec3<f32>,
+#endif
+#ifdef PICKING
+    @location(8) @interpolate(flat) instance: u32,
+#endif
+}
+
+@vertex
+fn vs_main(
+    model: VertexInput,
+    instance: InstanceInput,
+    @builtin(instance_index) instance_index: u32,
+) -> VertexOutput {
+    let model_matrix = object.model * mat4x4<f32>(instance.model_0, instance.model_1, instance.model_2, instance.model_3);
+    let normal_matrix = object.normal * mat3x3<f32>(instance.normal_0, instance.normal_1, instance.normal_2);
+    let world_position = model_matrix * vec4<f32>(model.position, 1.0);
+
+    var out: VertexOutput;
+    out.world_position = world_position.xyz;
+    out.view_depth = -(camera.view * world_position).z;
+    out.clip_position = camera.view_proj * world_position;
+
+    // Missing attributes fall back to values that leave shading unchanged.
+    out.tex_coords = vec2<f32>(0.0);
+    out.world_normal = vec3<f32>(0.0);
+    out.world_tangent = vec4<f32>(1.0, 0.0, 0.0, 1.0);
+    out.color = object.color * instance.color;
+#ifdef VERTEX_TEX_COORDS
+    out.tex_coords = model.tex_coords;
+#endif
+    out.tex_coords1 = out.tex_coords;
+#ifdef VERTEX_NORMAL
+    out.world_normal = normal_matrix * model.normal;
+#endif
+#ifdef VERTEX_TANGENT
+    out.world_tangent = vec4<f32>((model_matrix * vec4<f32>(model.tangent.xyz, 0.0)).xyz, model.tangent.w);
+#endif
+#ifdef VERTEX_COLOR
+    out.color *= model.color;
+#endif
+#ifdef VERTEX_TEX_COORDS1
+    out.tex_coords1 = model.tex_coords1;
+#endif
+#ifdef WIREFRAME
+    out.barycentric = model.barycentric;
+#endif
+#ifdef PICKING
+    out.instance = instance_index;
+#endif
+    return out;
+}
+
+// Meshes without normals are shaded flat, with the normal of the triangle.
+fn surface_normal(in: VertexOutput) -> vec3<f32> {
+#ifdef VERTEX_NORMAL
+    return normalize(in.world_normal);
+#else
+    return normalize(cross(dpdy(in.world_position), dpdx(in.world_position)));
+#endif
+}
+
+// Fragment shader
+
+@group(1) @binding(0)
+var t_diffuse: texture_2d<f32>;
+@group(1)@binding(1)
+var s_diffuse: sampler;
+
+#ifdef PICKING
+// Writes which instance covers each pixel, counted across the whole instance
+// buffer and offset by one so that zero means nothing. See `picking.rs`.
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) u32 {
+#ifdef CLIPPING
+    if clipped(in.world_position) {
+        discard;
+    }
+#endif
+    return in.instance + 1u;
+}
+#else
+#ifdef DEBUG_VIEW
+#include "debug"
+
+@fragment
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+#ifdef CLIPPING
+    if clipped(in.world_position) {
+        discard;
+    }
+#endif
+#ifdef CLIP_CAPS
+    if !front_facing {
+        return section_cap(in.clip_position.xy, in.color);
+    }
+#endif
+    return debug_view(in);
+}
+#else
+@fragment
+fn fs_main(in: VertexOutput, @builtin(front_facing) front_facing: bool) -> @location(0) vec4<f32> {
+#ifdef CLIPPING
+    if clipped(in.world_position) {
+        discard;
+    }
+#endif
+#ifdef CLIP_CAPS
+    // The inside of a mesh, seen through a cut.
+    if !front_facing {
+        return section_cap(in.clip_position.xy, in.color);
+    }
+#endif
+#ifdef VERTEX_TEX_COORDS
+    let albedo = textureSample(t_diffuse, s_diffuse, in.tex_coords) * in.color;
+#else
+    let albedo = in.color;
+#endif
+    let lighting = shade(in.clip_position.xy, in.view_depth, in.world_position, surface_normal(in));
+    return vec4<f32>(albedo.rgb * lighting, albedo.a);
+}
+#endif
+#endif
@@ -0,0 +1,118 @@
+// An infinite grid on the ground plane, y = 0, drawn over the whole viewport.
+//
+// Every pixel casts a ray from the camera, and where it meets the plane the
+// distance to the nearest grid line is measured in pixels using screen space
+// derivatives, which gives anti-aliased lines of constant width at any
+// distance. The spacing steps up by a factor of ten as cells shrink on
+// screen, with the finer lines fading out before they turn into noise.
+
+struct GridParams {
+    view_proj: mat4x4<f32>,
+    inverse_view_proj: mat4x4<f32>,
+    color: vec4<f32>,
+    x_axis_color: vec4<f32>,
+    z_axis_color: vec4<f32>,
+    // The size of the smallest cells, in world units.
+    unit: f32,
+    // Distance from the camera at which the grid has faded out.
+    fade_distance: f32,
+    // Nonzero to draw the axes.
+    axes: f32,
+    _padding: f32,
+};
+
+@group(0) @binding(0)
+var<uniform> params: GridParams;
+
+// Cells are kept at least this many pixels across.
+const MIN_CELL_PIXELS: f32 = 8.0;
+// Lines are this many pixels wide.
+const LINE_WIDTH: f32 = 1.0;
+
+struct VertexOutput {
+    @builtin(position) clip_position: vec4<f32>,
+    @location(0) ndc: vec2<f32>,
+};
+
+// A triangle covering the whole viewport.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> VertexOutput {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    var out: VertexOutput;
+    out.ndc = uv * 2.0 - 1.0;
+    out.clip_position = vec4<f32>(out.ndc, 0.0, 1.0);
+    return out;
+}
+
+struct FragmentOutput {
+    @location(0) color: vec4<f32>,
+    @builtin(frag_depth) depth: f32,
+};
+
+fn unproject(point: vec4<f32>) -> vec3<f32> {
+    let world = params.inverse_view_proj * point;
+    return world.xyz / world.w;
+}
+
+// How much of a pixel the lines `spacing` apart cover, for each axis.
+fn line_coverage(coord: vec2<f32>, derivative: vec2<f32>, spacing: f32) -> vec2<f32> {
+    let cell = coord / spacing;
+    let pixels = abs(fract(cell - 0.5) - 0.5) / max(derivative / spacing, vec2<f32>(1e-6));
+    return 1.0 - clamp(pixels - (LINE_WIDTH - 1.0) * 0.5, vec2<f32>(0.0), vec2<f32>(1.0));
+}
+
+fn max2(v: vec2<f32>) -> f32 {
+    return max(v.x, v.y);
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> FragmentOutput {
+    // The ray starts on the near plane and runs away from the center of
+    // projection, like `Camera::ray`.
+    let near = unproject(vec4<f32>(in.ndc, 0.0, 1.0));
+    let center = unproject(vec4<f32>(0.0, 0.0, 1.0, 0.0));
+    let direction = near - center;
+    let t = -near.y / direction.y;
+    let position = near + direction * t;
+    let clip = params.view_proj * vec4<f32>(position, 1.0);
+    let depth = clip.z / clip.w;
+    // Derivatives need every pixel of a quad, so misses are only discarded
+    // at the end.
+    let hit = t > 0.0 && clip.w > 0.0 && depth >= 0.0 && depth <= 1.0;
+
+    let coord = position.xz;
+    let derivative = fwidth(coord);
+    // Pick the power of ten spacing that keeps cells big enough, and the next
+    // one up, fading from the first to the second.
+    let lod = max(log2(max2(derivative) * MIN_CELL_PIXELS / params.unit) / log2(10.0), 0.0);
+    let spacing = params.unit * pow(10.0, floor(lod));
+    let fine = max2(line_coverage(coord, derivative, spacing)) * (1.0 - fract(lod));
+    let coarse = max2(line_coverage(coord, derivative, spacing * 10.0));
+
+    var color = vec4<f32>(params.color.rgb, params.color.a * max(fine * 0.5, coarse));
+    if params.axes != 0.0 {
+        // Lines about a pixel wide along x = 0 and z = 0.
+        let axes = 1.0 - clamp(abs(coord) / max(derivative, vec2<f32>(1e-6)) - 0.5, vec2<f32>(0.0), vec2<f32>(1.0));
+        if axes.y > 0.0 {
+            color = mix(color, params.x_axis_color, axes.y);
+        }
+        if axes.x > 0.0 {
+            color = mix(color, params.z_axis_color, axes.x);
+        }
+    }
+
+    // Fade out with distance, and where the plane is seen edge on and the
+    // lines would crowd together.
+    let distance = length(position - center);
+    let fade = 1.0 - smoothstep(0.5, 1.0, distance / params.fade_distance);
+    let grazing = smoothstep(0.0, 0.1, abs(normalize(direction).y));
+    color.a *= fade * grazing;
+    if !hit || !(color.a > 0.0) {
+        discard;
+    }
+
+    var out: FragmentOutput;
+    out.color = color;
+    out.depth = depth;
+    return out;
+}
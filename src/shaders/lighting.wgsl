@@ -0,0 +1,55 @@
+// Clustered forward lighting, bound to group 2 of the scene pipelines.
+
+#include "lights"
+
+const AMBIENT: vec3<f32> = vec3<f32>(0.05, 0.05, 0.05);
+
+@group(2) @binding(0)
+var<uniform> clusters: ClusterParams;
+@group(2) @binding(1)
+var<storage, read> lights: array<Light>;
+@group(2) @binding(2)
+var<storage, read> cluster_light_counts: array<u32>;
+@group(2) @binding(3)
+var<storage, read> cluster_light_indices: array<u32>;
+
+fn cluster_index(frag_coord: vec2<f32>, view_depth: f32) -> u32 {
+    let uv = clamp((frag_coord - clusters.viewport.xy) / clusters.viewport.zw, vec2<f32>(0.0), vec2<f32>(0.9999));
+    let tile = vec2<u32>(uv * vec2<f32>(clusters.grid.xy));
+    let slice = depth_slice(clusters, view_depth);
+    return tile.x + tile.y * clusters.grid.x + slice * clusters.grid.x * clusters.grid.y;
+}
+
+fn attenuation(distance: f32, range: f32) -> f32 {
+    let ratio = distance / range;
+    let window = clamp(1.0 - ratio * ratio * ratio * ratio, 0.0, 1.0);
+    return window * window / (distance * distance + 1.0);
+}
+
+fn shade_light(light: Light, position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let to_light = light.position_range.xyz - position;
+    let distance = length(to_light);
+    let direction = to_light / max(distance, 0.0001);
+
+    var intensity = light.color_intensity.w * attenuation(distance, light.position_range.w);
+    if (light.direction_kind.w == LIGHT_KIND_SPOT) {
+        let cos_theta = dot(-direction, normalize(light.direction_kind.xyz));
+        intensity *= smoothstep(light.cone.y, light.cone.x, cos_theta);
+    }
+
+    return light.color_intensity.rgb * intensity * max(dot(normal, direction), 0.0);
+}
+
+// Sums the contribution of every light in the fragment's cluster.
+fn shade(frag_coord: vec2<f32>, view_depth: f32, position: vec3<f32>, normal: vec3<f32>) -> vec3<f32> {
+    let cluster = cluster_index(frag_coord, view_depth);
+    let count = cluster_light_counts[cluster];
+    let offset = cluster * MAX_LIGHTS_PER_CLUSTER;
+
+    var lighting = AMBIENT;
+    for (var i = 0u; i < count; i += 1u) {
+        let light = lights[cluster_light_indices[offset + i]];
+        lighting += shade_light(light, position, normal);
+    }
+    return lighting;
+}
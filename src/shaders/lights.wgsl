@@ -0,0 +1,38 @@
+// Light and cluster data shared by the clustering pass and the forward shader.
+
+const LIGHT_KIND_SPOT: f32 = 1.0;
+
+struct ClusterParams {
+    inverse_projection: mat4x4<f32>,
+    view: mat4x4<f32>,
+    // cluster dimensions in xyz, light count in w
+    grid: vec4<u32>,
+    // origin and size of the viewport in physical pixels
+    viewport: vec4<f32>,
+    // znear, zfar
+    depth: vec4<f32>,
+};
+
+struct Light {
+    position_range: vec4<f32>,
+    color_intensity: vec4<f32>,
+    // w holds the light kind
+    direction_kind: vec4<f32>,
+    // cos(inner), cos(outer), sin(outer)
+    cone: vec4<f32>,
+};
+
+// View-space depth of the near plane of the given depth slice.
+fn slice_depth(params: ClusterParams, slice: u32) -> f32 {
+    let znear = params.depth.x;
+    let zfar = params.depth.y;
+    return znear * pow(zfar / znear, f32(slice) / f32(params.grid.z));
+}
+
+// The depth slice containing the given view-space depth.
+fn depth_slice(params: ClusterParams, view_depth: f32) -> u32 {
+    let znear = params.depth.x;
+    let zfar = params.depth.y;
+    let slice = log(max(view_depth, znear) / znear) * f32(params.grid.z) / log(zfar / znear);
+    return min(u32(max(slice, 0.0)), params.grid.z - 1u);
+}
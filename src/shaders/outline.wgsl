@@ -0,0 +1,88 @@
+// Screen-space selection outlines, drawn with a jump flood.
+//
+// `fs_seed` marks every selected pixel of the mask as its own nearest seed,
+// each `fs_flood` pass looks for closer seeds `step` pixels away, and
+// `fs_composite` turns the distance to the nearest seed into an outline.
+
+struct OutlineParams {
+    color: vec4<f32>,
+    overlay_color: vec4<f32>,
+    // Top left of the viewport in the render target, in pixels.
+    origin: vec2<f32>,
+    width: f32,
+    step: f32,
+};
+
+// Nonzero where a selected instance was drawn.
+@group(0) @binding(0)
+var mask: texture_2d<u32>;
+// The nearest selected pixel found so far, or `NO_SEED`.
+@group(0) @binding(1)
+var seeds: texture_2d<u32>;
+@group(0) @binding(2)
+var<uniform> params: OutlineParams;
+
+const NO_SEED: u32 = 65535u;
+
+// A triangle covering the whole viewport.
+@vertex
+fn vs_main(@builtin(vertex_index) index: u32) -> @builtin(position) vec4<f32> {
+    let uv = vec2<f32>(f32((index << 1u) & 2u), f32(index & 2u));
+    return vec4<f32>(uv * 2.0 - 1.0, 0.0, 1.0);
+}
+
+@fragment
+fn fs_seed(@builtin(position) position: vec4<f32>) -> @location(0) vec4<u32> {
+    let pixel = vec2<u32>(position.xy);
+    if textureLoad(mask, pixel, 0).x != 0u {
+        return vec4<u32>(pixel, 0u, 0u);
+    }
+    return vec4<u32>(NO_SEED, NO_SEED, 0u, 0u);
+}
+
+@fragment
+fn fs_flood(@builtin(position) position: vec4<f32>) -> @location(0) vec4<u32> {
+    let pixel = vec2<i32>(position.xy);
+    let size = vec2<i32>(textureDimensions(seeds));
+    let step = i32(params.step);
+    var nearest = vec2<u32>(NO_SEED, NO_SEED);
+    var nearest_distance = 1e20;
+    for (var y = -1; y <= 1; y++) {
+        for (var x = -1; x <= 1; x++) {
+            let neighbor = pixel + vec2<i32>(x, y) * step;
+            if any(neighbor < vec2<i32>(0)) || any(neighbor >= size) {
+                continue;
+            }
+            let seed = textureLoad(seeds, neighbor, 0).xy;
+            if seed.x == NO_SEED {
+                continue;
+            }
+            let offset = vec2<f32>(seed) - vec2<f32>(pixel);
+            let distance = dot(offset, offset);
+            if distance < nearest_distance {
+                nearest = seed;
+                nearest_distance = distance;
+            }
+        }
+    }
+    return vec4<u32>(nearest, 0u, 0u);
+}
+
+@fragment
+fn fs_composite(@builtin(position) position: vec4<f32>) -> @location(0) vec4<f32> {
+    let pixel = vec2<i32>(position.xy - params.origin);
+    if any(pixel < vec2<i32>(0)) || any(pixel >= vec2<i32>(textureDimensions(mask))) {
+        discard;
+    }
+    if textureLoad(mask, pixel, 0).x != 0u {
+        return params.overlay_color;
+    }
+    let seed = textureLoad(seeds, pixel, 0).xy;
+    if seed.x == NO_SEED {
+        discard;
+    }
+    // Anti-aliased over the outermost pixel.
+    let distance = length(vec2<f32>(seed) - vec2<f32>(pixel));
+    let coverage = clamp(params.width + 0.5 - distance, 0.0, 1.0);
+    return vec4<f32>(params.color.rgb, params.color.a * coverage);
+}
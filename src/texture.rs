@@ -1,5 +1,7 @@
 use crate::renderer::Resource;
+use anyhow::Context;
 use egui_wgpu::{self, wgpu, RenderState};
+use std::path::{Path, PathBuf};
 
 pub struct TextureResource {
     diffuse_texture: wgpu::Texture,
@@ -77,62 +79,40 @@ impl TextureResource {
 
     pub fn get_bind_group(&mut self, render_state: &RenderState) -> wgpu::BindGroupLayout {
         let device = &render_state.device;
-        let diffuse_texture_view = self
-            .diffuse_texture
-            .create_view(&wgpu::TextureViewDescriptor::default());
-        let diffuse_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
-            address_mode_u: wgpu::AddressMode::ClampToEdge,
-            address_mode_v: wgpu::AddressMode::ClampToEdge,
-            address_mode_w: wgpu::AddressMode::ClampToEdge,
-            mag_filter: wgpu::FilterMode::Linear,
-            min_filter: wgpu::FilterMode::Nearest,
-            mipmap_filter: wgpu::FilterMode::Nearest,
-            ..Default::default()
-        });
-
-        let texture_bind_group_layout =
-            device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
-                entries: &[
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 0,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        ty: wgpu::BindingType::Texture {
-                            multisampled: false,
-                            view_dimension: wgpu::TextureViewDimension::D2,
-                            sample_type: wgpu::TextureSampleType::Float { filterable: true },
-                        },
-                        count: None,
-                    },
-                    wgpu::BindGroupLayoutEntry {
-                        binding: 1,
-                        visibility: wgpu::ShaderStages::FRAGMENT,
-                        // This should match the filterable field of the
-                        // corresponding Texture entry above.
-                        ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
-                        count: None,
-                    },
-                ],
-                label: Some("texture_bind_group_layout"),
-            });
+        let texture_bind_group_layout = Self::bind_group_layout(device);
+        self.bind_group = Some(create_bind_group(
+            device,
+            &texture_bind_group_layout,
+            &self.diffuse_texture,
+        ));
+        texture_bind_group_layout
+    }
 
-        let diffuse_bind_group = device.create_bind_group(&wgpu::BindGroupDescriptor {
-            layout: &texture_bind_group_layout,
+    /// The layout of bind group 1, holding the texture drawn on meshes.
+    pub fn bind_group_layout(device: &wgpu::Device) -> wgpu::BindGroupLayout {
+        device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
             entries: &[
-                wgpu::BindGroupEntry {
+                wgpu::BindGroupLayoutEntry {
                     binding: 0,
-                    resource: wgpu::BindingResource::TextureView(&diffuse_texture_view),
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    ty: wgpu::BindingType::Texture {
+                        multisampled: false,
+                        view_dimension: wgpu::TextureViewDimension::D2,
+                        sample_type: wgpu::TextureSampleType::Float { filterable: true },
+                    },
+                    count: None,
                 },
-                wgpu::BindGroupEntry {
+                wgpu::BindGroupLayoutEntry {
                     binding: 1,
-                    resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
+                    visibility: wgpu::ShaderStages::FRAGMENT,
+                    // This should match the filterable field of the
+                    // corresponding Texture entry above.
+                    ty: wgpu::BindingType::Sampler(wgpu::SamplerBindingType::Filtering),
+                    count: None,
                 },
             ],
-            label: Some("diffuse_bind_group"),
-        });
-
-        self.bind_group = Some(diffuse_bind_group);
-
-        texture_bind_group_layout
+            label: Some("texture_bind_group_layout"),
+        })
     }
 
     pub fn paint<'rp>(&'rp self, render_pass: &mut wgpu::RenderPass<'rp>) {
@@ -143,3 +123,91 @@ impl TextureResource {
 }
 
 impl Resource for TextureResource {}
+
+/// Binds `texture` for sampling at group 1.
+fn create_bind_group(
+    device: &wgpu::Device,
+    layout: &wgpu::BindGroupLayout,
+    texture: &wgpu::Texture,
+) -> wgpu::BindGroup {
+    let diffuse_texture_view = texture.create_view(&wgpu::TextureViewDescriptor::default());
+    let diffuse_sampler = device.create_sampler(&wgpu::SamplerDescriptor {
+        address_mode_u: wgpu::AddressMode::ClampToEdge,
+        address_mode_v: wgpu::AddressMode::ClampToEdge,
+        address_mode_w: wgpu::AddressMode::ClampToEdge,
+        mag_filter: wgpu::FilterMode::Linear,
+        min_filter: wgpu::FilterMode::Nearest,
+        mipmap_filter: wgpu::FilterMode::Nearest,
+        ..Default::default()
+    });
+
+    device.create_bind_group(&wgpu::BindGroupDescriptor {
+        layout,
+        entries: &[
+            wgpu::BindGroupEntry {
+                binding: 0,
+                resource: wgpu::BindingResource::TextureView(&diffuse_texture_view),
+            },
+            wgpu::BindGroupEntry {
+                binding: 1,
+                resource: wgpu::BindingResource::Sampler(&diffuse_sampler),
+            },
+        ],
+        label: Some("diffuse_bind_group"),
+    })
+}
+
+/// An image loaded from disk for a [`Material`](crate::scene::Material).
+#[derive(Debug)]
+pub struct TextureAsset {
+    /// As written in the scene file.
+    pub path: PathBuf,
+    pub image: image::RgbaImage,
+}
+
+impl TextureAsset {
+    /// Loads `path`, resolved relative to `base_dir`.
+    pub fn load(base_dir: &Path, path: &Path) -> anyhow::Result<Self> {
+        let image = image::open(base_dir.join(path))
+            .with_context(|| format!("Failed to load texture {}", path.display()))?;
+        Ok(Self {
+            path: path.to_owned(),
+            image: image.to_rgba8(),
+        })
+    }
+
+    /// Uploads the image and binds it at group 1, in place of the default texture.
+    pub fn create_bind_group(
+        &self,
+        device: &wgpu::Device,
+        queue: &wgpu::Queue,
+        layout: &wgpu::BindGroupLayout,
+    ) -> wgpu::BindGroup {
+        let size = wgpu::Extent3d {
+            width: self.image.width(),
+            height: self.image.height(),
+            depth_or_array_layers: 1,
+        };
+        let texture = device.create_texture(&wgpu::TextureDescriptor {
+            size,
+            mip_level_count: 1,
+            sample_count: 1,
+            dimension: wgpu::TextureDimension::D2,
+            format: wgpu::TextureFormat::Rgba8UnormSrgb,
+            usage: wgpu::TextureUsages::TEXTURE_BINDING | wgpu::TextureUsages::COPY_DST,
+            label: Some("material_texture"),
+            view_formats: &[],
+        });
+        queue.write_texture(
+            texture.as_image_copy(),
+            &self.image,
+            wgpu::ImageDataLayout {
+                offset: 0,
+                bytes_per_row: Some(4 * size.width),
+                rows_per_image: Some(size.height),
+            },
+            size,
+        );
+        create_bind_group(device, layout, &texture)
+    }
+}
@@ -0,0 +1,375 @@
+//! A cube in a corner of the viewport that turns with the camera, showing
+//! which way the world faces, and that swings the camera round to look at
+//! whichever face, edge or corner of it is clicked.
+
+use cgmath::{InnerSpace, Matrix3, Quaternion, Vector3};
+
+use crate::{camera::Camera, gizmo::polygon_contains};
+
+/// Radius of the circle the cube fits in, in points.
+const RADIUS: f32 = 48.0;
+
+/// Distance from the cube to the edges of the viewport, in points.
+const MARGIN: f32 = 16.0;
+
+/// The width of the edge and corner regions around each face, as a fraction
+/// of the face.
+const EDGE: f32 = 0.22;
+
+/// How long the camera takes to turn to a new view, in seconds.
+const ANIMATION_SECONDS: f64 = 0.3;
+
+const HOVER_COLOR: egui::Color32 = egui::Color32::from_rgb(255, 210, 60);
+
+/// A face of the cube, with its outward normal and two axes across it.
+struct Face {
+    label: &'static str,
+    normal: [f32; 3],
+    u: [f32; 3],
+    v: [f32; 3],
+}
+
+/// The default camera looks at the front.
+const FACES: [Face; 6] = [
+    Face {
+        label: "Right",
+        normal: [1.0, 0.0, 0.0],
+        u: [0.0, 0.0, -1.0],
+        v: [0.0, 1.0, 0.0],
+    },
+    Face {
+        label: "Left",
+        normal: [-1.0, 0.0, 0.0],
+        u: [0.0, 0.0, 1.0],
+        v: [0.0, 1.0, 0.0],
+    },
+    Face {
+        label: "Top",
+        normal: [0.0, 1.0, 0.0],
+        u: [1.0, 0.0, 0.0],
+        v: [0.0, 0.0, -1.0],
+    },
+    Face {
+        label: "Bottom",
+        normal: [0.0, -1.0, 0.0],
+        u: [1.0, 0.0, 0.0],
+        v: [0.0, 0.0, 1.0],
+    },
+    Face {
+        label: "Front",
+        normal: [0.0, 0.0, 1.0],
+        u: [1.0, 0.0, 0.0],
+        v: [0.0, 1.0, 0.0],
+    },
+    Face {
+        label: "Back",
+        normal: [0.0, 0.0, -1.0],
+        u: [-1.0, 0.0, 0.0],
+        v: [0.0, 1.0, 0.0],
+    },
+];
+
+impl Face {
+    fn axes(&self) -> (Vector3<f32>, Vector3<f32>, Vector3<f32>) {
+        (self.normal.into(), self.u.into(), self.v.into())
+    }
+}
+
+/// A turn of the camera around its target that is under way.
+struct Animation {
+    start: f64,
+    from: Quaternion<f32>,
+    to: Quaternion<f32>,
+    /// The camera's up vector once the turn is over.
+    up: Vector3<f32>,
+}
+
+#[derive(Default)]
+pub struct ViewCube {
+    /// The direction, from the target, of the view under the pointer.
+    hovered: Option<Vector3<f32>>,
+    animation: Option<Animation>,
+}
+
+impl ViewCube {
+    /// Whether the pointer is over the cube, so clicks are meant for it
+    /// rather than the scene.
+    pub fn has_pointer(&self) -> bool {
+        self.hovered.is_some()
+    }
+
+    /// Draws the cube in the top right corner of `viewport`, turns the camera
+    /// when it is clicked, and moves on any turn under way.
+    ///
+    /// `response` is the viewport's, covering `viewport`.
+    pub fn show(
+        &mut self,
+        ui: &egui::Ui,
+        response: &egui::Response,
+        viewport: egui::Rect,
+        camera: &mut Camera,
+    ) {
+        self.animate(ui, camera);
+
+        // The canvas can run on under the side panels, so the cube goes in the
+        // corner of the part that is seen.
+        let visible = viewport.intersect(ui.clip_rect());
+        let center = egui::pos2(
+            visible.max.x - MARGIN - RADIUS,
+            visible.min.y + MARGIN + RADIUS,
+        );
+        let Some((right, up, back)) = basis(camera.eye - camera.target, camera.up) else {
+            self.hovered = None;
+            return;
+        };
+        let scale = RADIUS / 3.0f32.sqrt();
+        let project =
+            |point: Vector3<f32>| center + egui::vec2(point.dot(right), -point.dot(up)) * scale;
+
+        // Faces are split into a grid of three by three cells: the middle one
+        // looks at the face, the others at the edge or corner they touch.
+        let bounds = [-1.0, -1.0 + 2.0 * EDGE, 1.0 - 2.0 * EDGE, 1.0];
+        let pointer = response.hover_pos();
+        let painter = ui.painter_at(visible);
+        let mut cells = Vec::new();
+        let mut labels = Vec::new();
+        for face in &FACES {
+            let (normal, u, v) = face.axes();
+            let facing = normal.dot(back);
+            if facing <= 0.01 {
+                continue;
+            }
+            for i in 0..3 {
+                for j in 0..3 {
+                    let corners = [(i, j), (i + 1, j), (i + 1, j + 1), (i, j + 1)]
+                        .map(|(a, b)| project(normal + u * bounds[a] + v * bounds[b]));
+                    let direction = normal + u * (i as f32 - 1.0) + v * (j as f32 - 1.0);
+                    cells.push((corners, direction.normalize(), facing));
+                }
+            }
+            labels.push((face.label, project(normal), facing));
+        }
+
+        self.hovered = pointer.and_then(|pointer| {
+            cells
+                .iter()
+                .find(|(corners, ..)| polygon_contains(corners, pointer))
+                .map(|&(_, direction, _)| direction)
+        });
+
+        let outline = egui::Stroke::new(1.0, egui::Color32::from_gray(60));
+        for (corners, direction, facing) in &cells {
+            let fill = if self.hovered == Some(*direction) {
+                HOVER_COLOR
+            } else {
+                egui::Color32::from_gray((140.0 + 90.0 * facing) as u8)
+            };
+            painter.add(egui::Shape::convex_polygon(
+                corners.to_vec(),
+                fill,
+                egui::Stroke::NONE,
+            ));
+        }
+        for face in &FACES {
+            let (normal, u, v) = face.axes();
+            if normal.dot(back) > 0.01 {
+                let corners = [(-1.0, -1.0), (1.0, -1.0), (1.0, 1.0), (-1.0, 1.0)]
+                    .map(|(a, b)| project(normal + u * a + v * b));
+                painter.add(egui::Shape::closed_line(corners.to_vec(), outline));
+            }
+        }
+
+        for (label, position, facing) in labels {
+            // Faces seen at a glancing angle are too thin to label.
+            if facing > 0.3 {
+                painter.text(
+                    position,
+                    egui::Align2::CENTER_CENTER,
+                    label,
+                    egui::FontId::proportional(11.0),
+                    egui::Color32::from_gray(30),
+                );
+            }
+        }
+        if let Some(direction) = self.hovered.filter(|_| response.clicked()) {
+            self.look_from(ui, camera, direction);
+        }
+    }
+
+    /// Starts turning `camera` around its target to look back along
+    /// `direction`.
+    fn look_from(&mut self, ui: &egui::Ui, camera: &Camera, direction: Vector3<f32>) {
+        let up = view_up(direction);
+        let (Some(from), Some(to)) = (
+            orientation(camera.eye - camera.target, camera.up),
+            orientation(direction, up),
+        ) else {
+            return;
+        };
+        self.animation = Some(Animation {
+            start: ui.input(|i| i.time),
+            from,
+            to,
+            up,
+        });
+        ui.ctx().request_repaint();
+    }
+
+    fn animate(&mut self, ui: &egui::Ui, camera: &mut Camera) {
+        let Some(animation) = &self.animation else {
+            return;
+        };
+        let elapsed = ui.input(|i| i.time) - animation.start;
+        let t = (elapsed / ANIMATION_SECONDS).clamp(0.0, 1.0) as f32;
+        let eased = t * t * (3.0 - 2.0 * t);
+        let rotation = animation.from.slerp(animation.to, eased);
+        let distance = (camera.eye - camera.target).magnitude();
+        camera.eye = camera.target + rotation * Vector3::unit_z() * distance;
+        if t < 1.0 {
+            camera.up = rotation * Vector3::unit_y();
+            ui.ctx().request_repaint();
+        } else {
+            camera.up = animation.up;
+            self.animation = None;
+        }
+    }
+}
+
+/// The up vector of the camera once it looks back along `direction`.
+fn view_up(direction: Vector3<f32>) -> Vector3<f32> {
+    // Looking straight down or up, the front stays at the bottom or top of
+    // the screen.
+    if direction.x == 0.0 && direction.z == 0.0 {
+        Vector3::new(0.0, 0.0, -direction.y.signum())
+    } else {
+        Vector3::unit_y()
+    }
+}
+
+/// The right, up and backward unit vectors of a camera looking back along
+/// `back`, keeping `up` as near to up as it can.
+fn basis(
+    back: Vector3<f32>,
+    up: Vector3<f32>,
+) -> Option<(Vector3<f32>, Vector3<f32>, Vector3<f32>)> {
+    if back.magnitude2() <= f32::EPSILON {
+        return None;
+    }
+    let back = back.normalize();
+    let mut right = up.cross(back);
+    if right.magnitude2() <= 1e-8 {
+        // Looking along `up`, any right angle to it will do.
+        let other = if back.x.abs() < 0.9 {
+            Vector3::unit_x()
+        } else {
+            Vector3::unit_y()
+        };
+        right = other.cross(back);
+    }
+    let right = right.normalize();
+    Some((right, back.cross(right), back))
+}
+
+/// The rotation taking the z axis to `back` and the y axis as near to `up`
+/// as it goes.
+fn orientation(back: Vector3<f32>, up: Vector3<f32>) -> Option<Quaternion<f32>> {
+    let (right, up, back) = basis(back, up)?;
+    Some(Matrix3::from_cols(right, up, back).into())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn assert_near(actual: Vector3<f32>, expected: Vector3<f32>) {
+        assert!(
+            (actual - expected).magnitude() < 1e-5,
+            "{actual:?} != {expected:?}"
+        );
+    }
+
+    #[test]
+    fn faces_read_upright_when_looked_at() {
+        for face in &FACES {
+            let (normal, u, v) = face.axes();
+            assert_near(u.cross(v), normal);
+            let (right, up, back) = basis(normal, view_up(normal)).unwrap();
+            assert_near(right, u);
+            assert_near(up, v);
+            assert_near(back, normal);
+        }
+    }
+
+    #[test]
+    fn bases_are_right_handed_and_orthonormal() {
+        let views = [
+            Vector3::new(1.0, 1.0, 0.0),
+            Vector3::new(0.0, -1.0, 1.0),
+            Vector3::new(-1.0, 1.0, -1.0),
+            Vector3::new(0.0, 2.0, 0.0),
+        ];
+        for back in views {
+            // Looking straight down with the default up exercises the fallback.
+            for up in [Vector3::unit_y(), view_up(back)] {
+                let (right, up, unit_back) = basis(back, up).unwrap();
+                assert_near(unit_back, back.normalize());
+                assert_near(right.cross(up), unit_back);
+                assert!((right.magnitude() - 1.0).abs() < 1e-5);
+                assert!((up.magnitude() - 1.0).abs() < 1e-5);
+            }
+        }
+        assert_eq!(basis(Vector3::new(0.0, 0.0, 0.0), Vector3::unit_y()), None);
+    }
+
+    #[test]
+    fn edges_and_corners_keep_up_above_the_horizon() {
+        let (right, up, _) = basis(Vector3::new(1.0, 0.0, 1.0), Vector3::unit_y()).unwrap();
+        assert_near(right, Vector3::new(1.0, 0.0, -1.0).normalize());
+        assert_near(up, Vector3::unit_y());
+
+        let (right, up, _) = basis(Vector3::new(0.0, 1.0, 1.0), Vector3::unit_y()).unwrap();
+        assert_near(right, Vector3::unit_x());
+        assert_near(up, Vector3::new(0.0, 1.0, -1.0).normalize());
+
+        let (right, up, _) = basis(Vector3::new(1.0, 1.0, 1.0), Vector3::unit_y()).unwrap();
+        assert_near(right, Vector3::new(1.0, 0.0, -1.0).normalize());
+        assert!(up.y > 0.0);
+    }
+
+    #[test]
+    fn orientations_turn_the_default_view_onto_the_basis() {
+        for back in [
+            Vector3::unit_x(),
+            -Vector3::unit_y(),
+            -Vector3::unit_z(),
+            Vector3::new(-1.0, 0.0, 1.0),
+            Vector3::new(1.0, -1.0, -1.0),
+        ] {
+            let (right, up, back) = basis(back, view_up(back)).unwrap();
+            let rotation = orientation(back, view_up(back)).unwrap();
+            assert_near(rotation * Vector3::unit_x(), right);
+            assert_near(rotation * Vector3::unit_y(), up);
+            assert_near(rotation * Vector3::unit_z(), back);
+        }
+    }
+
+    #[test]
+    fn turns_start_and_end_on_their_views() {
+        let front = Vector3::unit_z();
+        for to in [
+            -Vector3::unit_z(),
+            Vector3::unit_y(),
+            -Vector3::unit_y(),
+            Vector3::new(1.0, 1.0, 1.0).normalize(),
+        ] {
+            let from_rotation = orientation(front, Vector3::unit_y()).unwrap();
+            let to_rotation = orientation(to, view_up(to)).unwrap();
+            let start = from_rotation.slerp(to_rotation, 0.0);
+            let end = from_rotation.slerp(to_rotation, 1.0);
+            assert_near(start * Vector3::unit_z(), front);
+            assert_near(start * Vector3::unit_y(), Vector3::unit_y());
+            assert_near(end * Vector3::unit_z(), to);
+            assert_near(end * Vector3::unit_y(), basis(to, view_up(to)).unwrap().1);
+        }
+    }
+}